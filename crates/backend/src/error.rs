@@ -0,0 +1,205 @@
+use axum::{
+    Json,
+    extract::{
+        FromRequest, FromRequestParts,
+        rejection::{JsonRejection, PathRejection, QueryRejection},
+    },
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use heurs_database::DbErr;
+use serde::Serialize;
+use serde_json::Value;
+
+/// API のエラー。`{"code": ..., "message": ..., "details": ...}` の JSON で返す
+///
+/// `code` はクライアントが分岐に使う機械向けの識別子 (`submission_not_found` など)、
+/// `message` は画面にそのまま出せる説明、`details` は関係する値 (ID など) で、なければ `null`。
+#[derive(Debug)]
+pub struct ApiError {
+    status: StatusCode,
+    code: &'static str,
+    message: String,
+    details: Option<Value>,
+}
+
+#[derive(Serialize)]
+struct ErrorBody<'a> {
+    code: &'a str,
+    message: &'a str,
+    details: &'a Option<Value>,
+}
+
+impl ApiError {
+    pub fn new(status: StatusCode, code: &'static str, message: impl Into<String>) -> Self {
+        ApiError {
+            status,
+            code,
+            message: message.into(),
+            details: None,
+        }
+    }
+
+    /// 400: リクエストの内容が正しくない
+    pub fn bad_request(code: &'static str, message: impl Into<String>) -> Self {
+        Self::new(StatusCode::BAD_REQUEST, code, message)
+    }
+
+    /// 404: 対象が存在しない
+    pub fn not_found(code: &'static str, message: impl Into<String>) -> Self {
+        Self::new(StatusCode::NOT_FOUND, code, message)
+    }
+
+    /// 409: リクエストは正しいが、現在の状態では受け付けられない
+    pub fn conflict(code: &'static str, message: impl Into<String>) -> Self {
+        Self::new(StatusCode::CONFLICT, code, message)
+    }
+
+    /// 500: サーバー側の問題
+    pub fn internal(code: &'static str, message: impl Into<String>) -> Self {
+        Self::new(StatusCode::INTERNAL_SERVER_ERROR, code, message)
+    }
+
+    pub fn status(&self) -> StatusCode {
+        self.status
+    }
+
+    pub fn with_details(mut self, details: impl Serialize) -> Self {
+        self.details = serde_json::to_value(details).ok();
+        self
+    }
+
+    /// 問題が見つからない
+    pub fn problem_not_found(name: &str) -> Self {
+        Self::not_found(
+            "problem_not_found",
+            format!("問題 {} が見つかりません", name),
+        )
+        .with_details(serde_json::json!({ "problem": name }))
+    }
+
+    /// 提出が見つからない (別の問題の提出も含む)
+    pub fn submission_not_found(id: i32) -> Self {
+        Self::not_found(
+            "submission_not_found",
+            format!("提出 {} が見つかりません", id),
+        )
+        .with_details(serde_json::json!({ "submission_id": id }))
+    }
+
+    /// テストケースのセットが見つからない
+    pub fn test_case_set_not_found(name: &str) -> Self {
+        Self::not_found(
+            "test_case_set_not_found",
+            format!("テストケースのセット {} が見つかりません", name),
+        )
+        .with_details(serde_json::json!({ "set": name }))
+    }
+}
+
+/// SQL や接続先などの内部の情報を含みうるので、中身はサーバーのログにだけ残す
+impl From<DbErr> for ApiError {
+    fn from(e: DbErr) -> Self {
+        eprintln!("Database error: {}", e);
+        Self::internal("database_error", "データベースエラーが発生しました")
+    }
+}
+
+impl From<JsonRejection> for ApiError {
+    fn from(rejection: JsonRejection) -> Self {
+        Self::new(
+            rejection.status(),
+            "invalid_body",
+            "リクエストの本文を読み取れません",
+        )
+        .with_details(rejection.body_text())
+    }
+}
+
+impl From<PathRejection> for ApiError {
+    fn from(rejection: PathRejection) -> Self {
+        Self::new(
+            rejection.status(),
+            "invalid_path",
+            "URL のパスが正しくありません",
+        )
+        .with_details(rejection.body_text())
+    }
+}
+
+impl From<QueryRejection> for ApiError {
+    fn from(rejection: QueryRejection) -> Self {
+        Self::new(
+            rejection.status(),
+            "invalid_query",
+            "クエリパラメータが正しくありません",
+        )
+        .with_details(rejection.body_text())
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        // サーバー側の問題はクライアントに返すだけでなくログにも残す
+        if self.status.is_server_error() {
+            eprintln!(
+                "{} {}: {}{}",
+                self.status.as_u16(),
+                self.code,
+                self.message,
+                self.details
+                    .as_ref()
+                    .map(|d| format!(" ({})", d))
+                    .unwrap_or_default()
+            );
+        }
+        let body = ErrorBody {
+            code: self.code,
+            message: &self.message,
+            details: &self.details,
+        };
+        (self.status, Json(body)).into_response()
+    }
+}
+
+/// `axum::Json` と同じだが、読み取れない本文を [`ApiError`] で返す
+#[derive(FromRequest)]
+#[from_request(via(axum::Json), rejection(ApiError))]
+pub struct ApiJson<T>(pub T);
+
+/// `axum::extract::Path` と同じだが、パースできないパスを [`ApiError`] で返す
+#[derive(FromRequestParts)]
+#[from_request(via(axum::extract::Path), rejection(ApiError))]
+pub struct ApiPath<T>(pub T);
+
+/// `axum::extract::Query` と同じだが、パースできないクエリを [`ApiError`] で返す
+#[derive(FromRequestParts)]
+#[from_request(via(axum::extract::Query), rejection(ApiError))]
+pub struct ApiQuery<T>(pub T);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn database_errors_do_not_leak_details() {
+        let error = ApiError::from(DbErr::Custom(
+            "SELECT * FROM submissions: no such table".to_string(),
+        ));
+        let response = error.into_response();
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(
+            body,
+            serde_json::json!({
+                "code": "database_error",
+                "message": "データベースエラーが発生しました",
+                "details": null,
+            })
+        );
+    }
+}
@@ -1,3 +1,29 @@
+pub mod problems;
+pub mod remote;
 pub mod run;
 pub mod submissions;
 pub mod test_cases;
+
+use crate::error::ApiError;
+use heurs_database::{
+    DatabaseConnection, ProblemModel, ProblemRepository, SubmissionModel, SubmissionRepository,
+};
+
+/// 名前で問題を引く。なければ 404
+async fn find_problem(db: &DatabaseConnection, name: &str) -> Result<ProblemModel, ApiError> {
+    ProblemRepository::find_by_name(db, name)
+        .await?
+        .ok_or_else(|| ApiError::problem_not_found(name))
+}
+
+/// 問題の提出を引く。別の問題の提出は存在しないものとして 404 にする
+async fn find_submission(
+    db: &DatabaseConnection,
+    problem: &ProblemModel,
+    id: i32,
+) -> Result<SubmissionModel, ApiError> {
+    SubmissionRepository::find_by_id(db, id)
+        .await?
+        .filter(|s| s.problem_id == problem.id)
+        .ok_or_else(|| ApiError::submission_not_found(id))
+}
@@ -0,0 +1,25 @@
+use crate::error::ApiError;
+use crate::models::problems::{ProblemMeta, ProblemsResponse};
+use crate::state::AppState;
+use axum::{Json, Router, extract::State, routing::get};
+use heurs_database::{DatabaseConnection, ProblemRepository};
+
+pub fn problem_routes() -> Router<AppState> {
+    Router::new().route("/api/problems", get(get_problems))
+}
+
+async fn get_problems(
+    State(db): State<DatabaseConnection>,
+) -> Result<Json<ProblemsResponse>, ApiError> {
+    let problems = ProblemRepository::find_all(&db).await?;
+    Ok(Json(ProblemsResponse {
+        problems: problems
+            .into_iter()
+            .map(|p| ProblemMeta {
+                id: p.id,
+                name: p.name,
+                created_at: p.created_at,
+            })
+            .collect(),
+    }))
+}
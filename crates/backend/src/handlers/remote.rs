@@ -0,0 +1,671 @@
+use crate::error::{ApiError, ApiJson, ApiPath, ApiQuery};
+use crate::state::AppState;
+use axum::{
+    Json, Router,
+    extract::{Request, State},
+    http::{StatusCode, header::AUTHORIZATION},
+    middleware::{self, Next},
+    response::Response,
+    routing::{get, post},
+};
+use heurs_core::ExecutionResult;
+use heurs_core::remote::{
+    BatchResults, LeaseResponse, RemoteBatch, RemoteCase, RemoteJobCreated, RemoteJobRequest,
+    RemoteJobResults, WorkerInfo, WorkerRegistered, WorkerRegistration, is_plain_file_name,
+};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+/// 貸し出したバッチがこの時間 (+ ケースごとのタイムアウト) 以内に返ってこなければ再度キューに戻す
+const LEASE_GRACE: Duration = Duration::from_secs(60);
+
+/// 結果の問い合わせがこの時間途絶えたジョブは、投入した RemoteRunner がいなくなったものとして捨てる
+/// (RemoteRunner は 1 秒ごとに問い合わせる)
+const JOB_TTL: Duration = Duration::from_secs(300);
+
+/// RemoteRunner から投入されたジョブとワーカーの状態をメモリ上で管理するキュー
+///
+/// 永続化はしないので、バックエンドを再起動すると実行中のジョブは失われる
+/// (RemoteRunner 側の問い合わせが 404 になって失敗する)。
+#[derive(Default)]
+pub struct RemoteQueue {
+    jobs: HashMap<String, RemoteJob>,
+    /// まだ貸し出していないバッチ (投入順)
+    pending: VecDeque<PendingBatch>,
+    /// 貸し出し中のバッチ: batch_id -> 貸し出し情報
+    leases: HashMap<String, Lease>,
+    workers: HashMap<String, Worker>,
+}
+
+struct RemoteJob {
+    /// 投入時のリクエスト。ケースの入力はバッチ側に移すので `cases` は空。
+    request: RemoteJobRequest,
+    /// バックエンドの設定ファイルのコマンド (リクエストでは受け取らない)
+    compile_cmd: String,
+    exec_cmd: String,
+    total: usize,
+    results: Vec<ExecutionResult>,
+    /// 受け取った結果の (test_case_id, repeat)。同じ実行の結果を二重に数えないため
+    received: HashSet<(u32, u32)>,
+    /// 投入した側が最後に結果を問い合わせた時刻
+    last_polled: Instant,
+}
+
+struct PendingBatch {
+    job_id: String,
+    cases: Vec<RemoteCase>,
+}
+
+struct Lease {
+    batch: PendingBatch,
+    worker_id: String,
+    expires_at: Instant,
+}
+
+struct Worker {
+    name: String,
+    parallel: u32,
+    last_seen: Instant,
+}
+
+impl RemoteQueue {
+    /// 期限切れの貸し出しをキューの先頭に戻し (ワーカーが落ちた場合など)、
+    /// 問い合わせの途絶えたジョブを捨てる (`heurs run` が中断された場合など)
+    fn expire(&mut self, now: Instant) {
+        let expired: Vec<String> = self
+            .leases
+            .iter()
+            .filter(|(_, lease)| lease.expires_at <= now)
+            .map(|(batch_id, _)| batch_id.clone())
+            .collect();
+
+        for batch_id in expired {
+            if let Some(lease) = self.leases.remove(&batch_id) {
+                eprintln!(
+                    "Lease {} on worker {} expired; requeueing",
+                    batch_id, lease.worker_id
+                );
+                self.pending.push_front(lease.batch);
+            }
+        }
+
+        let abandoned: Vec<String> = self
+            .jobs
+            .iter()
+            .filter(|(_, job)| now.saturating_duration_since(job.last_polled) >= JOB_TTL)
+            .map(|(job_id, _)| job_id.clone())
+            .collect();
+        for job_id in abandoned {
+            eprintln!(
+                "Remote job {} was abandoned by its submitter; removing",
+                job_id
+            );
+            self.remove_job(&job_id);
+        }
+    }
+
+    /// ジョブを消す。まだ誰も実行していないバッチは捨て、貸し出し中のものは結果が届いた時点で捨てる
+    fn remove_job(&mut self, job_id: &str) -> bool {
+        if self.jobs.remove(job_id).is_none() {
+            return false;
+        }
+        self.pending.retain(|batch| batch.job_id != job_id);
+        true
+    }
+}
+
+pub type SharedRemoteQueue = Arc<Mutex<RemoteQueue>>;
+
+/// ジョブとワーカーの API。どれも共有トークンがなければ受け付けない
+pub fn remote_routes(state: AppState) -> Router<AppState> {
+    Router::new()
+        .route("/api/remote/jobs", post(create_job))
+        .route("/api/remote/jobs/{id}", axum::routing::delete(delete_job))
+        .route("/api/remote/jobs/{id}/results", get(get_job_results))
+        .route("/api/workers", get(get_workers))
+        .route("/api/workers/register", post(register_worker))
+        .route("/api/workers/{id}/lease", post(lease_batch))
+        .route(
+            "/api/workers/{id}/batches/{batch_id}/results",
+            post(report_batch),
+        )
+        .route_layer(middleware::from_fn_with_state(state, require_token))
+}
+
+/// `Authorization: Bearer <token>` がバックエンドのトークンと一致するリクエストだけを通す。
+/// ワーカーは受け取ったコマンドをそのまま実行するので、トークンが設定されていなければ API ごと閉じる。
+/// 提出の削除など、閲覧以外の API にも使う
+pub(crate) async fn require_token(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Result<Response, ApiError> {
+    let Some(expected) = state.remote_token.as_deref() else {
+        return Err(ApiError::new(
+            StatusCode::SERVICE_UNAVAILABLE,
+            "token_not_configured",
+            "共有トークンが設定されていないため、この API は無効です",
+        ));
+    };
+    let given = request
+        .headers()
+        .get(AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "));
+    if !given.is_some_and(|given| constant_time_eq(given.as_bytes(), expected.as_bytes())) {
+        return Err(ApiError::new(
+            StatusCode::UNAUTHORIZED,
+            "invalid_token",
+            "共有トークンがないか、正しくありません",
+        ));
+    }
+    Ok(next.run(request).await)
+}
+
+/// 一致しない位置で比較を打ち切らない (応答時間からトークンを推測されないため)
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+async fn create_job(
+    State(state): State<AppState>,
+    ApiJson(req): ApiJson<RemoteJobRequest>,
+) -> Result<(StatusCode, Json<RemoteJobCreated>), ApiError> {
+    // ワーカーはこの名前で作業ディレクトリにソースを書く
+    if !is_plain_file_name(&req.source_name) {
+        return Err(ApiError::bad_request(
+            "invalid_source_name",
+            format!(
+                "ソースのファイル名 {} にはディレクトリを含められません",
+                req.source_name
+            ),
+        )
+        .with_details(serde_json::json!({ "source_name": req.source_name })));
+    }
+    // コマンドはリクエストではなくバックエンドの設定ファイルから取る
+    let Some(config) = state.config.as_deref() else {
+        return Err(ApiError::new(
+            StatusCode::SERVICE_UNAVAILABLE,
+            "config_missing",
+            "設定ファイルがないため実行できません",
+        ));
+    };
+
+    let job_id = Uuid::new_v4().to_string();
+    let mut queue = state.remote.lock().unwrap();
+
+    // ケースをバッチに分けてキューに積む
+    let batch_size = req.batch_size.max(1) as usize;
+    for chunk in req.cases.chunks(batch_size) {
+        queue.pending.push_back(PendingBatch {
+            job_id: job_id.clone(),
+            cases: chunk.to_vec(),
+        });
+    }
+
+    let total = req.cases.len() * req.repeat.max(1) as usize;
+    queue.jobs.insert(
+        job_id.clone(),
+        RemoteJob {
+            request: RemoteJobRequest {
+                cases: Vec::new(),
+                ..req
+            },
+            compile_cmd: config.execution.compile_cmd.clone(),
+            exec_cmd: config.execution.exec_cmd.clone(),
+            total,
+            results: Vec::with_capacity(total),
+            received: HashSet::with_capacity(total),
+            last_polled: Instant::now(),
+        },
+    );
+
+    Ok((StatusCode::CREATED, Json(RemoteJobCreated { job_id })))
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct ResultsParams {
+    #[serde(default)]
+    after: usize,
+}
+
+async fn get_job_results(
+    State(queue): State<SharedRemoteQueue>,
+    ApiPath(id): ApiPath<String>,
+    ApiQuery(params): ApiQuery<ResultsParams>,
+) -> Result<Json<RemoteJobResults>, ApiError> {
+    let mut queue = queue.lock().unwrap();
+    queue.expire(Instant::now());
+
+    let job = queue.jobs.get_mut(&id).ok_or_else(|| job_not_found(&id))?;
+    job.last_polled = Instant::now();
+
+    Ok(Json(RemoteJobResults {
+        results: job.results.iter().skip(params.after).cloned().collect(),
+        total: job.total,
+        done: job.results.len() >= job.total,
+    }))
+}
+
+async fn delete_job(
+    State(queue): State<SharedRemoteQueue>,
+    ApiPath(id): ApiPath<String>,
+) -> Result<StatusCode, ApiError> {
+    if !queue.lock().unwrap().remove_job(&id) {
+        return Err(job_not_found(&id));
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// バックエンドの再起動などでジョブが失われた
+fn job_not_found(id: &str) -> ApiError {
+    ApiError::not_found("job_not_found", format!("ジョブ {} が見つかりません", id))
+        .with_details(serde_json::json!({ "job_id": id }))
+}
+
+async fn register_worker(
+    State(queue): State<SharedRemoteQueue>,
+    ApiJson(req): ApiJson<WorkerRegistration>,
+) -> (StatusCode, Json<WorkerRegistered>) {
+    let worker_id = Uuid::new_v4().to_string();
+    println!(
+        "Worker '{}' registered as {} (parallel: {})",
+        req.name, worker_id, req.parallel
+    );
+
+    queue.lock().unwrap().workers.insert(
+        worker_id.clone(),
+        Worker {
+            name: req.name,
+            parallel: req.parallel,
+            last_seen: Instant::now(),
+        },
+    );
+
+    (StatusCode::CREATED, Json(WorkerRegistered { worker_id }))
+}
+
+async fn get_workers(State(queue): State<SharedRemoteQueue>) -> Json<Vec<WorkerInfo>> {
+    let queue = queue.lock().unwrap();
+    let workers = queue
+        .workers
+        .iter()
+        .map(|(worker_id, worker)| WorkerInfo {
+            worker_id: worker_id.clone(),
+            name: worker.name.clone(),
+            parallel: worker.parallel,
+            last_seen_secs: worker.last_seen.elapsed().as_secs(),
+            leased_batches: queue
+                .leases
+                .values()
+                .filter(|lease| &lease.worker_id == worker_id)
+                .count(),
+        })
+        .collect();
+    Json(workers)
+}
+
+async fn lease_batch(
+    State(queue): State<SharedRemoteQueue>,
+    ApiPath(worker_id): ApiPath<String>,
+) -> Result<Json<LeaseResponse>, ApiError> {
+    let mut queue = queue.lock().unwrap();
+    queue.expire(Instant::now());
+
+    // 未登録のワーカーには 404 を返し、登録し直してもらう
+    let worker = queue.workers.get_mut(&worker_id).ok_or_else(|| {
+        ApiError::not_found(
+            "worker_not_found",
+            format!("ワーカー {} は登録されていません", worker_id),
+        )
+        .with_details(serde_json::json!({ "worker_id": worker_id }))
+    })?;
+    worker.last_seen = Instant::now();
+
+    // 削除済みジョブのバッチは読み飛ばす
+    while let Some(batch) = queue.pending.pop_front() {
+        let Some(job) = queue.jobs.get(&batch.job_id) else {
+            continue;
+        };
+
+        let batch_id = Uuid::new_v4().to_string();
+        let remote_batch = RemoteBatch {
+            batch_id: batch_id.clone(),
+            job_id: batch.job_id.clone(),
+            source_code: job.request.source_code.clone(),
+            source_name: job.request.source_name.clone(),
+            bundle: job.request.bundle.clone(),
+            compile_cmd: job.compile_cmd.clone(),
+            exec_cmd: job.exec_cmd.clone(),
+            timeout: job.request.timeout,
+            repeat: job.request.repeat,
+            cases: batch.cases.clone(),
+        };
+
+        let runs = batch.cases.len() as u64 * job.request.repeat.max(1) as u64;
+        let expires_at =
+            Instant::now() + LEASE_GRACE + Duration::from_secs(job.request.timeout as u64 * runs);
+        queue.leases.insert(
+            batch_id,
+            Lease {
+                batch,
+                worker_id,
+                expires_at,
+            },
+        );
+
+        return Ok(Json(LeaseResponse {
+            batch: Some(remote_batch),
+        }));
+    }
+
+    Ok(Json(LeaseResponse { batch: None }))
+}
+
+async fn report_batch(
+    State(queue): State<SharedRemoteQueue>,
+    ApiPath((worker_id, batch_id)): ApiPath<(String, String)>,
+    ApiJson(req): ApiJson<BatchResults>,
+) -> Result<StatusCode, ApiError> {
+    let mut queue = queue.lock().unwrap();
+    // 期限切れの貸し出しは先に回収しておき、遅れた報告として扱う
+    queue.expire(Instant::now());
+
+    // 期限切れで別のワーカーに回したバッチの遅れた報告は受け取らない
+    let lease_expired = || {
+        ApiError::conflict(
+            "lease_expired",
+            format!(
+                "バッチ {} は貸し出し期限が切れたため別のワーカーに回しました",
+                batch_id
+            ),
+        )
+        .with_details(serde_json::json!({ "batch_id": batch_id }))
+    };
+    match queue.leases.get(&batch_id) {
+        None => return Err(lease_expired()),
+        Some(lease) if lease.worker_id != worker_id => {
+            return Err(ApiError::conflict(
+                "lease_not_held",
+                format!(
+                    "バッチ {} はワーカー {} に貸し出していません",
+                    batch_id, worker_id
+                ),
+            )
+            .with_details(serde_json::json!({ "batch_id": batch_id, "worker_id": worker_id })));
+        }
+        Some(_) => {}
+    }
+    let lease = queue.leases.remove(&batch_id).ok_or_else(lease_expired)?;
+
+    let Some(job) = queue.jobs.get_mut(&lease.batch.job_id) else {
+        // ジョブが削除済みなら結果は捨てる
+        return Ok(StatusCode::NO_CONTENT);
+    };
+
+    // 貸し出したケースと実行回の結果だけを、1 回ずつ受け取る
+    let repeat = job.request.repeat.max(1);
+    let expected: HashSet<(u32, u32)> = lease
+        .batch
+        .cases
+        .iter()
+        .flat_map(|case| (0..repeat).map(move |r| (case.test_case_id as u32, r)))
+        .collect();
+    let mut dropped = 0;
+    for result in req.results {
+        let key = (result.test_case_id, result.repeat);
+        if expected.contains(&key) && job.received.insert(key) {
+            job.results.push(result);
+        } else {
+            dropped += 1;
+        }
+    }
+    if dropped > 0 {
+        eprintln!(
+            "Dropped {} unexpected or duplicate results in batch {} from worker {}",
+            dropped, batch_id, worker_id
+        );
+    }
+
+    // 返ってこなかった実行は失敗として記録し、ジョブが終わらなくならないようにする
+    let mut missing: Vec<&(u32, u32)> = expected
+        .iter()
+        .filter(|key| !job.received.contains(key))
+        .collect();
+    missing.sort();
+    for &(test_case_id, repeat) in missing {
+        job.received.insert((test_case_id, repeat));
+        job.results.push(ExecutionResult {
+            test_case_id,
+            repeat,
+            success: false,
+            stdout: String::new(),
+            stderr: format!("Worker {} returned no result", worker_id),
+            execution_time_ms: 0,
+            score: 0,
+        });
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use heurs_core::Config;
+    use heurs_core::config::Execution;
+    use heurs_database::DatabaseManager;
+
+    async fn state() -> AppState {
+        let (db, _) = DatabaseManager::connect_and_migrate("sqlite::memory:")
+            .await
+            .unwrap();
+        let config = Config {
+            problem: None,
+            database: Default::default(),
+            execution: Execution {
+                compile_cmd: "g++ {{src}}".to_string(),
+                exec_cmd: "./a.out".to_string(),
+            },
+            aws: Default::default(),
+            remote: Default::default(),
+            container: Default::default(),
+            sandbox: Default::default(),
+            cpu: Default::default(),
+        };
+        AppState {
+            db,
+            config: Some(Arc::new(config)),
+            runners: Default::default(),
+            remote: Default::default(),
+            remote_token: Some(Arc::from("secret")),
+        }
+    }
+
+    async fn submit(state: &AppState, cases: i32, batch_size: u32) -> String {
+        let request = RemoteJobRequest {
+            source_code: "int main() {}".to_string(),
+            source_name: "main.cpp".to_string(),
+            bundle: None,
+            timeout: 1,
+            repeat: 1,
+            batch_size,
+            cases: (1..=cases)
+                .map(|id| RemoteCase {
+                    test_case_id: id,
+                    filename: format!("{:04}.txt", id),
+                    input: String::new(),
+                })
+                .collect(),
+        };
+        let (status, Json(created)) = create_job(State(state.clone()), ApiJson(request))
+            .await
+            .unwrap();
+        assert_eq!(status, StatusCode::CREATED);
+        created.job_id
+    }
+
+    async fn register(queue: &SharedRemoteQueue, name: &str) -> String {
+        let registration = WorkerRegistration {
+            name: name.to_string(),
+            parallel: 1,
+        };
+        let (_, Json(registered)) =
+            register_worker(State(queue.clone()), ApiJson(registration)).await;
+        registered.worker_id
+    }
+
+    async fn lease(queue: &SharedRemoteQueue, worker_id: &str) -> Option<RemoteBatch> {
+        let Json(response) = lease_batch(State(queue.clone()), ApiPath(worker_id.to_string()))
+            .await
+            .unwrap();
+        response.batch
+    }
+
+    async fn report(
+        queue: &SharedRemoteQueue,
+        worker_id: &str,
+        batch_id: &str,
+        results: Vec<ExecutionResult>,
+    ) -> Result<StatusCode, ApiError> {
+        report_batch(
+            State(queue.clone()),
+            ApiPath((worker_id.to_string(), batch_id.to_string())),
+            ApiJson(BatchResults { results }),
+        )
+        .await
+    }
+
+    async fn job_results(queue: &SharedRemoteQueue, job_id: &str) -> RemoteJobResults {
+        let Json(results) = get_job_results(
+            State(queue.clone()),
+            ApiPath(job_id.to_string()),
+            ApiQuery(ResultsParams { after: 0 }),
+        )
+        .await
+        .unwrap();
+        results
+    }
+
+    fn result(test_case_id: u32, repeat: u32) -> ExecutionResult {
+        ExecutionResult {
+            test_case_id,
+            repeat,
+            success: true,
+            stdout: String::new(),
+            stderr: String::new(),
+            execution_time_ms: 1,
+            score: 100,
+        }
+    }
+
+    fn case_ids(batch: &RemoteBatch) -> Vec<i32> {
+        batch.cases.iter().map(|case| case.test_case_id).collect()
+    }
+
+    #[tokio::test]
+    async fn leases_hand_out_each_batch_once() {
+        let state = state().await;
+        let queue = &state.remote;
+        submit(&state, 3, 2).await;
+        let worker = register(queue, "a").await;
+
+        let first = lease(queue, &worker).await.unwrap();
+        assert_eq!(case_ids(&first), [1, 2]);
+        // コマンドはリクエストではなくバックエンドの設定から渡す
+        assert_eq!(first.compile_cmd, "g++ {{src}}");
+        assert_eq!(case_ids(&lease(queue, &worker).await.unwrap()), [3]);
+        assert!(lease(queue, &worker).await.is_none());
+
+        // 未登録のワーカーには貸さない
+        let error = lease_batch(State(queue.clone()), ApiPath("unknown".to_string()))
+            .await
+            .unwrap_err();
+        assert_eq!(error.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn expired_leases_are_requeued_for_other_workers() {
+        let state = state().await;
+        let queue = &state.remote;
+        let job_id = submit(&state, 1, 1).await;
+        let (slow, fast) = (register(queue, "slow").await, register(queue, "fast").await);
+
+        let stale = lease(queue, &slow).await.unwrap();
+        assert!(lease(queue, &fast).await.is_none());
+        queue
+            .lock()
+            .unwrap()
+            .expire(Instant::now() + LEASE_GRACE + Duration::from_secs(2));
+
+        let retried = lease(queue, &fast).await.unwrap();
+        assert_eq!(case_ids(&retried), case_ids(&stale));
+        // 期限切れになった貸し出しの遅れた報告は受け取らない
+        let error = report(queue, &slow, &stale.batch_id, vec![result(1, 0)])
+            .await
+            .unwrap_err();
+        assert_eq!(error.status(), StatusCode::CONFLICT);
+
+        report(queue, &fast, &retried.batch_id, vec![result(1, 0)])
+            .await
+            .unwrap();
+        let results = job_results(queue, &job_id).await;
+        assert!(results.done);
+        assert_eq!(results.results.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn reports_keep_only_the_leased_runs() {
+        let state = state().await;
+        let queue = &state.remote;
+        let job_id = submit(&state, 2, 2).await;
+        let (worker, other) = (register(queue, "a").await, register(queue, "b").await);
+        let batch = lease(queue, &worker).await.unwrap();
+
+        // 借りていないワーカーからの報告は受け取らない
+        let error = report(queue, &other, &batch.batch_id, vec![result(1, 0)])
+            .await
+            .unwrap_err();
+        assert_eq!(error.status(), StatusCode::CONFLICT);
+
+        // 重複と貸し出していないケースは捨て、返ってこなかったケース 2 は失敗にする
+        report(
+            queue,
+            &worker,
+            &batch.batch_id,
+            vec![result(1, 0), result(1, 0), result(99, 0), result(1, 1)],
+        )
+        .await
+        .unwrap();
+        let results = job_results(queue, &job_id).await;
+        assert!(results.done);
+        assert_eq!(results.results.len(), 2);
+        assert!(results.results[0].success);
+        let missing = &results.results[1];
+        assert_eq!((missing.test_case_id, missing.success), (2, false));
+    }
+
+    #[tokio::test]
+    async fn abandoned_jobs_are_removed() {
+        let state = state().await;
+        let queue = &state.remote;
+        let abandoned = submit(&state, 2, 1).await;
+        let worker = register(queue, "a").await;
+
+        queue
+            .lock()
+            .unwrap()
+            .expire(Instant::now() + JOB_TTL + Duration::from_secs(1));
+
+        assert!(lease(queue, &worker).await.is_none());
+        let error = get_job_results(
+            State(queue.clone()),
+            ApiPath(abandoned),
+            ApiQuery(ResultsParams { after: 0 }),
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(error.status(), StatusCode::NOT_FOUND);
+    }
+}
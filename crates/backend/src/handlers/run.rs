@@ -1,136 +1,166 @@
+use super::find_problem;
+use crate::error::{ApiError, ApiJson, ApiPath};
 use crate::models::run::{RunRequest, RunResponse};
-use axum::{Json, Router, http::StatusCode, routing::post};
-use heurs_core::{LocalRunner, Runner, load_config};
+use crate::state::{AppState, RunnerLookupError};
+use axum::{Json, Router, extract::State, http::StatusCode, routing::post};
+use heurs_core::{Bundle, Source, Workspace};
 use heurs_database::{
-    DatabaseManager, ExecutionResultRepository, SubmissionRepository, TestCaseRepository,
+    SubmissionMetadata, SubmissionRepository, TestCaseRepository, TestCaseSetRepository,
 };
-use std::fs::File;
-use std::io::Write;
-use std::path::PathBuf;
 
-pub fn run_routes() -> Router {
-    Router::new().route("/api/run", post(run_code))
+pub fn run_routes() -> Router<AppState> {
+    Router::new().route("/api/problems/{problem}/run", post(run_code))
 }
 
+/// ブラウザからの提出を保存するファイル名 (`{{src}}` に入る)
+const WEB_SOURCE_NAME: &str = "source.cpp";
+
 #[axum::debug_handler]
-async fn run_code(Json(req): Json<RunRequest>) -> (StatusCode, Json<RunResponse>) {
-    // データベース接続を確立
-    let db = match DatabaseManager::connect("sqlite://heurs.db").await {
-        Ok(db) => db,
-        Err(e) => {
-            return (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(RunResponse {
-                    success: false,
-                    result: String::new(),
-                    error: Some(format!("データベース接続エラー: {}", e)),
-                    submission_id: None,
-                }),
-            );
-        }
+async fn run_code(
+    State(state): State<AppState>,
+    ApiPath(problem): ApiPath<String>,
+    ApiJson(req): ApiJson<RunRequest>,
+) -> Result<Json<RunResponse>, ApiError> {
+    let db = &state.db;
+
+    // 設定ファイルがなければコンパイル・実行のコマンドが分からない
+    let Some(config) = state.config.clone() else {
+        return Err(ApiError::new(
+            StatusCode::SERVICE_UNAVAILABLE,
+            "config_missing",
+            "設定ファイルがないため実行できません",
+        ));
     };
+    let env = req.env.clone().unwrap_or_else(|| "local".to_string());
+    let runner = state.runners.get(&env).map_err(|e| match e {
+        RunnerLookupError::Unknown => ApiError::bad_request(
+            "unknown_environment",
+            format!("不明な実行環境です: {}", env),
+        )
+        .with_details(serde_json::json!({ "env": env })),
+        RunnerLookupError::NotAllowed => ApiError::new(
+            StatusCode::FORBIDDEN,
+            "environment_not_allowed",
+            format!("実行環境 {} はこのサーバーでは使えません", env),
+        )
+        .with_details(serde_json::json!({ "env": env, "allowed": state.runners.names() })),
+    })?;
 
-    let config = load_config(&PathBuf::from("heurs.toml")).unwrap();
+    // 問題は CLI で登録したものだけを受け付ける (テストケースがないと実行できないため)
+    let problem = find_problem(db, &problem).await?;
 
-    // submissionをデータベースに保存
-    let submission = match SubmissionRepository::create(&db, req.source_code.clone()).await {
-        Ok(submission) => submission,
-        Err(e) => {
-            return (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(RunResponse {
-                    success: false,
-                    result: String::new(),
-                    error: Some(format!("Submission保存エラー: {}", e)),
-                    submission_id: None,
-                }),
-            );
+    // 指定がなければ現在のセットで実行する
+    let set = match TestCaseSetRepository::resolve(db, problem.id, req.set.as_deref()).await? {
+        Some(set) => set,
+        None => {
+            return Err(match &req.set {
+                Some(name) => ApiError::test_case_set_not_found(name),
+                // セットを作るか選び直すまでは実行できない
+                None => ApiError::conflict(
+                    "no_active_test_case_set",
+                    format!(
+                        "問題 {} に現在のテストケースのセットがありません",
+                        problem.name
+                    ),
+                ),
+            });
         }
     };
 
-    let tmp_path = PathBuf::from("/tmp/source.cpp");
-    match File::create(&tmp_path).and_then(|mut f| f.write_all(req.source_code.as_bytes())) {
-        Ok(_) => {}
-        Err(e) => {
-            return (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(RunResponse {
-                    success: false,
-                    result: String::new(),
-                    error: Some(format!("ファイル作成エラー: {}", e)),
-                    submission_id: Some(submission.id),
-                }),
-            );
-        }
+    // 親は同じ問題の提出でなければならない
+    if let Some(parent_id) = req.parent_id
+        && !SubmissionRepository::is_valid_parent(db, problem.id, None, parent_id).await?
+    {
+        return Err(ApiError::bad_request(
+            "invalid_parent",
+            format!("親の提出 {} が見つかりません", parent_id),
+        )
+        .with_details(serde_json::json!({ "parent_id": parent_id })));
     }
 
-    let test_cases = TestCaseRepository::find_limit(&db, req.cases as u64)
-        .await
-        .unwrap();
+    // 同時に来たリクエストがソースや実行ファイルを上書きし合わないよう、リクエストごとの
+    // ワークスペースに書き出してバンドルとして渡す (実行器もバンドルは自分のワークスペースに
+    // 展開してコンパイル・実行する)
+    let source = prepare_source(&req.source_code).map_err(|e| {
+        ApiError::internal("source_write_failed", "ソースコードを書き出せません")
+            .with_details(e.to_string())
+    })?;
 
-    let runner = LocalRunner::new();
+    let environment = heurs_core::environment::capture(
+        &env,
+        &source,
+        &config.execution.compile_cmd,
+        &config.execution.exec_cmd,
+        req.parallel,
+        &config.container,
+    )
+    .await;
+
+    let test_cases = TestCaseRepository::find_limit(db, set.id, req.cases as u64).await?;
 
     let result = runner
         .execute(
-            &tmp_path,
+            &source,
             &config.execution.compile_cmd,
             &config.execution.exec_cmd,
             req.parallel,
             test_cases,
+            req.repeat,
             req.timeout,
         )
         .await;
 
     match result {
         Ok(execution_results) => {
-            // 実行結果をデータベースに保存
-            let mut saved_count = 0;
-            for result in execution_results {
-                match ExecutionResultRepository::create(
-                    &db,
-                    submission.id as i64,
-                    result.test_case_id as i64,
-                    result.success,
-                    result.stdout,
-                    result.stderr,
-                    result.score,
-                    result.execution_time_ms,
-                )
-                .await
-                {
-                    Ok(_) => {
-                        saved_count += 1;
-                    }
-                    Err(e) => {
-                        eprintln!(
-                            "Failed to save test case {} result: {}",
-                            result.test_case_id, e
-                        );
-                    }
-                }
-            }
-
-            (
-                StatusCode::OK,
-                Json(RunResponse {
-                    success: true,
-                    result: format!(
-                        "実行に成功しました。{}個のテストケース結果を保存しました。",
-                        saved_count
-                    ),
-                    error: None,
-                    submission_id: Some(submission.id),
-                }),
+            // CLI と同じく、実行が終わってから提出と実行結果をまとめて保存する
+            let saved_count = execution_results.len();
+            let (submission, _) = SubmissionRepository::create_with_results(
+                db,
+                problem.id,
+                set.id,
+                req.source_code,
+                SubmissionMetadata {
+                    title: req.title,
+                    notes: req.notes,
+                    tags: req.tags,
+                    author: req.author,
+                    parent_id: req.parent_id,
+                    environment: Some(environment),
+                    // ブラウザからの提出はファイル名も git の状態も持たない
+                    ..Default::default()
+                },
+                execution_results.into_iter().map(Into::into),
             )
-        }
-        Err(e) => (
-            StatusCode::OK,
-            Json(RunResponse {
-                success: false,
-                result: String::new(),
-                error: Some(format!("実行エラー: {}", e)),
+            .await?;
+
+            Ok(Json(RunResponse {
+                success: true,
+                result: format!(
+                    "実行に成功しました。{}個のテストケース結果を保存しました。",
+                    saved_count
+                ),
+                error: None,
                 submission_id: Some(submission.id),
-            }),
-        ),
+            }))
+        }
+        // コンパイルエラーなどは提出の結果なので、API のエラーではなく success: false で返す。
+        // 実行できなかった提出は保存しない
+        Err(e) => Ok(Json(RunResponse {
+            success: false,
+            result: String::new(),
+            error: Some(format!("実行エラー: {}", e)),
+            submission_id: None,
+        })),
     }
 }
+
+/// ソースを一時ワークスペースに書き出し、そのファイルだけのバンドルにする
+fn prepare_source(source_code: &str) -> Result<Source, Box<dyn std::error::Error + Send + Sync>> {
+    let workspace = Workspace::create("heurs-web")?;
+    std::fs::write(workspace.path().join(WEB_SOURCE_NAME), source_code)?;
+    Ok(Source::Bundle(Bundle::from_dir(
+        workspace.path(),
+        WEB_SOURCE_NAME,
+        &[],
+    )?))
+}
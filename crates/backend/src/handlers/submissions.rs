@@ -1,179 +1,534 @@
-use chrono::Utc;
-
+use super::{find_problem, find_submission};
+use crate::error::{ApiError, ApiJson, ApiPath, ApiQuery};
+use crate::handlers::remote::require_token;
 use crate::models::submissions::{
-    ExecutionResultMeta, Submission, SubmissionMeta, SubmissionResponse, SubmissionsResponse,
+    EditSubmissionRequest, ExecutionOutputResponse, ExecutionResultMeta, PruneResponse, Submission,
+    SubmissionFileMeta, SubmissionFileResponse, SubmissionMeta, SubmissionResponse,
+    SubmissionsResponse,
 };
+use crate::state::AppState;
 use axum::{
     Json, Router,
-    extract::{Path, Query},
+    extract::State,
     http::StatusCode,
-    routing::get,
+    middleware,
+    routing::{delete, get, patch},
+};
+use heurs_core::{
+    Bundle, ExecutionResult,
+    stats::{case_stats, relative_score},
 };
 use heurs_database::{
-    DatabaseManager, ExecutionResultModel, ExecutionResultRepository, SubmissionModel,
-    SubmissionRepository,
+    DatabaseConnection, Environment, ExecutionResultRepository, ExecutionResultSummary,
+    PrunePolicy, SubmissionAggregate, SubmissionEdit, SubmissionRepository, TestCaseRepository,
+    TestCaseSetRepository,
 };
+use std::collections::HashMap;
 
-pub fn submission_routes() -> Router {
+/// 提出の閲覧・編集・削除の API。編集と削除はリモート実行と同じ共有トークンがなければ受け付けない
+pub fn submission_routes(state: AppState) -> Router<AppState> {
+    let require_token = || middleware::from_fn_with_state(state.clone(), require_token);
     Router::new()
-        .route("/api/submissions", get(get_submissions))
-        .route("/api/submissions/{id}", get(get_submission))
+        .route(
+            "/api/problems/{problem}/submissions",
+            get(get_submissions).merge(delete(prune_submissions).route_layer(require_token())),
+        )
+        .route(
+            "/api/problems/{problem}/submissions/{id}",
+            get(get_submission).merge(
+                patch(edit_submission)
+                    .delete(delete_submission)
+                    .route_layer(require_token()),
+            ),
+        )
+        .route(
+            "/api/problems/{problem}/submissions/{id}/outputs/{test_case_id}",
+            get(get_output),
+        )
+        .route(
+            "/api/problems/{problem}/submissions/{id}/files/{*path}",
+            get(get_file),
+        )
 }
 
 #[derive(Debug, serde::Deserialize)]
 struct ListParams {
     #[serde(default)]
     limit: Option<u64>,
+    /// このタグの付いたケースの結果だけで集計する
+    #[serde(default)]
+    tag: Option<String>,
 }
 
 async fn get_submissions(
-    Query(params): Query<ListParams>,
-) -> (StatusCode, Json<SubmissionsResponse>) {
-    // DB 接続
-    let db = match DatabaseManager::connect("sqlite://heurs.db").await {
-        Ok(db) => db,
-        Err(_) => {
-            return (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(SubmissionsResponse {
-                    submissions: vec![],
-                }),
-            );
-        }
-    };
+    State(db): State<DatabaseConnection>,
+    ApiPath(problem): ApiPath<String>,
+    ApiQuery(params): ApiQuery<ListParams>,
+) -> Result<Json<SubmissionsResponse>, ApiError> {
+    let problem = find_problem(&db, &problem).await?;
 
-    // 取得
-    let mut submissions: Vec<SubmissionModel> = SubmissionRepository::find_all(&db)
-        .await
-        .unwrap_or_default();
+    // 一覧に要る列だけを、件数を絞って取得する
+    let submissions =
+        SubmissionRepository::find_summaries_by_problem(&db, problem.id, params.limit).await?;
 
-    // offset / limit
-    if let Some(lim) = params.limit {
-        submissions.truncate(lim as usize);
-    }
+    let sets: HashMap<i32, String> = TestCaseSetRepository::find_by_problem(&db, problem.id)
+        .await?
+        .into_iter()
+        .map(|s| (s.id, s.name))
+        .collect();
 
-    let mut submission_metas: Vec<SubmissionMeta> = vec![];
+    // タグの指定があれば、そのタグの付いたケースの結果だけで集計する
+    let test_case_ids = match &params.tag {
+        Some(tag) => Some(TestCaseRepository::find_ids_by_tag(&db, problem.id, tag).await?),
+        None => None,
+    };
 
-    for submission in submissions {
-        let execution_results: Vec<ExecutionResultModel> =
-            ExecutionResultRepository::find_by_submission_id(&db, submission.id as i64)
-                .await
-                .unwrap_or_default();
+    // 提出ごとの件数・平均は DB 側でまとめて集計する
+    let aggregates: HashMap<i32, SubmissionAggregate> =
+        ExecutionResultRepository::aggregate_by_submission(
+            &db,
+            submissions.iter().map(|s| s.id).collect(),
+            test_case_ids,
+        )
+        .await?
+        .into_iter()
+        .map(|a| (a.submission_id, a))
+        .collect();
 
-        let number_of_test_cases = execution_results.len() as i32;
+    let submission_metas: Vec<SubmissionMeta> = submissions
+        .into_iter()
+        .map(|submission| {
+            // 結果がない提出は平均値を 0.0 にする
+            let aggregate = aggregates.get(&submission.id);
+            let environment = submission.environment().map(Into::into);
+            SubmissionMeta {
+                id: submission.id,
+                number_of_test_cases: aggregate.map_or(0, |a| a.number_of_test_cases as i32),
+                average_score: aggregate.map_or(0.0, |a| a.average_score),
+                average_execution_time_ms: aggregate.map_or(0.0, |a| a.average_execution_time_ms),
+                number_of_failures: aggregate.map_or(0, |a| a.number_of_failures as i32),
+                created_at: submission.timestamp,
+                test_case_set: submission
+                    .test_case_set_id
+                    .and_then(|id| sets.get(&id).cloned()),
+                tags: tag_list(&submission.tags),
+                title: submission.title,
+                author: submission.author,
+                parent_id: submission.parent_id,
+                git_commit: submission.git_commit,
+                git_dirty: submission.git_dirty,
+                environment,
+            }
+        })
+        .collect();
 
-        // テストケースが存在しない場合は平均値を 0.0 にする
-        let (average_score, average_execution_time_ms) = if number_of_test_cases == 0 {
-            (0.0, 0.0)
-        } else {
-            let avg_score = execution_results.iter().map(|r| r.score).sum::<i64>() as f64
-                / number_of_test_cases as f64;
-            let avg_time = execution_results
-                .iter()
-                .map(|r| r.execution_time_ms)
-                .sum::<i32>() as f64
-                / number_of_test_cases as f64;
-            (avg_score, avg_time)
-        };
+    Ok(Json(SubmissionsResponse {
+        submissions: submission_metas,
+    }))
+}
 
-        submission_metas.push(SubmissionMeta {
-            id: submission.id,
-            number_of_test_cases,
-            average_score,
-            average_execution_time_ms,
-            created_at: submission.timestamp,
-        });
-    }
+/// カンマ区切りで保存しているタグを配列にする
+fn tag_list(tags: &str) -> Vec<String> {
+    SubmissionRepository::split_tags(tags)
+        .into_iter()
+        .map(str::to_string)
+        .collect()
+}
 
-    (
-        StatusCode::OK,
-        Json(SubmissionsResponse {
-            submissions: submission_metas,
-        }),
+/// 保存してあるバンドルが読めない (DB が壊れている) ときのエラー
+fn broken_bundle(id: i32, e: impl std::fmt::Display) -> ApiError {
+    ApiError::internal(
+        "broken_bundle",
+        format!("提出 {} のファイル一式を読み取れません", id),
     )
+    .with_details(e.to_string())
 }
 
-async fn get_submission(Path(id): Path<i32>) -> (StatusCode, Json<SubmissionResponse>) {
-    // DB 接続
-    let db = match DatabaseManager::connect("sqlite://heurs.db").await {
-        Ok(db) => db,
-        Err(_) => {
-            return (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(SubmissionResponse {
-                    submission: Submission {
-                        id: 0,
-                        source_code: String::new(),
-                        number_of_test_cases: 0,
-                        average_score: 0.0,
-                        average_execution_time_ms: 0.0,
-                        created_at: Utc::now(),
-                        execution_results: vec![],
-                    },
-                }),
-            );
-        }
+async fn get_submission(
+    State(db): State<DatabaseConnection>,
+    ApiPath((problem, id)): ApiPath<(String, i32)>,
+) -> Result<Json<SubmissionResponse>, ApiError> {
+    let problem = find_problem(&db, &problem).await?;
+    let submission = find_submission(&db, &problem, id).await?;
+
+    let execution_results: Vec<ExecutionResultSummary> =
+        ExecutionResultRepository::find_summaries_by_submission_id(&db, submission.id).await?;
+
+    let test_case_set = match submission.test_case_set_id {
+        Some(id) => TestCaseSetRepository::find_by_id(&db, id)
+            .await?
+            .map(|s| s.name),
+        None => None,
     };
 
-    let submission = SubmissionRepository::find_by_id(&db, id)
-        .await
-        .unwrap_or_default();
-
-    if submission.is_none() {
-        return (
-            StatusCode::NOT_FOUND,
-            Json(SubmissionResponse {
-                submission: Submission {
-                    id: 0,
-                    source_code: String::new(),
-                    number_of_test_cases: 0,
-                    average_score: 0.0,
-                    average_execution_time_ms: 0.0,
-                    created_at: Utc::now(),
-                    execution_results: vec![],
-                },
-            }),
-        );
-    }
+    let best_known: HashMap<i32, i64> = TestCaseRepository::find_by_ids(
+        &db,
+        execution_results.iter().map(|r| r.test_case_id).collect(),
+    )
+    .await?
+    .into_iter()
+    .filter_map(|c| Some((c.id, c.best_known_score?)))
+    .collect();
 
-    let submission = submission.unwrap();
+    // 結果がなければ平均値は 0.0 にする
+    let aggregate =
+        ExecutionResultRepository::aggregate_by_submission(&db, vec![submission.id], None)
+            .await?
+            .pop();
 
-    let execution_results: Vec<ExecutionResultModel> =
-        ExecutionResultRepository::find_by_submission_id(&db, submission.id as i64)
-            .await
-            .unwrap_or_default();
+    // 複数ファイルの提出ならファイル一覧を返す (内容は get_file で個別に取得する)
+    let bundle = SubmissionRepository::find_bundle(&db, submission.id)
+        .await?
+        .map(|b| Bundle::from_archive(b.entry, b.archive));
+    let files: Vec<SubmissionFileMeta> = match &bundle {
+        Some(bundle) => bundle
+            .files()
+            .map_err(|e| broken_bundle(submission.id, e))?
+            .into_iter()
+            .map(|f| SubmissionFileMeta {
+                path: f.path,
+                size: f.size,
+            })
+            .collect(),
+        None => vec![],
+    };
+    let environment = Environment::of(&submission).map(Into::into);
 
-    let result = SubmissionResponse {
+    let stats = case_stats(
+        &execution_results
+            .iter()
+            .map(|r| r.into())
+            .collect::<Vec<ExecutionResult>>(),
+    );
+
+    Ok(Json(SubmissionResponse {
         submission: Submission {
             id: submission.id,
             source_code: submission.source_code,
-            number_of_test_cases: execution_results.len() as i32,
-            average_score: if execution_results.is_empty() {
-                0.0
-            } else {
-                execution_results.iter().map(|r| r.score).sum::<i64>() as f64
-                    / execution_results.len() as f64
-            },
-            average_execution_time_ms: if execution_results.is_empty() {
-                0.0
-            } else {
-                execution_results
-                    .iter()
-                    .map(|r| r.execution_time_ms)
-                    .sum::<i32>() as f64
-                    / execution_results.len() as f64
-            },
+            number_of_test_cases: stats.len() as i32,
+            average_score: aggregate.as_ref().map_or(0.0, |a| a.average_score),
+            average_execution_time_ms: aggregate
+                .as_ref()
+                .map_or(0.0, |a| a.average_execution_time_ms),
             created_at: submission.timestamp,
+            test_case_set,
+            tags: tag_list(&submission.tags),
+            title: submission.title,
+            notes: submission.notes,
+            author: submission.author,
+            parent_id: submission.parent_id,
+            source_path: submission.source_path,
+            git_commit: submission.git_commit,
+            git_branch: submission.git_branch,
+            git_dirty: submission.git_dirty,
+            git_diff: submission.git_diff,
+            environment,
+            entry_point: bundle.as_ref().map(|b| b.entry().to_string()),
+            files,
             execution_results: execution_results
                 .iter()
-                .map(|r| ExecutionResultMeta {
-                    test_case_id: r.test_case_id as i32,
-                    score: r.score,
-                    execution_time_ms: r.execution_time_ms,
+                .map(|r| {
+                    let best = best_known.get(&r.test_case_id).copied();
+                    ExecutionResultMeta {
+                        test_case_id: r.test_case_id,
+                        repeat_index: r.repeat_index,
+                        score: r.score,
+                        execution_time_ms: r.execution_time_ms,
+                        best_known: best,
+                        relative_score: relative_score(r.score as f64, best),
+                    }
                 })
                 .collect(),
+            case_stats: stats,
         },
+    }))
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct OutputParams {
+    /// 何回目の実行か (0 始まり)
+    #[serde(default)]
+    repeat: i32,
+}
+
+async fn get_output(
+    State(db): State<DatabaseConnection>,
+    ApiPath((problem, id, test_case_id)): ApiPath<(String, i32, i32)>,
+    ApiQuery(params): ApiQuery<OutputParams>,
+) -> Result<Json<ExecutionOutputResponse>, ApiError> {
+    let problem = find_problem(&db, &problem).await?;
+    find_submission(&db, &problem, id).await?;
+
+    let output = ExecutionResultRepository::find_output(&db, id, test_case_id, params.repeat)
+        .await?
+        .ok_or_else(|| {
+            ApiError::not_found(
+                "output_not_found",
+                format!(
+                    "提出 {} のテストケース {} の {} 回目の出力がありません",
+                    id, test_case_id, params.repeat
+                ),
+            )
+            .with_details(serde_json::json!({
+                "submission_id": id,
+                "test_case_id": test_case_id,
+                "repeat": params.repeat,
+            }))
+        })?;
+
+    Ok(Json(ExecutionOutputResponse {
+        stdout: output.stdout,
+        stderr: output.stderr,
+    }))
+}
+
+async fn edit_submission(
+    State(db): State<DatabaseConnection>,
+    ApiPath((problem, id)): ApiPath<(String, i32)>,
+    ApiJson(req): ApiJson<EditSubmissionRequest>,
+) -> Result<StatusCode, ApiError> {
+    let problem = find_problem(&db, &problem).await?;
+    let submission = find_submission(&db, &problem, id).await?;
+
+    // 親は同じ問題の提出で、自分の子孫であってはならない
+    if let Some(Some(parent_id)) = req.parent_id {
+        find_submission(&db, &problem, parent_id)
+            .await
+            .map_err(|e| match e.status() {
+                StatusCode::NOT_FOUND => ApiError::bad_request(
+                    "invalid_parent",
+                    format!("親の提出 {} が見つかりません", parent_id),
+                )
+                .with_details(serde_json::json!({ "parent_id": parent_id })),
+                _ => e,
+            })?;
+        if !SubmissionRepository::is_valid_parent(&db, problem.id, Some(id), parent_id).await? {
+            return Err(ApiError::conflict(
+                "parent_cycle",
+                format!(
+                    "提出 {} は提出 {} の子孫なので親にできません",
+                    parent_id, id
+                ),
+            )
+            .with_details(serde_json::json!({ "parent_id": parent_id })));
+        }
+    }
+
+    let edit = SubmissionEdit {
+        title: req.title,
+        notes: req.notes,
+        author: req.author,
+        tags: req.tags,
+        parent_id: req.parent_id,
+    };
+    SubmissionRepository::update_metadata(&db, submission, edit).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn get_file(
+    State(db): State<DatabaseConnection>,
+    ApiPath((problem, id, path)): ApiPath<(String, i32, String)>,
+) -> Result<Json<SubmissionFileResponse>, ApiError> {
+    let problem = find_problem(&db, &problem).await?;
+    find_submission(&db, &problem, id).await?;
+
+    let file_not_found = || {
+        ApiError::not_found(
+            "file_not_found",
+            format!("提出 {} にファイル {} はありません", id, path),
+        )
+        .with_details(serde_json::json!({ "submission_id": id, "path": path }))
     };
 
-    (StatusCode::OK, Json(result))
+    // 1 ファイルの提出にはバンドルがない
+    let bundle = SubmissionRepository::find_bundle(&db, id)
+        .await?
+        .map(|b| Bundle::from_archive(b.entry, b.archive))
+        .ok_or_else(file_not_found)?;
+    let content = bundle
+        .read(&path)
+        .map_err(|e| broken_bundle(id, e))?
+        .ok_or_else(file_not_found)?;
+
+    Ok(Json(SubmissionFileResponse {
+        path,
+        content: String::from_utf8_lossy(&content).into_owned(),
+    }))
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct DeleteParams {
+    /// 提出とスコアは残し、stdout / stderr だけを消す
+    #[serde(default)]
+    keep_scores: bool,
+}
+
+async fn delete_submission(
+    State(db): State<DatabaseConnection>,
+    ApiPath((problem, id)): ApiPath<(String, i32)>,
+    ApiQuery(params): ApiQuery<DeleteParams>,
+) -> Result<StatusCode, ApiError> {
+    let problem = find_problem(&db, &problem).await?;
+    find_submission(&db, &problem, id).await?;
+
+    if params.keep_scores {
+        ExecutionResultRepository::clear_outputs(&db, vec![id]).await?;
+    } else {
+        SubmissionRepository::delete(&db, vec![id]).await?;
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct PruneParams {
+    /// テストケースのセットごとに平均スコアの上位この件数を残す
+    #[serde(default)]
+    keep_best: Option<u32>,
+    /// これより古い提出だけを消す (`14d`, `12h` など)
+    #[serde(default)]
+    older_than: Option<String>,
+    #[serde(default)]
+    keep_tagged: bool,
+    #[serde(default)]
+    keep_scores: bool,
+    /// 消す提出を返すだけで、実際には消さない
+    #[serde(default)]
+    dry_run: bool,
+}
+
+async fn prune_submissions(
+    State(db): State<DatabaseConnection>,
+    ApiPath(problem): ApiPath<String>,
+    ApiQuery(params): ApiQuery<PruneParams>,
+) -> Result<Json<PruneResponse>, ApiError> {
+    // 条件がなければ全件消えてしまうので、どちらかの指定を必須にする
+    if params.keep_best.is_none() && params.older_than.is_none() {
+        return Err(ApiError::bad_request(
+            "missing_prune_policy",
+            "keep_best か older_than のどちらかを指定してください",
+        ));
+    }
+    let older_than = params
+        .older_than
+        .as_deref()
+        .map(PrunePolicy::parse_age)
+        .transpose()
+        .map_err(|e| ApiError::bad_request("invalid_age", e))?;
+
+    let problem = find_problem(&db, &problem).await?;
+
+    let policy = PrunePolicy {
+        keep_best: params.keep_best,
+        older_than,
+        keep_tagged: params.keep_tagged,
+    };
+    let pruned: Vec<i32> = SubmissionRepository::find_prunable(&db, problem.id, &policy)
+        .await?
+        .iter()
+        .map(|s| s.id)
+        .collect();
+
+    if !params.dry_run && !pruned.is_empty() {
+        if params.keep_scores {
+            ExecutionResultRepository::clear_outputs(&db, pruned.clone()).await?;
+        } else {
+            SubmissionRepository::delete(&db, pruned.clone()).await?;
+        }
+    }
+
+    Ok(Json(PruneResponse { pruned }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Body;
+    use axum::http::{Method, Request, header::AUTHORIZATION};
+    use heurs_database::DatabaseManager;
+    use std::sync::Arc;
+    use tower::ServiceExt;
+
+    async fn app(remote_token: Option<&str>) -> Router {
+        let (db, _) = DatabaseManager::connect_and_migrate("sqlite::memory:")
+            .await
+            .unwrap();
+        let state = AppState {
+            db,
+            config: None,
+            runners: Default::default(),
+            remote: Default::default(),
+            remote_token: remote_token.map(Arc::from),
+        };
+        submission_routes(state.clone()).with_state(state)
+    }
+
+    async fn status(app: &Router, method: Method, uri: &str, token: Option<&str>) -> StatusCode {
+        let mut request = Request::builder().method(method).uri(uri);
+        if let Some(token) = token {
+            request = request.header(AUTHORIZATION, format!("Bearer {}", token));
+        }
+        app.clone()
+            .oneshot(request.body(Body::empty()).unwrap())
+            .await
+            .unwrap()
+            .status()
+    }
+
+    #[tokio::test]
+    async fn deleting_requires_the_shared_token() {
+        let app = app(Some("secret")).await;
+        for uri in [
+            "/api/problems/p/submissions/1",
+            "/api/problems/p/submissions?keep_best=1",
+        ] {
+            assert_eq!(
+                status(&app, Method::DELETE, uri, None).await,
+                StatusCode::UNAUTHORIZED
+            );
+            assert_eq!(
+                status(&app, Method::DELETE, uri, Some("wrong")).await,
+                StatusCode::UNAUTHORIZED
+            );
+            // トークンが合えばハンドラまで届く (問題がないので 404)
+            assert_eq!(
+                status(&app, Method::DELETE, uri, Some("secret")).await,
+                StatusCode::NOT_FOUND
+            );
+        }
+        // 閲覧にはトークンは要らない
+        assert_eq!(
+            status(&app, Method::GET, "/api/problems/p/submissions/1", None).await,
+            StatusCode::NOT_FOUND
+        );
+    }
+
+    #[tokio::test]
+    async fn editing_requires_the_shared_token() {
+        let app = app(Some("secret")).await;
+        let edit = |token: Option<&str>| {
+            let mut request = Request::builder()
+                .method(Method::PATCH)
+                .uri("/api/problems/p/submissions/1")
+                .header("content-type", "application/json");
+            if let Some(token) = token {
+                request = request.header(AUTHORIZATION, format!("Bearer {}", token));
+            }
+            app.clone()
+                .oneshot(request.body(Body::from(r#"{"title":"x"}"#)).unwrap())
+        };
+        assert_eq!(edit(None).await.unwrap().status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(
+            edit(Some("secret")).await.unwrap().status(),
+            StatusCode::NOT_FOUND
+        );
+    }
+
+    #[tokio::test]
+    async fn deleting_is_disabled_without_a_token() {
+        let app = app(None).await;
+        assert_eq!(
+            status(&app, Method::DELETE, "/api/problems/p/submissions/1", None).await,
+            StatusCode::SERVICE_UNAVAILABLE
+        );
+    }
 }
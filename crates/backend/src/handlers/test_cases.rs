@@ -1,11 +1,14 @@
-use crate::models::test_cases::{TestCase, TestCaseMeta, TestCaseResponse, TestCasesResponse};
-use axum::{
-    Json, Router,
-    extract::{Path, Query},
-    http::StatusCode,
-    routing::get,
+use super::find_problem;
+use crate::error::{ApiError, ApiPath, ApiQuery};
+use crate::models::test_cases::{
+    TestCase, TestCaseMeta, TestCaseResponse, TestCaseSetMeta, TestCaseSetsResponse,
+    TestCasesResponse,
+};
+use crate::state::AppState;
+use axum::{Json, Router, extract::State, routing::get};
+use heurs_database::{
+    DatabaseConnection, TestCaseModel, TestCaseRepository, TestCaseSetRepository,
 };
-use heurs_database::{DatabaseManager, TestCaseModel, TestCaseRepository};
 
 #[derive(Debug, serde::Deserialize)]
 struct ListParams {
@@ -13,29 +16,42 @@ struct ListParams {
     offset: Option<u64>,
     #[serde(default)]
     limit: Option<u64>,
+    /// このセットに含まれるケースだけを返す
+    #[serde(default)]
+    set: Option<String>,
 }
 
-pub fn test_case_routes() -> Router {
+pub fn test_case_routes() -> Router<AppState> {
     Router::new()
-        .route("/api/test_cases", get(get_test_cases))
-        .route("/api/test_cases/{id}", get(get_test_case))
+        .route("/api/problems/{problem}/test_cases", get(get_test_cases))
+        .route(
+            "/api/problems/{problem}/test_cases/{id}",
+            get(get_test_case),
+        )
+        .route(
+            "/api/problems/{problem}/test_case_sets",
+            get(get_test_case_sets),
+        )
 }
 
-async fn get_test_cases(Query(params): Query<ListParams>) -> (StatusCode, Json<TestCasesResponse>) {
-    // DB 接続
-    let db = match DatabaseManager::connect("sqlite://heurs.db").await {
-        Ok(db) => db,
-        Err(_) => {
-            return (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(TestCasesResponse { test_cases: vec![] }),
-            );
+async fn get_test_cases(
+    State(db): State<DatabaseConnection>,
+    ApiPath(problem): ApiPath<String>,
+    ApiQuery(params): ApiQuery<ListParams>,
+) -> Result<Json<TestCasesResponse>, ApiError> {
+    let problem = find_problem(&db, &problem).await?;
+
+    // 取得 (セットの指定がなければ問題のすべてのケース)
+    let mut cases: Vec<TestCaseModel> = match &params.set {
+        Some(name) => {
+            let set = TestCaseSetRepository::find_by_name(&db, problem.id, name)
+                .await?
+                .ok_or_else(|| ApiError::test_case_set_not_found(name))?;
+            TestCaseRepository::find_by_set(&db, set.id).await?
         }
+        None => TestCaseRepository::find_by_problem(&db, problem.id).await?,
     };
 
-    // 取得
-    let mut cases: Vec<TestCaseModel> = TestCaseRepository::find_all(&db).await.unwrap_or_default();
-
     // offset / limit
     if let Some(off) = params.offset {
         cases = cases.into_iter().skip(off as usize).collect();
@@ -44,73 +60,68 @@ async fn get_test_cases(Query(params): Query<ListParams>) -> (StatusCode, Json<T
         cases.truncate(lim as usize);
     }
 
-    (
-        StatusCode::OK,
-        Json(TestCasesResponse {
-            test_cases: cases
-                .into_iter()
-                .map(|c| TestCaseMeta {
-                    id: c.id,
-                    filename: c.filename,
-                    created_at: c.created_at,
-                })
-                .collect(),
-        }),
-    )
+    Ok(Json(TestCasesResponse {
+        test_cases: cases
+            .into_iter()
+            .map(|c| TestCaseMeta {
+                id: c.id,
+                filename: c.filename,
+                created_at: c.created_at,
+            })
+            .collect(),
+    }))
 }
 
-async fn get_test_case(Path(id): Path<i32>) -> (StatusCode, Json<TestCaseResponse>) {
-    // DB 接続
-    let db = match DatabaseManager::connect("sqlite://heurs.db").await {
-        Ok(db) => db,
-        Err(_) => {
-            return (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(TestCaseResponse {
-                    test_case: TestCase {
-                        id: 0,
-                        filename: String::new(),
-                        content: String::new(),
-                        created_at: chrono::Utc::now(),
-                    },
-                }),
-            );
-        }
-    };
+async fn get_test_case(
+    State(db): State<DatabaseConnection>,
+    ApiPath((problem, id)): ApiPath<(String, i32)>,
+) -> Result<Json<TestCaseResponse>, ApiError> {
+    let problem = find_problem(&db, &problem).await?;
 
-    match TestCaseRepository::find_by_id(&db, id).await {
-        Ok(Some(c)) => (
-            StatusCode::OK,
-            Json(TestCaseResponse {
-                test_case: TestCase {
-                    id: c.id,
-                    filename: c.filename,
-                    content: c.input,
-                    created_at: c.created_at,
-                },
-            }),
-        ),
-        Ok(None) => (
-            StatusCode::NOT_FOUND,
-            Json(TestCaseResponse {
-                test_case: TestCase {
-                    id: 0,
-                    filename: String::new(),
-                    content: String::new(),
-                    created_at: chrono::Utc::now(),
-                },
-            }),
-        ),
-        Err(_) => (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(TestCaseResponse {
-                test_case: TestCase {
-                    id: 0,
-                    filename: String::new(),
-                    content: String::new(),
-                    created_at: chrono::Utc::now(),
-                },
-            }),
-        ),
+    // 別の問題のケースは存在しないものとして扱う
+    let c = TestCaseRepository::find_by_id(&db, id)
+        .await?
+        .filter(|c| c.problem_id == problem.id)
+        .ok_or_else(|| {
+            ApiError::not_found(
+                "test_case_not_found",
+                format!("テストケース {} が見つかりません", id),
+            )
+            .with_details(serde_json::json!({ "test_case_id": id }))
+        })?;
+
+    Ok(Json(TestCaseResponse {
+        test_case: TestCase {
+            id: c.id,
+            filename: c.filename,
+            content: c.input,
+            created_at: c.created_at,
+        },
+    }))
+}
+
+async fn get_test_case_sets(
+    State(db): State<DatabaseConnection>,
+    ApiPath(problem): ApiPath<String>,
+) -> Result<Json<TestCaseSetsResponse>, ApiError> {
+    let problem = find_problem(&db, &problem).await?;
+
+    let sets = TestCaseSetRepository::find_by_problem(&db, problem.id).await?;
+    let active_id = TestCaseSetRepository::find_active(&db, problem.id)
+        .await?
+        .map(|s| s.id);
+
+    let mut test_case_sets = vec![];
+    for set in sets {
+        test_case_sets.push(TestCaseSetMeta {
+            id: set.id,
+            number_of_test_cases: TestCaseSetRepository::count_cases(&db, set.id).await?,
+            active: Some(set.id) == active_id,
+            name: set.name,
+            created_at: set.created_at,
+            archived_at: set.archived_at,
+        });
     }
+
+    Ok(Json(TestCaseSetsResponse { test_case_sets }))
 }
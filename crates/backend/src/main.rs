@@ -1,15 +1,105 @@
+mod error;
 mod handlers;
 mod models;
+mod state;
 
 use axum::Router;
+use clap::Parser;
+use heurs_core::{load_config, resolve_database_url};
+use heurs_database::{DatabaseManager, redact_database_url};
+use state::{AppState, RunnerRegistry};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
 use tower_http::cors::{Any, CorsLayer};
 
+#[derive(Parser)]
+#[command(name = "heurs-back", about = "heurs web backend")]
+struct Args {
+    /// 待ち受けるアドレス
+    #[arg(long, env = "HEURS_BIND", default_value = "0.0.0.0:3000")]
+    bind: String,
+
+    /// データベースURL (`sqlite://...` / `postgres://...`)。省略時は設定ファイルの `[database] url`、それもなければ sqlite://heurs.db
+    #[arg(short, long, env = "HEURS_DATABASE_URL")]
+    database_url: Option<String>,
+
+    /// 設定ファイル。なければ閲覧だけできる (実行はできない)
+    #[arg(short, long, env = "HEURS_CONFIG", default_value = "heurs.toml")]
+    config: PathBuf,
+
+    /// リモート実行 (ジョブとワーカーの API) の共有トークン。省略時は設定ファイルの `[remote] token`。
+    /// どちらもなければリモート実行の API は無効になる
+    #[arg(long, env = "HEURS_REMOTE_TOKEN", hide_env_values = true)]
+    remote_token: Option<String>,
+
+    /// Web UI からの実行で使わせる実行環境 (local / container / aws / remote)。カンマ区切りで複数指定できる
+    #[arg(
+        long,
+        env = "HEURS_ALLOW_ENV",
+        value_delimiter = ',',
+        default_value = "local",
+        value_parser = clap::builder::PossibleValuesParser::new(state::RUNNER_NAMES)
+    )]
+    allow_env: Vec<String>,
+}
+
 #[tokio::main]
-async fn main() {
+async fn main() -> anyhow::Result<()> {
+    let args = Args::parse();
+
+    // --database-url (HEURS_DATABASE_URL) > 設定ファイルの `[database] url` > 既定の URL
+    let url = resolve_database_url(args.database_url, &args.config)
+        .map_err(|e| anyhow::anyhow!("Failed to read {}: {}", args.config.display(), e))?;
+    println!("Using database {}", redact_database_url(&url));
+
+    // 空の DB でもそのまま使えるよう、未適用のマイグレーションを適用しておく
+    let (db, applied) = DatabaseManager::connect_and_migrate(&url).await?;
+    for name in applied {
+        println!("Applied migration '{}'", name);
+    }
+
+    let config = if args.config.exists() {
+        let mut config = load_config(&args.config)
+            .map_err(|e| anyhow::anyhow!("Failed to read {}: {}", args.config.display(), e))?;
+        // 提出コードから設定ファイルのトークンや DB の中身を読めないようにする
+        config.sandbox.hide_config_and_database(&args.config, &url);
+        Some(Arc::new(config))
+    } else {
+        eprintln!(
+            "Warning: {} not found; submissions cannot be run from the web UI",
+            args.config.display()
+        );
+        None
+    };
+    let remote_token = args
+        .remote_token
+        .or_else(|| config.as_ref().and_then(|c| c.remote.token.clone()))
+        .map(Arc::from);
+    if remote_token.is_none() {
+        eprintln!(
+            "Warning: no remote token (--remote-token or [remote] token); remote workers are disabled"
+        );
+    }
+    let runners = config
+        .as_deref()
+        .map(|config| RunnerRegistry::from_config(config, &args.allow_env))
+        .unwrap_or_default();
+
+    let state = AppState {
+        db,
+        config,
+        runners: Arc::new(runners),
+        remote: Arc::new(Mutex::new(Default::default())),
+        remote_token,
+    };
+
     let app = Router::new()
+        .merge(handlers::problems::problem_routes())
         .merge(handlers::run::run_routes())
         .merge(handlers::test_cases::test_case_routes())
-        .merge(handlers::submissions::submission_routes())
+        .merge(handlers::submissions::submission_routes(state.clone()))
+        .merge(handlers::remote::remote_routes(state.clone()))
+        .with_state(state)
         .layer(
             CorsLayer::new()
                 .allow_origin(Any)
@@ -17,8 +107,9 @@ async fn main() {
                 .allow_headers(Any),
         );
 
-    let listener = tokio::net::TcpListener::bind("0.0.0.0:3000").await.unwrap();
-    println!("Server running on http://0.0.0.0:3000");
+    let listener = tokio::net::TcpListener::bind(&args.bind).await?;
+    println!("Server running on http://{}", args.bind);
 
-    axum::serve(listener, app).await.unwrap();
+    axum::serve(listener, app).await?;
+    Ok(())
 }
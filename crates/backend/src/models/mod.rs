@@ -1,3 +1,4 @@
+pub mod problems;
 pub mod run;
 pub mod submissions;
 pub mod test_cases;
@@ -6,6 +6,32 @@ pub struct RunRequest {
     pub cases: u32,
     pub parallel: u32,
     pub timeout: u32,
+    /// 各ケースを実行する回数 (省略時 1)
+    #[serde(default = "default_repeat")]
+    pub repeat: u32,
+    /// 実行するテストケースのセット名 (省略時は現在のセット)
+    #[serde(default)]
+    pub set: Option<String>,
+    /// 提出のタイトル
+    #[serde(default)]
+    pub title: Option<String>,
+    /// 自由記述のメモ
+    #[serde(default)]
+    pub notes: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub author: Option<String>,
+    /// 元にした提出の ID
+    #[serde(default)]
+    pub parent_id: Option<i32>,
+    /// 実行環境 (local / container / aws / remote)。省略時は local
+    #[serde(default)]
+    pub env: Option<String>,
+}
+
+fn default_repeat() -> u32 {
+    1
 }
 
 #[derive(Serialize, Deserialize)]
@@ -1,4 +1,6 @@
 use chrono::{DateTime, Utc};
+use heurs_core::stats::CaseStats;
+use heurs_database::Environment;
 use serde::{Deserialize, Serialize};
 
 #[derive(Serialize, Deserialize)]
@@ -7,7 +9,55 @@ pub struct SubmissionMeta {
     pub number_of_test_cases: i32,
     pub average_score: f64,
     pub average_execution_time_ms: f64,
+    /// 失敗した実行の数
+    pub number_of_failures: i32,
     pub created_at: DateTime<Utc>,
+    /// 実行したテストケースのセット名
+    pub test_case_set: Option<String>,
+    pub title: Option<String>,
+    pub tags: Vec<String>,
+    pub author: Option<String>,
+    /// 元にした提出の ID
+    pub parent_id: Option<i32>,
+    /// `heurs run` を実行したときの HEAD のコミット
+    pub git_commit: Option<String>,
+    pub git_dirty: bool,
+    /// 実行した環境 (記録を始める前の提出なら `None`)
+    pub environment: Option<EnvironmentMeta>,
+}
+
+/// 提出を実行した環境。実行時間に依存するスコアは環境が違うと比べられない
+#[derive(Serialize, Deserialize)]
+pub struct EnvironmentMeta {
+    pub hostname: Option<String>,
+    pub cpu_model: Option<String>,
+    pub cpu_cores: Option<i32>,
+    pub os: Option<String>,
+    pub compiler_version: Option<String>,
+    pub compile_cmd: Option<String>,
+    pub exec_cmd: Option<String>,
+    /// local / container / aws / remote
+    pub runner: String,
+    pub parallelism: i32,
+    /// 表示用の 1 行の要約
+    pub summary: String,
+}
+
+impl From<Environment> for EnvironmentMeta {
+    fn from(env: Environment) -> Self {
+        EnvironmentMeta {
+            summary: env.summary(),
+            hostname: env.hostname,
+            cpu_model: env.cpu_model,
+            cpu_cores: env.cpu_cores,
+            os: env.os,
+            compiler_version: env.compiler_version,
+            compile_cmd: env.compile_cmd,
+            exec_cmd: env.exec_cmd,
+            runner: env.runner,
+            parallelism: env.parallelism,
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize)]
@@ -18,8 +68,13 @@ pub struct SubmissionsResponse {
 #[derive(Serialize, Deserialize)]
 pub struct ExecutionResultMeta {
     pub test_case_id: i32,
+    pub repeat_index: i32,
     pub score: i64,
     pub execution_time_ms: i32,
+    /// ケースの既知の最良スコア
+    pub best_known: Option<i64>,
+    /// 既知の最良スコアに対する割合 (%)
+    pub relative_score: Option<f64>,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -30,10 +85,87 @@ pub struct Submission {
     pub average_score: f64,
     pub average_execution_time_ms: f64,
     pub created_at: DateTime<Utc>,
+    /// 実行したテストケースのセット名
+    pub test_case_set: Option<String>,
+    pub title: Option<String>,
+    pub notes: Option<String>,
+    pub tags: Vec<String>,
+    pub author: Option<String>,
+    /// 元にした提出の ID
+    pub parent_id: Option<i32>,
+    /// git リポジトリ内ならルートからの相対パス、そうでなければファイル名
+    pub source_path: Option<String>,
+    /// `heurs run` を実行したときの HEAD のコミット
+    pub git_commit: Option<String>,
+    pub git_branch: Option<String>,
+    pub git_dirty: bool,
+    /// HEAD からの未コミットの変更
+    pub git_diff: Option<String>,
+    /// 実行した環境 (記録を始める前の提出なら `None`)
+    pub environment: Option<EnvironmentMeta>,
+    /// 複数ファイルの提出ならエントリポイントのパス
+    pub entry_point: Option<String>,
+    /// 複数ファイルの提出のファイル一覧 (1 ファイルの提出なら空)
+    pub files: Vec<SubmissionFileMeta>,
     pub execution_results: Vec<ExecutionResultMeta>,
+    /// ケースごとのスコアの平均・標準偏差・最悪値 (`--repeat` で複数回実行した場合に意味を持つ)
+    pub case_stats: Vec<CaseStats>,
 }
 
 #[derive(Serialize, Deserialize)]
 pub struct SubmissionResponse {
     pub submission: Submission,
 }
+
+#[derive(Serialize, Deserialize)]
+pub struct SubmissionFileMeta {
+    /// バンドルのルートからの相対パス (`/` 区切り)
+    pub path: String,
+    pub size: u64,
+}
+
+/// 複数ファイルの提出に含まれる 1 ファイルの内容
+#[derive(Serialize, Deserialize)]
+pub struct SubmissionFileResponse {
+    pub path: String,
+    pub content: String,
+}
+
+/// 1 回分の実行の stdout / stderr。一覧には含めず、必要になったときに取得する
+#[derive(Serialize, Deserialize)]
+pub struct ExecutionOutputResponse {
+    pub stdout: String,
+    pub stderr: String,
+}
+
+/// 提出の説明の書き換え。省略したフィールドは変更しない (空文字列・`null` で消す)
+#[derive(Serialize, Deserialize)]
+pub struct EditSubmissionRequest {
+    #[serde(default)]
+    pub title: Option<String>,
+    #[serde(default)]
+    pub notes: Option<String>,
+    #[serde(default)]
+    pub author: Option<String>,
+    /// 指定するとタグをこの内容で置き換える
+    #[serde(default)]
+    pub tags: Option<Vec<String>>,
+    /// 省略すると変更せず、`null` なら親との関係を消す
+    #[serde(default, deserialize_with = "double_option")]
+    pub parent_id: Option<Option<i32>>,
+}
+
+/// フィールドがあれば (`null` でも) `Some` にする
+fn double_option<'de, D, T>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    Option::<T>::deserialize(deserializer).map(Some)
+}
+
+/// 削除 (または `keep_scores` なら出力の削除) の対象になった提出
+#[derive(Serialize, Deserialize)]
+pub struct PruneResponse {
+    pub pruned: Vec<i32>,
+}
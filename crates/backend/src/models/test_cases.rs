@@ -13,6 +13,22 @@ pub struct TestCasesResponse {
     pub test_cases: Vec<TestCaseMeta>,
 }
 
+#[derive(Serialize, Deserialize)]
+pub struct TestCaseSetMeta {
+    pub id: i32,
+    pub name: String,
+    pub number_of_test_cases: u64,
+    pub created_at: DateTime<Utc>,
+    pub archived_at: Option<DateTime<Utc>>,
+    /// 指定がないときに実行に使われるセットか
+    pub active: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct TestCaseSetsResponse {
+    pub test_case_sets: Vec<TestCaseSetMeta>,
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct TestCase {
     pub id: i32,
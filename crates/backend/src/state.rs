@@ -0,0 +1,120 @@
+use crate::handlers::remote::SharedRemoteQueue;
+use axum::extract::FromRef;
+use heurs_core::{AWSRunner, Config, ContainerRunner, LocalRunner, RemoteRunner, Runner};
+use heurs_database::DatabaseConnection;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// 起動時に一度だけ作り、axum の `State` で各ハンドラに渡す状態
+#[derive(Clone, FromRef)]
+pub struct AppState {
+    /// コネクションプール。クローンしても同じプールを共有する
+    pub db: DatabaseConnection,
+    /// 設定ファイルがなければ `None` (閲覧はできるが実行はできない)
+    pub config: Option<Arc<Config>>,
+    pub runners: Arc<RunnerRegistry>,
+    pub remote: SharedRemoteQueue,
+    /// ジョブとワーカーの API の共有トークン。なければそれらの API は閉じる
+    pub remote_token: Option<Arc<str>>,
+}
+
+/// 実行環境の名前
+pub const RUNNER_NAMES: [&str; 4] = ["local", "container", "aws", "remote"];
+
+/// 実行環境の名前 (`local` / `container` / `aws` / `remote`) から実行器を引く。
+/// Web UI から使わせる実行環境 (`--allow-env`) の実行器だけを持つ
+#[derive(Default)]
+pub struct RunnerRegistry {
+    runners: HashMap<&'static str, Arc<dyn Runner>>,
+}
+
+pub enum RunnerLookupError {
+    /// そのような実行環境はない
+    Unknown,
+    /// サーバーの設定で許可されていない
+    NotAllowed,
+}
+
+impl RunnerRegistry {
+    /// 設定ファイルの各セクションから、`allowed` に含まれる実行環境の実行器を作る
+    pub fn from_config(config: &Config, allowed: &[String]) -> Self {
+        let mut runners: HashMap<&'static str, Arc<dyn Runner>> = HashMap::new();
+        for name in RUNNER_NAMES {
+            if !allowed.iter().any(|a| a == name) {
+                continue;
+            }
+            let runner: Arc<dyn Runner> = match name {
+                "local" => Arc::new(LocalRunner::from_config(config)),
+                "container" => Arc::new(ContainerRunner::new(config.container.clone())),
+                "aws" => Arc::new(AWSRunner::new(config.aws.clone())),
+                _ => Arc::new(RemoteRunner::new(config.remote.clone())),
+            };
+            runners.insert(name, runner);
+        }
+        RunnerRegistry { runners }
+    }
+
+    pub fn get(&self, name: &str) -> Result<Arc<dyn Runner>, RunnerLookupError> {
+        match self.runners.get(name) {
+            Some(runner) => Ok(runner.clone()),
+            None if RUNNER_NAMES.contains(&name) => Err(RunnerLookupError::NotAllowed),
+            None => Err(RunnerLookupError::Unknown),
+        }
+    }
+
+    /// 使える実行環境の名前
+    pub fn names(&self) -> Vec<&'static str> {
+        RUNNER_NAMES
+            .into_iter()
+            .filter(|name| self.runners.contains_key(name))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use heurs_core::config::Execution;
+
+    fn config() -> Config {
+        Config {
+            problem: None,
+            database: Default::default(),
+            execution: Execution {
+                compile_cmd: "g++ {{src}}".to_string(),
+                exec_cmd: "./a.out".to_string(),
+            },
+            aws: Default::default(),
+            remote: Default::default(),
+            container: Default::default(),
+            sandbox: Default::default(),
+            cpu: Default::default(),
+        }
+    }
+
+    #[test]
+    fn registry_holds_only_the_allowed_runners() {
+        let allowed = ["remote".to_string(), "local".to_string()];
+        let registry = RunnerRegistry::from_config(&config(), &allowed);
+
+        // 許可した順ではなく RUNNER_NAMES の順に並ぶ
+        assert_eq!(registry.names(), ["local", "remote"]);
+        assert!(registry.get("local").is_ok());
+        assert!(registry.get("remote").is_ok());
+    }
+
+    #[test]
+    fn registry_rejects_disallowed_and_unknown_runners() {
+        let registry = RunnerRegistry::from_config(&config(), &["local".to_string()]);
+
+        assert!(matches!(
+            registry.get("aws"),
+            Err(RunnerLookupError::NotAllowed)
+        ));
+        assert!(matches!(
+            registry.get("gpu"),
+            Err(RunnerLookupError::Unknown)
+        ));
+        assert!(RunnerRegistry::default().names().is_empty());
+    }
+}
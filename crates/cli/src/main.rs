@@ -1,15 +1,22 @@
 use clap::{Args, Parser, Subcommand};
-use heurs_core::{AWSRunner, ExecutionResult, LocalRunner, Runner, load_config};
+use heurs_core::stats::case_stats;
+use heurs_core::{
+    AWSRunner, Bundle, ContainerRunner, ExecutionResult, LocalRunner, RemoteRunner, Runner, Source,
+    import, load_config, load_problem, resolve_database_url,
+};
 use heurs_database::{
-    DatabaseManager, ExecutionResultRepository, SubmissionRepository, TestCaseRepository,
+    DEFAULT_PROBLEM, DatabaseConnection, DatabaseManager, ExecutionResultRepository, ProblemModel,
+    ProblemRepository, PrunePolicy, SubmissionBundle, SubmissionEdit, SubmissionMetadata,
+    SubmissionRepository, TestCaseRepository, TestCaseSetModel, TestCaseSetRepository,
+    redact_database_url,
 };
-use sea_orm;
+use sea_orm::TransactionTrait;
+use std::collections::HashMap;
 use std::error::Error as StdError;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use thiserror::Error;
 
-// DbOpt を下で定義
 mod view;
 
 #[derive(Debug, Error)]
@@ -18,10 +25,38 @@ enum CliError {
     Db(#[from] sea_orm::DbErr),
     #[error("IO error: {0}")]
     Io(#[from] std::io::Error),
-    #[error("Failed to read directory entries")]
-    ReadDir,
+    #[error("Import error: {0}")]
+    Import(Box<dyn StdError + Send + Sync>),
     #[error("Execution error: {0}")]
     Execution(Box<dyn StdError + Send + Sync>),
+    #[error("Config error: {0}")]
+    Config(Box<dyn StdError + Send + Sync>),
+    #[error("Invalid problem name '{0}' (use letters, digits, '-' and '_')")]
+    InvalidProblem(String),
+    #[error("Problem '{0}' not found")]
+    ProblemNotFound(String),
+    #[error("Submission {0} not found in problem '{1}'")]
+    SubmissionNotFound(i32, String),
+    #[error("Test case set '{0}' not found in problem '{1}'")]
+    TestCaseSetNotFound(String, String),
+    #[error("Problem '{0}' has no active test case set (add cases with `heurs test-case add`)")]
+    NoActiveTestCaseSet(String),
+    #[error("Test case set '{0}' is archived")]
+    TestCaseSetArchived(String),
+    #[error("Test case set '{0}' already has submissions and cannot be changed")]
+    TestCaseSetInUse(String),
+    #[error(
+        "Submission {0} cannot be the parent (it must exist in the same problem and not be a descendant)"
+    )]
+    InvalidParent(i32),
+    #[error("No execution result for test case {1} (repeat {2}) in submission {0}")]
+    ExecutionResultNotFound(i32, i32, i32),
+    #[error("Bundle error: {0}")]
+    Bundle(Box<dyn StdError + Send + Sync>),
+    #[error("Git error: {0}")]
+    Git(Box<dyn StdError + Send + Sync>),
+    #[error("'{0}' already exists and is not an empty directory")]
+    CheckoutDestinationNotEmpty(PathBuf),
 }
 
 #[derive(Parser)]
@@ -31,23 +66,38 @@ enum CliError {
     about = "Heuristics contest helper tool"
 )]
 struct Cli {
+    /// 対象の問題名。省略時は heurs.toml の `problem`、それもなければ "default"
+    #[arg(long, global = true)]
+    problem: Option<String>,
+
+    #[command(flatten)]
+    db: DbOpt,
+
     #[command(subcommand)]
     command: Commands,
 }
 
 #[derive(Args, Clone, Debug)]
 struct DbOpt {
-    /// データベースURL
-    #[arg(short, long, default_value = "sqlite://heurs.db")]
-    database_url: String,
+    /// データベースURL (`sqlite://...` / `postgres://...`)。省略時は heurs.toml の `[database] url`、それもなければ sqlite://heurs.db
+    #[arg(short, long, global = true, env = "HEURS_DATABASE_URL")]
+    database_url: Option<String>,
 }
 
 #[derive(Subcommand)]
 enum Commands {
     Run {
-        // ソースコードのパス
+        // ソースコードのパス。ディレクトリなら中身をまとめて (バンドルとして) 提出する
         source_path: PathBuf,
 
+        // ディレクトリを提出するときのエントリポイント (ディレクトリからの相対パス)。`{{src}}` に入る
+        #[arg(long)]
+        entry: Option<PathBuf>,
+
+        // バンドルに含めるファイルの glob (複数回指定可)。ファイルを提出するときはカレントディレクトリからの相対パス
+        #[arg(long = "include")]
+        includes: Vec<String>,
+
         // テストケースの数
         #[arg(short, long, default_value = "10")]
         cases: u32,
@@ -60,27 +110,130 @@ enum Commands {
         #[arg(short, long, default_value = "10")]
         timeout: u32,
 
+        // 各ケースの実行回数。回ごとに HEURS_RNG_SEED = 0, 1, ... を渡す
+        #[arg(short, long, default_value = "1")]
+        repeat: u32,
+
+        // 実行するテストケースのセット名。指定がなければ現在のセット
+        #[arg(short, long)]
+        set: Option<String>,
+
         // 設定ファイルパス
         #[arg(long, default_value = "heurs.toml")]
         config: PathBuf,
 
-        #[command(flatten)]
-        db: DbOpt,
-
-        // 実行環境 (local / aws など)。指定がなければ環境変数 HEURS_ENV を使用。
+        // 実行環境 (local / container / aws / remote)。指定がなければ環境変数 HEURS_ENV を使用。
         #[arg(short, long)]
         env: Option<String>,
+
+        #[command(flatten)]
+        metadata: MetadataArgs,
     },
     TestCase(TestCaseArgs),
     LeaderBoard {
-        #[command(flatten)]
-        db: DbOpt,
-
         // 何件表示するか
         #[arg(short, long, default_value = "10")]
         limit: u32,
+
+        // このテストケースのセットで実行した提出だけを比べる
+        #[arg(short, long)]
+        set: Option<String>,
+
+        // このタグの付いたケースの結果だけで比べる
+        #[arg(long)]
+        tag: Option<String>,
     },
     Submission(SubmissionArgs),
+    Problem(ProblemArgs),
+    Db(DbArgs),
+    /// 古い提出や平均スコアの低い提出を実行結果ごと削除する
+    #[command(group(
+        clap::ArgGroup::new("criteria")
+            .required(true)
+            .multiple(true)
+            .args(["keep_best", "older_than"])
+    ))]
+    Prune {
+        // テストケースのセットごとに平均スコアの上位 N 件を残す
+        #[arg(long)]
+        keep_best: Option<u32>,
+
+        // これより古い提出だけを消す (例: 14d, 12h, 2w)
+        #[arg(long, value_parser = PrunePolicy::parse_age)]
+        older_than: Option<chrono::Duration>,
+
+        // タグの付いた提出を残す
+        #[arg(long)]
+        keep_tagged: bool,
+
+        // 提出とスコアは残し、stdout / stderr だけを消す
+        #[arg(long)]
+        keep_scores: bool,
+
+        // 消す提出を表示するだけで、実際には消さない
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// 基準ベンチマークでこのマシンの速度を測り、速度係数を表示する
+    Calibrate {
+        // 設定ファイルパス
+        #[arg(long, default_value = "heurs.toml")]
+        config: PathBuf,
+
+        // 計測の繰り返し回数 (中央値を採用)
+        #[arg(short, long, default_value = "5")]
+        runs: u32,
+    },
+    /// バックエンドからケースを借りて実行するワーカーとして動く
+    Worker {
+        // ワーカーを束ねるバックエンドの URL
+        #[arg(short, long, default_value = "http://localhost:3000")]
+        server: String,
+
+        // ワーカー名 (一覧表示用)。指定がなければホスト名を使用。
+        #[arg(short, long)]
+        name: Option<String>,
+
+        // 並列実行数
+        #[arg(short, long, default_value = "1")]
+        parallel: u32,
+
+        // 作業ディレクトリ。同じマシンで複数起動する場合は別々にすること。
+        #[arg(long)]
+        work_dir: Option<PathBuf>,
+
+        // バックエンドと共有するトークン。省略時は設定ファイルの `[remote] token`
+        #[arg(long, env = "HEURS_REMOTE_TOKEN", hide_env_values = true)]
+        token: Option<String>,
+
+        // 設定ファイルパス。あれば `[sandbox]` / `[cpu]` を実行に反映する
+        #[arg(long, default_value = "heurs.toml")]
+        config: PathBuf,
+    },
+}
+
+// 提出に付ける説明 (`heurs run`)
+#[derive(Args, Debug)]
+struct MetadataArgs {
+    // 提出のタイトル
+    #[arg(long)]
+    title: Option<String>,
+
+    // 自由記述のメモ
+    #[arg(long)]
+    notes: Option<String>,
+
+    // タグ。複数回指定できる (`--tag annealing --tag v2`)
+    #[arg(long = "tag")]
+    tags: Vec<String>,
+
+    // 作者名
+    #[arg(long, env = "HEURS_AUTHOR")]
+    author: Option<String>,
+
+    // 元にした提出の ID
+    #[arg(long)]
+    parent: Option<i32>,
 }
 
 #[derive(Parser, Debug)]
@@ -92,14 +245,114 @@ struct SubmissionArgs {
 #[derive(Subcommand, Debug)]
 enum SubmissionCommands {
     Describe {
-        #[command(flatten)]
-        db: DbOpt,
+        #[arg(short, long)]
+        submission_id: i32,
+    },
+    /// 既知の最良スコアとの差が大きい (伸びしろのある) ケースを表示する
+    Gaps {
+        // 対象の提出。指定がなければ最新の提出
+        #[arg(short, long)]
+        submission_id: Option<i32>,
+
+        // 何件表示するか
+        #[arg(short, long, default_value = "10")]
+        limit: u32,
+    },
+    /// 提出のタイトル・メモ・タグ・作者・親を書き換える (空文字列で消す)
+    Edit {
+        #[arg(short, long)]
+        submission_id: i32,
+
+        #[arg(long)]
+        title: Option<String>,
+
+        #[arg(long)]
+        notes: Option<String>,
+
+        #[arg(long)]
+        author: Option<String>,
+
+        // 追加するタグ (複数回指定できる)
+        #[arg(long = "add-tag")]
+        add_tags: Vec<String>,
 
+        // 外すタグ (複数回指定できる)
+        #[arg(long = "remove-tag")]
+        remove_tags: Vec<String>,
+
+        // 元にした提出の ID
+        #[arg(long, conflicts_with = "no_parent")]
+        parent: Option<i32>,
+
+        // 親との関係を消す
+        #[arg(long)]
+        no_parent: bool,
+    },
+    /// 提出を実行結果ごと削除する
+    Delete {
+        #[arg(required = true)]
+        submission_ids: Vec<i32>,
+
+        // 提出とスコアは残し、stdout / stderr だけを消す
+        #[arg(long)]
+        keep_scores: bool,
+    },
+    /// 提出したときのソースを復元する (git リポジトリ内で実行した提出はコミットと未コミットの変更ごと)
+    Checkout {
+        #[arg(short, long)]
+        submission_id: i32,
+
+        // 復元先のディレクトリ (存在しないか空であること)
+        path: PathBuf,
+
+        // コミットを取り出すリポジトリ (その中のどのディレクトリでもよい)
+        #[arg(long, default_value = ".")]
+        repo: PathBuf,
+    },
+    /// 1 回分の実行の stdout / stderr を表示する
+    Output {
         #[arg(short, long)]
         submission_id: i32,
+
+        #[arg(short = 'c', long)]
+        test_case_id: i32,
+
+        // 何回目の実行か (0 始まり)
+        #[arg(short, long, default_value = "0")]
+        repeat: i32,
     },
 }
 
+#[derive(Parser, Debug)]
+struct ProblemArgs {
+    #[command(subcommand)]
+    command: ProblemCommands,
+}
+
+#[derive(Subcommand, Debug)]
+enum ProblemCommands {
+    /// 登録されている問題の一覧
+    List {},
+    /// 問題を登録する
+    Add { name: String },
+}
+
+#[derive(Parser, Debug)]
+struct DbArgs {
+    #[command(subcommand)]
+    command: DbCommands,
+}
+
+#[derive(Subcommand, Debug)]
+enum DbCommands {
+    /// 未適用のマイグレーションを適用する (ほかのコマンドも起動時に自動で適用する)
+    Migrate {},
+    /// マイグレーションごとの適用状況
+    Status {},
+    /// 圧縮せずに保存されている大きな stdout / stderr を圧縮する
+    Compact {},
+}
+
 #[derive(Parser, Debug)]
 struct TestCaseArgs {
     #[command(subcommand)]
@@ -108,19 +361,101 @@ struct TestCaseArgs {
 
 #[derive(Subcommand, Debug)]
 enum TestCaseCommands {
+    /// ケースをセットに登録する。同じ内容のケースは登録済みのものを使い回す
     Add(AddArgs),
-    Clear {},
+    /// テストケースのセットの一覧
+    List {},
+    /// 既知の最良スコアを外部から取り込む (マニフェストか、チームメイトの DB の URL)
+    ImportBest {
+        // `best_known` を書いたマニフェスト (CSV / JSON) か、`sqlite://...` などの DB の URL
+        source: String,
+
+        // マニフェストのファイル名を照合するセット。指定がなければ現在のセット
+        #[arg(short, long)]
+        set: Option<String>,
+    },
+    /// セットをアーカイブする。ケースや過去の提出の結果は残る
+    #[command(alias = "clear")]
+    Archive {
+        // アーカイブするセット名。指定がなければ現在のセット
+        set: Option<String>,
+    },
 }
 
 #[derive(Parser, Debug)]
 struct AddArgs {
+    // ケースファイルのディレクトリ、または zip / tar.gz アーカイブ (公式の in.zip など)
     #[arg(short, long)]
     input_path: PathBuf,
+
+    // シード・タグ・既知の最良スコアを書いたマニフェスト (CSV / JSON)
+    #[arg(short, long)]
+    manifest: Option<PathBuf>,
+
+    // 登録先のセット名。なければ作る。指定がなければ現在のセット (それもなければ新しいセット)
+    #[arg(short, long)]
+    set: Option<String>,
+}
+
+/// 対象の問題名を決める (`--problem` > 設定ファイルの `problem` > 既定の問題)
+fn resolve_problem(flag: Option<String>, config_path: &Path) -> Result<String, CliError> {
+    let name = match flag {
+        Some(name) => name,
+        None => load_problem(config_path)
+            .map_err(CliError::Config)?
+            .unwrap_or_else(|| DEFAULT_PROBLEM.to_string()),
+    };
+    if !ProblemRepository::is_valid_name(&name) {
+        return Err(CliError::InvalidProblem(name));
+    }
+    Ok(name)
+}
+
+/// 名前で指定されたセット、なければ現在のセットを取得する
+async fn find_test_case_set(
+    db: &DatabaseConnection,
+    problem: &ProblemModel,
+    name: Option<&str>,
+) -> Result<TestCaseSetModel, CliError> {
+    TestCaseSetRepository::resolve(db, problem.id, name)
+        .await?
+        .ok_or_else(|| match name {
+            Some(name) => CliError::TestCaseSetNotFound(name.to_string(), problem.name.clone()),
+            None => CliError::NoActiveTestCaseSet(problem.name.clone()),
+        })
+}
+
+/// DB に接続する (`--database-url` / `HEURS_DATABASE_URL` > 設定ファイルの `[database] url` > 既定の URL)
+///
+/// 未適用のマイグレーションがあれば適用してから返す。
+async fn connect_db(opt: DbOpt, config_path: &Path) -> Result<DatabaseConnection, CliError> {
+    let url = resolve_database_url(opt.database_url, config_path).map_err(CliError::Config)?;
+    let (db, applied) = DatabaseManager::connect_and_migrate(&url).await?;
+    for name in applied {
+        eprintln!("Applied migration '{}'", name);
+    }
+    Ok(db)
+}
+
+/// 提出 ID の一覧を表示用にカンマ区切りにする
+fn join_ids(ids: &[i32]) -> String {
+    ids.iter()
+        .map(|id| id.to_string())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// 登録済みの問題を名前で取得する。参照系のコマンドでは勝手に作らない
+async fn find_problem(db: &DatabaseConnection, name: &str) -> Result<ProblemModel, CliError> {
+    ProblemRepository::find_by_name(db, name)
+        .await?
+        .ok_or_else(|| CliError::ProblemNotFound(name.to_string()))
 }
 
 #[tokio::main]
 async fn main() -> std::result::Result<(), CliError> {
     let cli = Cli::parse();
+    let default_config = PathBuf::from("heurs.toml");
 
     match cli.command {
         Commands::TestCase(args) => match args.command {
@@ -130,144 +465,892 @@ async fn main() -> std::result::Result<(), CliError> {
                     add_args.input_path.display()
                 );
 
-                let db = DatabaseManager::connect("sqlite://heurs.db").await?;
-                let entries =
-                    std::fs::read_dir(&add_args.input_path).map_err(|_| CliError::ReadDir)?;
-
-                let mut count = 0;
-                for entry in entries {
-                    let entry = entry?;
-                    let path = entry.path();
-                    if path.is_file() {
-                        if let Some(ext) = path.extension().and_then(|s| s.to_str()) {
-                            if ext == "txt" || ext == "in" {
-                                let input_data = std::fs::read_to_string(&path)?;
-                                let filename = path
-                                    .file_name()
-                                    .and_then(|s| s.to_str())
-                                    .unwrap_or_default()
-                                    .to_string();
-                                TestCaseRepository::create(&db, input_data, filename).await?;
-                                println!("Registered test case: {}", path.display());
-                                count += 1;
+                let files = import::read_cases(&add_args.input_path).map_err(CliError::Import)?;
+                let manifest = match &add_args.manifest {
+                    Some(path) => import::load_manifest(path).map_err(CliError::Import)?,
+                    None => HashMap::new(),
+                };
+
+                let problem_name = resolve_problem(cli.problem, &default_config)?;
+                let db = connect_db(cli.db, &default_config).await?;
+                // 途中で失敗したときに、セットだけ作られたり一部のケースだけ加わったりしないよう
+                // 取り込み全体を 1 つのトランザクションにする
+                let txn = db.begin().await?;
+                let problem = ProblemRepository::find_or_create(&txn, &problem_name).await?;
+
+                // 指定されたセット (なければ作る)、なければ現在のセット、それもなければ新しいセット
+                let existing =
+                    TestCaseSetRepository::resolve(&txn, problem.id, add_args.set.as_deref())
+                        .await?;
+                let set = match existing {
+                    Some(set) if set.archived_at.is_some() => {
+                        return Err(CliError::TestCaseSetArchived(set.name));
+                    }
+                    Some(base) => {
+                        // 提出が実行したセットの中身は変えない (増えるケースがなければそのまま)
+                        let base_cases = TestCaseRepository::find_by_set(&txn, base.id).await?;
+                        let changes = files.iter().any(|f| {
+                            let hash = TestCaseRepository::content_hash(&f.input);
+                            !base_cases.iter().any(|c| c.content_hash == hash)
+                        });
+                        if changes && SubmissionRepository::count_by_set(&txn, base.id).await? > 0 {
+                            if add_args.set.is_some() {
+                                return Err(CliError::TestCaseSetInUse(base.name));
+                            }
+                            // 現在のセットが使用済みなら、その中身を引き継いだ次の版を作る
+                            let name = TestCaseSetRepository::next_name(&txn, problem.id).await?;
+                            let set = TestCaseSetRepository::create(&txn, problem.id, name).await?;
+                            for test_case in &base_cases {
+                                TestCaseSetRepository::add_case(&txn, set.id, test_case.id).await?;
                             }
+                            println!(
+                                "Created test case set '{}' from '{}' (it already has submissions).",
+                                set.name, base.name
+                            );
+                            set
+                        } else {
+                            base
                         }
                     }
+                    None => {
+                        let name = match add_args.set {
+                            Some(name) => name,
+                            None => TestCaseSetRepository::next_name(&txn, problem.id).await?,
+                        };
+                        let set = TestCaseSetRepository::create(&txn, problem.id, name).await?;
+                        println!("Created test case set '{}'.", set.name);
+                        set
+                    }
+                };
+
+                // セット内のファイル名 -> 内容のハッシュ (同名で中身の違うケースを弾くため)
+                let mut in_set: HashMap<String, String> =
+                    TestCaseRepository::find_by_set(&txn, set.id)
+                        .await?
+                        .into_iter()
+                        .map(|c| (c.filename, c.content_hash))
+                        .collect();
+
+                let mut records = Vec::new();
+                for file in &files {
+                    let hash = TestCaseRepository::content_hash(&file.input);
+                    let entry = manifest.get(&file.filename);
+                    let conflict = |note: String| view::ImportRecord {
+                        filename: file.filename.clone(),
+                        outcome: view::ImportOutcome::Conflict,
+                        test_case_id: None,
+                        note,
+                    };
+
+                    if in_set.get(&file.filename).is_some_and(|h| *h != hash) {
+                        records.push(conflict(format!(
+                            "set '{}' already has a different {}",
+                            set.name, file.filename
+                        )));
+                        continue;
+                    }
+
+                    // ケースは書き換えず、同じ内容なら登録済みのものをセットに加える
+                    let (test_case, outcome, note) =
+                        match TestCaseRepository::find_by_content_hash(&txn, problem.id, &hash)
+                            .await?
+                        {
+                            Some(test_case) => {
+                                if let (Some(registered), Some(seed)) =
+                                    (test_case.seed, entry.and_then(|e| e.seed))
+                                    && registered != seed
+                                {
+                                    records.push(conflict(format!(
+                                        "manifest seed {} differs from registered seed {}",
+                                        seed, registered
+                                    )));
+                                    continue;
+                                }
+                                let note = if test_case.filename == file.filename {
+                                    "already registered".to_string()
+                                } else {
+                                    format!("same input as {}", test_case.filename)
+                                };
+                                (test_case, view::ImportOutcome::Skipped, note)
+                            }
+                            None => (
+                                TestCaseRepository::create(
+                                    &txn,
+                                    problem.id,
+                                    file.input.clone(),
+                                    file.filename.clone(),
+                                )
+                                .await?,
+                                view::ImportOutcome::Added,
+                                String::new(),
+                            ),
+                        };
+
+                    let test_case = match entry {
+                        Some(entry) => {
+                            TestCaseRepository::merge_metadata(
+                                &txn,
+                                test_case,
+                                entry.seed,
+                                &entry.tags,
+                                entry.best_known,
+                            )
+                            .await?
+                        }
+                        None => test_case,
+                    };
+                    TestCaseSetRepository::add_case(&txn, set.id, test_case.id).await?;
+                    in_set.insert(test_case.filename.clone(), hash);
+
+                    records.push(view::ImportRecord {
+                        filename: file.filename.clone(),
+                        outcome,
+                        test_case_id: Some(test_case.id),
+                        note,
+                    });
                 }
-                println!("\nSuccessfully added {} test cases.", count);
+
+                // マニフェストにあるのに見つからなかったファイル
+                let mut missing: Vec<&String> = manifest
+                    .keys()
+                    .filter(|name| !files.iter().any(|f| &f.filename == *name))
+                    .collect();
+                missing.sort();
+                for filename in missing {
+                    records.push(view::ImportRecord {
+                        filename: filename.clone(),
+                        outcome: view::ImportOutcome::Conflict,
+                        test_case_id: None,
+                        note: "listed in manifest but not found".to_string(),
+                    });
+                }
+
+                let count = TestCaseSetRepository::count_cases(&txn, set.id).await?;
+                txn.commit().await?;
+
+                view::render_import_report(&records);
+                println!(
+                    "Test case set '{}' of problem '{}' now has {} test cases.",
+                    set.name, problem.name, count
+                );
             }
-            TestCaseCommands::Clear {} => {
-                println!("Clearing all test cases...");
-                let db = DatabaseManager::connect("sqlite://heurs.db").await?;
-                let result = TestCaseRepository::clear(&db).await?;
-                println!("Successfully deleted {} test cases.", result.rows_affected);
+            TestCaseCommands::List {} => {
+                let problem_name = resolve_problem(cli.problem, &default_config)?;
+                let db = connect_db(cli.db, &default_config).await?;
+                let problem = find_problem(&db, &problem_name).await?;
+
+                let sets = TestCaseSetRepository::find_by_problem(&db, problem.id).await?;
+                let active = TestCaseSetRepository::find_active(&db, problem.id).await?;
+                let mut counts = Vec::with_capacity(sets.len());
+                for set in &sets {
+                    counts.push((
+                        TestCaseSetRepository::count_cases(&db, set.id).await?,
+                        SubmissionRepository::count_by_set(&db, set.id).await?,
+                    ));
+                }
+
+                view::render_test_case_sets(&sets, &counts, active.map(|s| s.id));
+            }
+            TestCaseCommands::ImportBest { source, set } => {
+                let problem_name = resolve_problem(cli.problem, &default_config)?;
+                let db = connect_db(cli.db, &default_config).await?;
+                let problem = find_problem(&db, &problem_name).await?;
+
+                // (ケース, 取り込む値) の組を集める
+                let mut candidates = Vec::new();
+                if source.contains("://") {
+                    // 同じ問題の同じ内容のケースを突き合わせる
+                    let other = DatabaseManager::connect(&source).await?;
+                    let other_problem = find_problem(&other, &problem.name).await?;
+                    for other_case in
+                        TestCaseRepository::find_by_problem(&other, other_problem.id).await?
+                    {
+                        let Some(best) = other_case.best_known_score else {
+                            continue;
+                        };
+                        if let Some(test_case) = TestCaseRepository::find_by_content_hash(
+                            &db,
+                            problem.id,
+                            &other_case.content_hash,
+                        )
+                        .await?
+                        {
+                            candidates.push((test_case, best));
+                        }
+                    }
+                } else {
+                    let manifest =
+                        import::load_manifest(Path::new(&source)).map_err(CliError::Import)?;
+                    let set = find_test_case_set(&db, &problem, set.as_deref()).await?;
+                    for test_case in TestCaseRepository::find_by_set(&db, set.id).await? {
+                        if let Some(best) =
+                            manifest.get(&test_case.filename).and_then(|e| e.best_known)
+                        {
+                            candidates.push((test_case, best));
+                        }
+                    }
+                }
+
+                let mut updated = 0;
+                for (test_case, best) in candidates {
+                    let previous = test_case.best_known_score;
+                    let test_case =
+                        TestCaseRepository::merge_metadata(&db, test_case, None, &[], Some(best))
+                            .await?;
+                    if test_case.best_known_score != previous {
+                        println!(
+                            "{}: {} -> {}",
+                            test_case.filename,
+                            previous.map(|s| s.to_string()).unwrap_or("-".to_string()),
+                            best
+                        );
+                        updated += 1;
+                    }
+                }
+                println!(
+                    "\nUpdated best-known scores of {} test cases in problem '{}'.",
+                    updated, problem.name
+                );
+            }
+            TestCaseCommands::Archive { set } => {
+                let problem_name = resolve_problem(cli.problem, &default_config)?;
+                let db = connect_db(cli.db, &default_config).await?;
+                let problem = find_problem(&db, &problem_name).await?;
+                let set = find_test_case_set(&db, &problem, set.as_deref()).await?;
+
+                if set.archived_at.is_some() {
+                    println!("Test case set '{}' is already archived.", set.name);
+                } else {
+                    let set = TestCaseSetRepository::archive(&db, set).await?;
+                    println!(
+                        "Archived test case set '{}' of problem '{}'. Past submissions keep their results.",
+                        set.name, problem.name
+                    );
+                }
             }
         },
         Commands::Run {
             source_path,
+            entry,
+            includes,
             cases,
             parallel,
             timeout,
+            repeat,
+            set,
             config,
-            db,
             env,
+            metadata,
         } => {
+            let repeat = repeat.max(1);
+            let problem_name = resolve_problem(cli.problem, &config)?;
             // データベース接続を確立
-            let db = DatabaseManager::connect(&db.database_url).await?;
+            let database_url = resolve_database_url(cli.db.database_url.clone(), &config)
+                .map_err(CliError::Config)?;
+            let db = connect_db(cli.db, &config).await?;
+            let config_path = config;
+            let mut config = load_config(&config_path).map_err(CliError::Config)?;
+            // サンドボックス内から設定ファイルや DB を読めないようにする
+            config
+                .sandbox
+                .hide_config_and_database(&config_path, &database_url);
+
+            let problem = ProblemRepository::find_or_create(&db, &problem_name).await?;
+            // アーカイブ済みのセットも名前を指定すれば実行できる (過去の提出との比較用)
+            let set = find_test_case_set(&db, &problem, set.as_deref()).await?;
+
+            // ディレクトリか --include の指定があれば、複数ファイルのバンドルとして提出する
+            let (source, entry_path) = if source_path.is_dir() {
+                let entry = entry.ok_or_else(|| {
+                    CliError::Bundle("--entry is required when submitting a directory".into())
+                })?;
+                let bundle = Bundle::from_dir(&source_path, &entry.to_string_lossy(), &includes)
+                    .map_err(CliError::Bundle)?;
+                (Source::Bundle(bundle), source_path.join(&entry))
+            } else if entry.is_some() {
+                return Err(CliError::Bundle(
+                    "--entry can only be used when submitting a directory".into(),
+                ));
+            } else if !includes.is_empty() {
+                // ファイルと glob はカレントディレクトリを基準にまとめる
+                let bundle =
+                    Bundle::from_dir(Path::new("."), &source_path.to_string_lossy(), &includes)
+                        .map_err(CliError::Bundle)?;
+                (Source::Bundle(bundle), source_path.clone())
+            } else {
+                (Source::File(source_path.clone()), source_path.clone())
+            };
+            if let Source::Bundle(bundle) = &source {
+                let files = bundle.files().map_err(CliError::Bundle)?;
+                println!(
+                    "Bundled {} files ({} bytes compressed, entry point: {})",
+                    files.len(),
+                    bundle.archive().len(),
+                    bundle.entry()
+                );
+            }
+
+            // ソースコード (バンドルならエントリポイント) を読み込み
+            let source_code = fs::read_to_string(&entry_path)?;
+            // git リポジトリ内なら HEAD と未コミットの変更も記録する
+            let location = heurs_core::git::locate(&entry_path);
 
-            // ソースコードを読み込み
-            let source_code = fs::read_to_string(&source_path)?;
+            if let Some(parent) = metadata.parent
+                && !SubmissionRepository::is_valid_parent(&db, problem.id, None, parent).await?
+            {
+                return Err(CliError::InvalidParent(parent));
+            }
 
-            // submissionをデータベースに保存
-            let submission = SubmissionRepository::create(&db, source_code.clone()).await?;
-            println!("Submission saved with ID: {}", submission.id);
+            // HEURS_ENV の値に応じて Runner を切り替える。不明な値ならローカルで実行する
+            let env_mode = env
+                .unwrap_or_else(|| {
+                    std::env::var("HEURS_ENV").unwrap_or_else(|_| "local".to_string())
+                })
+                .to_ascii_lowercase();
+            let runner_name = match env_mode.as_str() {
+                "aws" | "remote" | "container" => env_mode.as_str(),
+                _ => "local",
+            };
+            // 実行時間はマシンで変わるので、比べるときのために実行した環境も記録する
+            let environment = heurs_core::environment::capture(
+                runner_name,
+                &source,
+                &config.execution.compile_cmd,
+                &config.execution.exec_cmd,
+                parallel,
+                &config.container,
+            )
+            .await;
 
-            let test_cases = TestCaseRepository::find_limit(&db, cases as u64).await?;
+            let test_cases = TestCaseRepository::find_limit(&db, set.id, cases as u64).await?;
 
             // Runner 用にクローンを渡し、元の test_cases は後続の表示に再利用する
             let runner_test_cases = test_cases.clone();
 
-            let config = load_config(&config).unwrap();
+            let runner: Box<dyn Runner> = match runner_name {
+                "aws" => Box::new(AWSRunner::new(config.aws.clone())),
+                "remote" => Box::new(RemoteRunner::new(config.remote.clone())),
+                "container" => Box::new(ContainerRunner::new(config.container.clone())),
+                _ => Box::new(LocalRunner::from_config(&config)),
+            };
 
-            // HEURS_ENV の値に応じて Runner を切り替える。
-            let env_mode = env.unwrap_or_else(|| {
-                std::env::var("HEURS_ENV").unwrap_or_else(|_| "local".to_string())
+            // 終わったケースから順に進捗を表示する
+            let (progress_tx, mut progress_rx) = tokio::sync::mpsc::unbounded_channel();
+            let progress_test_cases = test_cases.clone();
+            let progress_printer = tokio::spawn(async move {
+                let total = progress_test_cases.len() * repeat as usize;
+                let mut done = 0;
+                while let Some(result) = progress_rx.recv().await {
+                    done += 1;
+                    view::render_progress(done, total, &result, &progress_test_cases, repeat);
+                }
             });
 
-            let runner: Box<dyn Runner> = match env_mode.to_ascii_lowercase().as_str() {
-                "aws" => Box::new(AWSRunner::new()),
-                _ => Box::new(LocalRunner::new()),
-            };
             let execution_results = runner
-                .execute(
-                    &source_path,
+                .execute_with_progress(
+                    &source,
                     &config.execution.compile_cmd,
                     &config.execution.exec_cmd,
                     parallel,
                     runner_test_cases,
+                    repeat,
                     timeout,
+                    Some(progress_tx),
                 )
                 .await
                 .map_err(CliError::Execution)?;
+            let _ = progress_printer.await;
 
             println!("実行に成功しました");
 
-            // 実行結果をデータベースに保存
-            for result in &execution_results {
-                match ExecutionResultRepository::create(
-                    &db,
-                    submission.id as i64,
-                    result.test_case_id as i64,
-                    result.success,
-                    result.stdout.clone(),
-                    result.stderr.clone(),
-                    result.score,
-                    result.execution_time_ms,
-                )
-                .await
-                {
-                    Ok(_) => {}
-                    Err(e) => {
-                        eprintln!(
-                            "Failed to save test case {} result: {}",
-                            result.test_case_id, e
-                        );
-                    }
-                }
-            }
+            // 実行が終わってから提出と実行結果をまとめて保存する。コンパイルエラーなどで
+            // 実行できなかった提出は残さず、保存の途中で失敗したときも何も残さない
+            let (submission, new_bests) = SubmissionRepository::create_with_results(
+                &db,
+                problem.id,
+                set.id,
+                source_code,
+                SubmissionMetadata {
+                    title: metadata.title,
+                    notes: metadata.notes,
+                    tags: metadata.tags,
+                    author: metadata.author,
+                    parent_id: metadata.parent,
+                    source_path: Some(location.source_path),
+                    git: location.git,
+                    bundle: match &source {
+                        Source::Bundle(bundle) => Some(SubmissionBundle {
+                            entry: bundle.entry().to_string(),
+                            archive: bundle.archive().to_vec(),
+                        }),
+                        Source::File(_) => None,
+                    },
+                    environment: Some(environment),
+                },
+                execution_results.iter().cloned().map(Into::into),
+            )
+            .await?;
+            println!(
+                "Submission saved with ID: {} (problem: {}, test case set: {})",
+                submission.id, problem.name, set.name
+            );
 
+            // 最良スコアに対する割合は更新後の値で出す
+            let test_cases =
+                TestCaseRepository::find_by_ids(&db, test_cases.iter().map(|c| c.id).collect())
+                    .await?;
             view::render_execution_results(&execution_results, &test_cases);
+            if new_bests > 0 {
+                println!("New best-known scores on {} test cases.", new_bests);
+            }
 
-            view::render_submission_summary(&submission, &execution_results);
+            view::render_submission_summary(&submission, Some(&set), &execution_results);
         }
-        Commands::LeaderBoard { db, limit } => {
-            let db = DatabaseManager::connect(&db.database_url).await?;
+        Commands::LeaderBoard { limit, set, tag } => {
+            let problem_name = resolve_problem(cli.problem, &default_config)?;
+            let db = connect_db(cli.db, &default_config).await?;
+            let problem = find_problem(&db, &problem_name).await?;
 
-            let submissions = SubmissionRepository::find_all(&db).await?;
-            let execution_results = ExecutionResultRepository::find_all(&db).await?;
+            // セットが違う提出同士のスコアは比べられないので、指定があれば絞る
+            let submissions = match set {
+                Some(name) => {
+                    let set = find_test_case_set(&db, &problem, Some(&name)).await?;
+                    SubmissionRepository::find_by_set(&db, set.id).await?
+                }
+                None => SubmissionRepository::find_by_problem(&db, problem.id).await?,
+            };
+            let sets = TestCaseSetRepository::find_by_problem(&db, problem.id).await?;
+            let test_case_ids = match tag {
+                Some(tag) => {
+                    Some(TestCaseRepository::find_ids_by_tag(&db, problem.id, &tag).await?)
+                }
+                None => None,
+            };
+            let aggregates = ExecutionResultRepository::aggregate_by_submission(
+                &db,
+                submissions.iter().map(|s| s.id).collect(),
+                test_case_ids,
+            )
+            .await?;
 
-            view::render_leaderboard(&submissions, &execution_results, limit);
+            view::render_leaderboard(&submissions, &sets, &aggregates, limit);
         }
         Commands::Submission(args) => match args.command {
-            SubmissionCommands::Describe { db, submission_id } => {
-                let db = DatabaseManager::connect(&db.database_url).await?;
+            SubmissionCommands::Describe { submission_id } => {
+                let problem_name = resolve_problem(cli.problem, &default_config)?;
+                let db = connect_db(cli.db, &default_config).await?;
+                let problem = find_problem(&db, &problem_name).await?;
+
+                // 別の問題の提出は見せない
+                let submission = SubmissionRepository::find_by_id(&db, submission_id)
+                    .await?
+                    .filter(|s| s.problem_id == problem.id)
+                    .ok_or_else(|| CliError::SubmissionNotFound(submission_id, problem_name))?;
 
                 let execution_results =
-                    ExecutionResultRepository::find_by_submission_id(&db, submission_id as i64)
+                    ExecutionResultRepository::find_summaries_by_submission_id(&db, submission_id)
                         .await?;
                 let execution_results = execution_results
                     .iter()
                     .map(|r| r.into())
                     .collect::<Vec<ExecutionResult>>();
 
-                let submission =
-                    SubmissionRepository::find_by_id(&db, submission_id as i32).await?;
-                let test_cases = TestCaseRepository::find_all(&db).await?;
+                let test_cases = TestCaseRepository::find_by_problem(&db, problem.id).await?;
+                let set = match submission.test_case_set_id {
+                    Some(id) => TestCaseSetRepository::find_by_id(&db, id).await?,
+                    None => None,
+                };
 
                 view::render_execution_results(&execution_results, &test_cases);
-                view::render_submission_summary(&submission.unwrap(), &execution_results);
+                view::render_submission_summary(&submission, set.as_ref(), &execution_results);
+            }
+            SubmissionCommands::Gaps {
+                submission_id,
+                limit,
+            } => {
+                let problem_name = resolve_problem(cli.problem, &default_config)?;
+                let db = connect_db(cli.db, &default_config).await?;
+                let problem = find_problem(&db, &problem_name).await?;
+
+                let submissions = SubmissionRepository::find_by_problem(&db, problem.id).await?;
+                let submission = match submission_id {
+                    Some(id) => submissions.into_iter().find(|s| s.id == id),
+                    None => submissions.into_iter().last(),
+                }
+                .ok_or_else(|| {
+                    CliError::SubmissionNotFound(submission_id.unwrap_or_default(), problem_name)
+                })?;
+
+                let execution_results =
+                    ExecutionResultRepository::find_summaries_by_submission_id(&db, submission.id)
+                        .await?
+                        .iter()
+                        .map(|r| r.into())
+                        .collect::<Vec<ExecutionResult>>();
+                let test_cases = TestCaseRepository::find_by_ids(
+                    &db,
+                    execution_results
+                        .iter()
+                        .map(|r| r.test_case_id as i32)
+                        .collect(),
+                )
+                .await?;
+
+                println!("Submission ID: {}", submission.id);
+                view::render_gaps(&case_stats(&execution_results), &test_cases, limit);
+            }
+            SubmissionCommands::Edit {
+                submission_id,
+                title,
+                notes,
+                author,
+                add_tags,
+                remove_tags,
+                parent,
+                no_parent,
+            } => {
+                let problem_name = resolve_problem(cli.problem, &default_config)?;
+                let db = connect_db(cli.db, &default_config).await?;
+                let problem = find_problem(&db, &problem_name).await?;
+
+                let submission = SubmissionRepository::find_by_id(&db, submission_id)
+                    .await?
+                    .filter(|s| s.problem_id == problem.id)
+                    .ok_or_else(|| CliError::SubmissionNotFound(submission_id, problem_name))?;
+
+                if let Some(parent) = parent
+                    && !SubmissionRepository::is_valid_parent(
+                        &db,
+                        problem.id,
+                        Some(submission.id),
+                        parent,
+                    )
+                    .await?
+                {
+                    return Err(CliError::InvalidParent(parent));
+                }
+
+                let tags = if add_tags.is_empty() && remove_tags.is_empty() {
+                    None
+                } else {
+                    let mut tags: Vec<String> = SubmissionRepository::split_tags(&submission.tags)
+                        .into_iter()
+                        .map(str::to_string)
+                        .collect();
+                    tags.extend(add_tags);
+                    tags.retain(|t| !remove_tags.contains(t));
+                    Some(tags)
+                };
+
+                let submission = SubmissionRepository::update_metadata(
+                    &db,
+                    submission,
+                    SubmissionEdit {
+                        title,
+                        notes,
+                        author,
+                        tags,
+                        parent_id: if no_parent {
+                            Some(None)
+                        } else {
+                            parent.map(Some)
+                        },
+                    },
+                )
+                .await?;
+                view::render_submission_metadata(&submission);
+            }
+            SubmissionCommands::Delete {
+                submission_ids,
+                keep_scores,
+            } => {
+                let problem_name = resolve_problem(cli.problem, &default_config)?;
+                let db = connect_db(cli.db, &default_config).await?;
+                let problem = find_problem(&db, &problem_name).await?;
+
+                // 別の問題の提出は消さない。1 件でも見つからなければ何も消さない
+                for &id in &submission_ids {
+                    SubmissionRepository::find_by_id(&db, id)
+                        .await?
+                        .filter(|s| s.problem_id == problem.id)
+                        .ok_or_else(|| CliError::SubmissionNotFound(id, problem_name.clone()))?;
+                }
+
+                if keep_scores {
+                    ExecutionResultRepository::clear_outputs(&db, submission_ids.clone()).await?;
+                    println!(
+                        "Dropped outputs of submissions: {}",
+                        join_ids(&submission_ids)
+                    );
+                } else {
+                    let deleted = SubmissionRepository::delete(&db, submission_ids).await?;
+                    println!("Deleted {} submissions.", deleted);
+                }
+            }
+            SubmissionCommands::Checkout {
+                submission_id,
+                path,
+                repo,
+            } => {
+                let problem_name = resolve_problem(cli.problem, &default_config)?;
+                let db = connect_db(cli.db, &default_config).await?;
+                let problem = find_problem(&db, &problem_name).await?;
+
+                let submission = SubmissionRepository::find_by_id(&db, submission_id)
+                    .await?
+                    .filter(|s| s.problem_id == problem.id)
+                    .ok_or_else(|| CliError::SubmissionNotFound(submission_id, problem_name))?;
+
+                if path.exists() && (!path.is_dir() || fs::read_dir(&path)?.next().is_some()) {
+                    return Err(CliError::CheckoutDestinationNotEmpty(path));
+                }
+
+                let bundle = SubmissionRepository::find_bundle(&db, submission.id).await?;
+                if let Some(commit) = &submission.git_commit {
+                    heurs_core::git::checkout(&repo, &path, commit, submission.git_diff.as_deref())
+                        .map_err(CliError::Git)?;
+                } else if let Some(bundle) = &bundle {
+                    Bundle::from_archive(bundle.entry.clone(), bundle.archive.clone())
+                        .unpack(&path)
+                        .map_err(CliError::Bundle)?;
+                } else {
+                    fs::create_dir_all(&path)?;
+                }
+
+                // 差分を当てたあとのファイルも、実際に実行したソースで上書きしておく
+                // (ブラウザからの提出はファイル名を持たないので source.cpp にする)。
+                // バンドルを展開しただけなら、中身が実行したソースそのもの
+                let source_path =
+                    path.join(submission.source_path.as_deref().unwrap_or("source.cpp"));
+                if submission.git_commit.is_some() || bundle.is_none() {
+                    if let Some(parent) = source_path.parent() {
+                        fs::create_dir_all(parent)?;
+                    }
+                    fs::write(&source_path, &submission.source_code)?;
+                }
+
+                match &submission.git_commit {
+                    Some(commit) => {
+                        // リポジトリの外で実行したなら、消すときもリポジトリを指定する
+                        let remove = if repo == Path::new(".") {
+                            format!("git worktree remove {}", path.display())
+                        } else {
+                            format!(
+                                "git -C {} worktree remove {}",
+                                repo.display(),
+                                std::path::absolute(&path)?.display()
+                            )
+                        };
+                        println!(
+                            "Checked out submission {} ({}) into {} (remove it with `{}`)",
+                            submission.id,
+                            commit,
+                            path.display(),
+                            remove
+                        )
+                    }
+                    None if bundle.is_some() => println!(
+                        "Submission {} was not run in a git repository; unpacked its bundle into {}",
+                        submission.id,
+                        path.display()
+                    ),
+                    None => println!(
+                        "Submission {} was not run in a git repository; wrote its source to {}",
+                        submission.id,
+                        source_path.display()
+                    ),
+                }
+            }
+            SubmissionCommands::Output {
+                submission_id,
+                test_case_id,
+                repeat,
+            } => {
+                let problem_name = resolve_problem(cli.problem, &default_config)?;
+                let db = connect_db(cli.db, &default_config).await?;
+                let problem = find_problem(&db, &problem_name).await?;
+
+                SubmissionRepository::find_by_id(&db, submission_id)
+                    .await?
+                    .filter(|s| s.problem_id == problem.id)
+                    .ok_or_else(|| {
+                        CliError::SubmissionNotFound(submission_id, problem_name.clone())
+                    })?;
+
+                let output = ExecutionResultRepository::find_output(
+                    &db,
+                    submission_id,
+                    test_case_id,
+                    repeat,
+                )
+                .await?
+                .ok_or(CliError::ExecutionResultNotFound(
+                    submission_id,
+                    test_case_id,
+                    repeat,
+                ))?;
+
+                println!("--- stdout ---");
+                print!("{}", output.stdout);
+                println!("--- stderr ---");
+                print!("{}", output.stderr);
             }
         },
+        Commands::Prune {
+            keep_best,
+            older_than,
+            keep_tagged,
+            keep_scores,
+            dry_run,
+        } => {
+            let problem_name = resolve_problem(cli.problem, &default_config)?;
+            let db = connect_db(cli.db, &default_config).await?;
+            let problem = find_problem(&db, &problem_name).await?;
+
+            let policy = PrunePolicy {
+                keep_best,
+                older_than,
+                keep_tagged,
+            };
+            let ids: Vec<i32> = SubmissionRepository::find_prunable(&db, problem.id, &policy)
+                .await?
+                .iter()
+                .map(|s| s.id)
+                .collect();
+
+            if ids.is_empty() {
+                println!("No submissions to prune.");
+            } else if dry_run {
+                println!("Would prune {} submissions: {}", ids.len(), join_ids(&ids));
+            } else if keep_scores {
+                ExecutionResultRepository::clear_outputs(&db, ids.clone()).await?;
+                println!(
+                    "Dropped outputs of {} submissions: {}",
+                    ids.len(),
+                    join_ids(&ids)
+                );
+            } else {
+                let deleted = SubmissionRepository::delete(&db, ids.clone()).await?;
+                println!("Deleted {} submissions: {}", deleted, join_ids(&ids));
+                println!("Run `heurs db compact` to shrink the database file.");
+            }
+        }
+        Commands::Problem(args) => match args.command {
+            ProblemCommands::List {} => {
+                let db = connect_db(cli.db, &default_config).await?;
+                let problems = ProblemRepository::find_all(&db).await?;
+                view::render_problems(&problems);
+            }
+            ProblemCommands::Add { name } => {
+                if !ProblemRepository::is_valid_name(&name) {
+                    return Err(CliError::InvalidProblem(name));
+                }
+                let db = connect_db(cli.db, &default_config).await?;
+                if ProblemRepository::find_by_name(&db, &name).await?.is_some() {
+                    println!("Problem '{}' already exists.", name);
+                } else {
+                    let problem = ProblemRepository::create(&db, name).await?;
+                    println!("Problem '{}' added with ID: {}", problem.name, problem.id);
+                }
+            }
+        },
+        Commands::Db(args) => {
+            let url = resolve_database_url(cli.db.database_url, &default_config)
+                .map_err(CliError::Config)?;
+            let db = DatabaseManager::connect(&url).await?;
+            match args.command {
+                DbCommands::Migrate {} => {
+                    let applied = DatabaseManager::migrate(&db).await?;
+                    if applied.is_empty() {
+                        println!("No pending migrations.");
+                    }
+                    for name in &applied {
+                        println!("Applied migration '{}'", name);
+                    }
+                }
+                DbCommands::Compact {} => {
+                    DatabaseManager::migrate(&db).await?;
+                    let compressed = ExecutionResultRepository::compress_outputs(&db).await?;
+                    println!("Compressed outputs of {} execution results.", compressed);
+                    DatabaseManager::vacuum(&db).await?;
+                }
+                DbCommands::Status {} => {
+                    println!("Database: {}", redact_database_url(&url));
+                    view::render_migrations(&DatabaseManager::migration_status(&db).await?);
+                }
+            }
+        }
+        Commands::Calibrate { config, runs } => {
+            let config = load_config(&config).map_err(CliError::Config)?;
+
+            println!("Running calibration benchmark {} times...", runs);
+            let calibration = heurs_core::calibrate::calibrate(
+                &config.execution.compile_cmd,
+                &config.execution.exec_cmd,
+                &config.cpu,
+                runs,
+            )
+            .map_err(CliError::Execution)?;
+
+            for (i, time_ms) in calibration.times_ms.iter().enumerate() {
+                println!("  run {}: {} ms", i + 1, time_ms);
+            }
+            let min = calibration.times_ms.iter().min().copied().unwrap_or(0);
+            let max = calibration.times_ms.iter().max().copied().unwrap_or(0);
+            println!(
+                "Median: {} ms (spread {:.1}%)",
+                calibration.median_ms,
+                (max - min) as f64 / calibration.median_ms.max(1) as f64 * 100.0
+            );
+            println!(
+                "Speed factor: {:.3} (reference: {} ms)",
+                calibration.speed_factor,
+                heurs_core::calibrate::REFERENCE_MS
+            );
+            println!(
+                "\nAdd `speed_factor = {:.3}` to [cpu] in heurs.toml to pass it to solutions as HEURS_SPEED_FACTOR.",
+                calibration.speed_factor
+            );
+        }
+        Commands::Worker {
+            server,
+            name,
+            parallel,
+            work_dir,
+            token,
+            config,
+        } => {
+            let config = if config.exists() {
+                let mut loaded = load_config(&config).map_err(CliError::Config)?;
+                // ワーカーは DB を持たないが、トークン入りの設定ファイルは隠す
+                loaded.sandbox.hide_config_and_database(&config, "");
+                Some(loaded)
+            } else {
+                None
+            };
+            let token = token
+                .or_else(|| config.as_ref().and_then(|c| c.remote.token.clone()))
+                .ok_or_else(|| {
+                    CliError::Config(
+                        "A shared token is required (--token, HEURS_REMOTE_TOKEN or [remote] token)"
+                            .into(),
+                    )
+                })?;
+            let runner = config
+                .as_ref()
+                .map(LocalRunner::from_config)
+                .unwrap_or_default();
+            let name = name
+                .or_else(heurs_core::environment::hostname)
+                .unwrap_or_else(|| "worker".to_string());
+            let work_dir = work_dir.unwrap_or_else(|| {
+                std::env::temp_dir().join(format!("heurs-worker-{}", std::process::id()))
+            });
+
+            heurs_core::worker::run_worker(&server, &name, parallel, work_dir, &token, runner)
+                .await
+                .map_err(CliError::Execution)?;
+        }
     }
 
     Ok(())
@@ -1,5 +1,9 @@
 use heurs_core::ExecutionResult;
-use heurs_database::{ExecutionResultModel, SubmissionModel, TestCaseModel};
+use heurs_core::stats::{CaseStats, case_stats, relative_score};
+use heurs_database::{
+    Environment, MigrationState, ProblemModel, SubmissionAggregate, SubmissionModel, TestCaseModel,
+    TestCaseSetModel,
+};
 use std::cmp::Ordering;
 use tabled::{Table, Tabled};
 
@@ -11,24 +15,157 @@ struct TestCaseRow {
     file_name: String,
     #[tabled(rename = "Score")]
     score: i64,
+    #[tabled(rename = "Best")]
+    best: String,
+    #[tabled(rename = "% Best")]
+    relative: String,
     #[tabled(rename = "Time(ms)")]
     time: u32,
 }
 
+#[derive(Clone, Tabled)]
+struct CaseStatsRow {
+    #[tabled(rename = "Case ID")]
+    case_id: u32,
+    #[tabled(rename = "File Name")]
+    file_name: String,
+    #[tabled(rename = "Runs")]
+    runs: usize,
+    #[tabled(rename = "Mean")]
+    mean: String,
+    #[tabled(rename = "Std")]
+    std_dev: String,
+    #[tabled(rename = "Worst")]
+    worst: i64,
+    #[tabled(rename = "Best")]
+    best: i64,
+    #[tabled(rename = "Best Known")]
+    best_known: String,
+    #[tabled(rename = "% Best")]
+    relative: String,
+    #[tabled(rename = "Avg Time(ms)")]
+    time: String,
+}
+
+#[derive(Clone, Tabled)]
+struct GapRow {
+    #[tabled(rename = "Case ID")]
+    case_id: u32,
+    #[tabled(rename = "File Name")]
+    file_name: String,
+    #[tabled(rename = "Seed")]
+    seed: String,
+    #[tabled(rename = "Score")]
+    score: String,
+    #[tabled(rename = "Best Known")]
+    best_known: i64,
+    #[tabled(rename = "% Best")]
+    relative: String,
+    #[tabled(rename = "Gap")]
+    gap: String,
+}
+
 #[derive(Clone, Tabled)]
 struct SubmissionRow {
     #[tabled(rename = "Submission ID")]
     submission_id: i32,
+    #[tabled(rename = "Title")]
+    title: String,
+    #[tabled(rename = "Tags")]
+    tags: String,
+    #[tabled(rename = "Set")]
+    set: String,
     #[tabled(rename = "Avg Score")]
     avg_score: f64,
     #[tabled(rename = "Avg Time(ms)")]
     avg_time: f64,
+    #[tabled(rename = "Failed")]
+    failures: i64,
+    /// 同じ環境で実行した提出に同じ記号を付ける (記録がなければ `-`)
+    #[tabled(rename = "Env")]
+    env: String,
+}
+
+#[derive(Clone, Tabled)]
+struct TestCaseSetRow {
+    #[tabled(rename = "ID")]
+    id: i32,
+    #[tabled(rename = "Name")]
+    name: String,
+    #[tabled(rename = "Cases")]
+    cases: u64,
+    #[tabled(rename = "Submissions")]
+    submissions: u64,
+    #[tabled(rename = "Created At")]
+    created_at: String,
+    #[tabled(rename = "Status")]
+    status: String,
+}
+
+#[derive(Clone, Tabled)]
+struct ImportRow {
+    #[tabled(rename = "File Name")]
+    file_name: String,
+    #[tabled(rename = "Result")]
+    result: String,
+    #[tabled(rename = "Case ID")]
+    case_id: String,
+    #[tabled(rename = "Note")]
+    note: String,
+}
+
+/// ケース 1 つ分の取り込み結果
+#[derive(Clone, Copy, PartialEq)]
+pub enum ImportOutcome {
+    /// 新しく登録した
+    Added,
+    /// 同じ内容のケースが登録済みだったので、それをセットに加えた
+    Skipped,
+    /// 既存のケースと食い違うので取り込まなかった
+    Conflict,
+}
+
+pub struct ImportRecord {
+    pub filename: String,
+    pub outcome: ImportOutcome,
+    pub test_case_id: Option<i32>,
+    pub note: String,
+}
+
+#[derive(Clone, Tabled)]
+struct ProblemRow {
+    #[tabled(rename = "ID")]
+    id: i32,
+    #[tabled(rename = "Name")]
+    name: String,
+    #[tabled(rename = "Created At")]
+    created_at: String,
+}
+
+#[derive(Clone, Tabled)]
+struct MigrationRow {
+    #[tabled(rename = "Migration")]
+    name: String,
+    #[tabled(rename = "Status")]
+    status: &'static str,
 }
 
 pub fn render_execution_results(
-    execution_results: &Vec<ExecutionResult>,
-    test_cases: &Vec<TestCaseModel>,
+    execution_results: &[ExecutionResult],
+    test_cases: &[TestCaseModel],
 ) {
+    // 繰り返し実行した結果はケースごとの平均・ばらつき・最悪値にまとめて表示する
+    if execution_results.iter().any(|r| r.repeat > 0) {
+        let stats = case_stats(execution_results);
+        render_case_stats(&stats, test_cases);
+        render_relative_summary(
+            stats
+                .iter()
+                .map(|s| relative_score(s.mean_score, best_known(test_cases, s.test_case_id))),
+        );
+        return;
+    }
+
     let mut rows: Vec<TestCaseRow> = execution_results
         .iter()
         .map(|r| {
@@ -37,12 +174,15 @@ pub fn render_execution_results(
                 .find(|t| t.id == r.test_case_id as i32)
                 .map(|t| t.filename.clone())
                 .unwrap_or_else(|| "".to_string());
+            let best = best_known(test_cases, r.test_case_id);
 
             TestCaseRow {
-                case_id: r.test_case_id as u32,
+                case_id: r.test_case_id,
                 file_name,
                 score: r.score,
-                time: r.execution_time_ms as u32,
+                best: format_option(best),
+                relative: format_relative(relative_score(r.score as f64, best)),
+                time: r.execution_time_ms,
             }
         })
         .collect();
@@ -50,13 +190,194 @@ pub fn render_execution_results(
     rows.sort_by(|a, b| a.file_name.cmp(&b.file_name));
 
     println!("\n{}", Table::new(rows));
+    render_relative_summary(
+        execution_results
+            .iter()
+            .map(|r| relative_score(r.score as f64, best_known(test_cases, r.test_case_id))),
+    );
 }
-pub fn render_submission_summary(
-    submission: &SubmissionModel,
-    execution_results: &Vec<ExecutionResult>,
+
+fn best_known(test_cases: &[TestCaseModel], test_case_id: u32) -> Option<i64> {
+    test_cases
+        .iter()
+        .find(|t| t.id == test_case_id as i32)
+        .and_then(|t| t.best_known_score)
+}
+
+fn format_option(value: Option<i64>) -> String {
+    value.map(|v| v.to_string()).unwrap_or_default()
+}
+
+fn format_relative(relative: Option<f64>) -> String {
+    relative.map(|r| format!("{:.2}%", r)).unwrap_or_default()
+}
+
+/// 既知の最良スコアがあるケースについて、最良スコアに対する割合の平均を表示する
+fn render_relative_summary(relatives: impl Iterator<Item = Option<f64>>) {
+    let relatives: Vec<f64> = relatives.flatten().collect();
+    if !relatives.is_empty() {
+        println!(
+            "Average % of best-known: {:.2}% ({} cases)",
+            relatives.iter().sum::<f64>() / relatives.len() as f64,
+            relatives.len()
+        );
+    }
+}
+
+fn render_case_stats(stats: &[CaseStats], test_cases: &[TestCaseModel]) {
+    let mut rows: Vec<CaseStatsRow> = stats
+        .iter()
+        .map(|s| {
+            let best = best_known(test_cases, s.test_case_id);
+            CaseStatsRow {
+                case_id: s.test_case_id,
+                file_name: test_cases
+                    .iter()
+                    .find(|t| t.id == s.test_case_id as i32)
+                    .map(|t| t.filename.clone())
+                    .unwrap_or_default(),
+                runs: s.runs,
+                mean: format!("{:.1}", s.mean_score),
+                std_dev: format!("{:.1}", s.std_dev_score),
+                worst: s.worst_score,
+                best: s.best_score,
+                best_known: format_option(best),
+                relative: format_relative(relative_score(s.mean_score, best)),
+                time: format!("{:.1}", s.mean_execution_time_ms),
+            }
+        })
+        .collect();
+
+    rows.sort_by(|a, b| a.file_name.cmp(&b.file_name));
+
+    println!("\n{}", Table::new(rows));
+}
+
+/// 既知の最良スコアに対する割合が低い順に `limit` 件表示する (繰り返し実行した場合は平均スコアで比べる)
+pub fn render_gaps(stats: &[CaseStats], test_cases: &[TestCaseModel], limit: u32) {
+    let mut gaps: Vec<(f64, GapRow)> = stats
+        .iter()
+        .filter_map(|s| {
+            let test_case = test_cases.iter().find(|t| t.id == s.test_case_id as i32)?;
+            let best = test_case.best_known_score?;
+            let relative = relative_score(s.mean_score, Some(best))?;
+            Some((
+                relative,
+                GapRow {
+                    case_id: s.test_case_id,
+                    file_name: test_case.filename.clone(),
+                    seed: format_option(test_case.seed),
+                    score: format!("{:.1}", s.mean_score),
+                    best_known: best,
+                    relative: format_relative(Some(relative)),
+                    gap: format!("{:.1}", best as f64 - s.mean_score),
+                },
+            ))
+        })
+        .collect();
+    let without_best = stats.len() - gaps.len();
+
+    gaps.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(Ordering::Equal));
+    let rows: Vec<GapRow> = gaps
+        .into_iter()
+        .take(limit as usize)
+        .map(|(_, row)| row)
+        .collect();
+
+    println!("\n{}", Table::new(rows));
+    if without_best > 0 {
+        println!("{} test cases have no best-known score.", without_best);
+    }
+}
+
+/// 1 ケース終わるごとに進捗を 1 行表示する
+pub fn render_progress(
+    done: usize,
+    total: usize,
+    result: &ExecutionResult,
+    test_cases: &[TestCaseModel],
+    repeat: u32,
 ) {
+    let file_name = test_cases
+        .iter()
+        .find(|t| t.id == result.test_case_id as i32)
+        .map(|t| t.filename.as_str())
+        .unwrap_or("");
+    // 繰り返し実行時は何回目か (= HEURS_RNG_SEED) も出す
+    let run = if repeat > 1 {
+        format!(" seed={}", result.repeat)
+    } else {
+        String::new()
+    };
+
+    println!(
+        "[{}/{}] {} {}{} score={} time={}ms",
+        done,
+        total,
+        if result.success { "OK" } else { "NG" },
+        file_name,
+        run,
+        result.score,
+        result.execution_time_ms
+    );
+}
+
+/// 提出の ID と、付いていればタイトル・作者・タグ・親・メモを表示する
+pub fn render_submission_metadata(submission: &SubmissionModel) {
     println!("Submission ID: {}", submission.id);
+    if let Some(title) = &submission.title {
+        println!("Title: {}", title);
+    }
     println!("Timestamp: {}", submission.timestamp);
+    if let Some(author) = &submission.author {
+        println!("Author: {}", author);
+    }
+    if !submission.tags.is_empty() {
+        println!("Tags: {}", submission.tags.replace(',', ", "));
+    }
+    if let Some(parent_id) = submission.parent_id {
+        println!("Parent: {}", parent_id);
+    }
+    if let Some(commit) = &submission.git_commit {
+        println!(
+            "Git: {}{}{}",
+            commit,
+            submission
+                .git_branch
+                .as_ref()
+                .map(|b| format!(" ({})", b))
+                .unwrap_or_default(),
+            if submission.git_dirty { " dirty" } else { "" }
+        );
+    }
+    if let Some(env) = Environment::of(submission) {
+        println!("Environment: {}", env.summary());
+        if let Some(hostname) = &env.hostname {
+            println!("Host: {}", hostname);
+        }
+    }
+    if let Some(notes) = &submission.notes {
+        println!("Notes:\n{}", notes);
+    }
+}
+
+pub fn render_submission_summary(
+    submission: &SubmissionModel,
+    test_case_set: Option<&TestCaseSetModel>,
+    execution_results: &[ExecutionResult],
+) {
+    render_submission_metadata(submission);
+    if let Some(set) = test_case_set {
+        println!(
+            "Test case set: {}{}",
+            set.name,
+            if set.archived_at.is_some() {
+                " (archived)"
+            } else {
+                ""
+            }
+        );
+    }
 
     println!(
         "Average score: {}",
@@ -73,52 +394,175 @@ pub fn render_submission_summary(
     );
 }
 
+/// `aggregates` は `ExecutionResultRepository::aggregate_by_submission` の結果。結果のない提出は 0 として並べる
 pub fn render_leaderboard(
-    submissions: &Vec<SubmissionModel>,
-    execution_results: &Vec<ExecutionResultModel>,
+    submissions: &[SubmissionModel],
+    test_case_sets: &[TestCaseSetModel],
+    aggregates: &[SubmissionAggregate],
     limit: u32,
 ) {
-    let mut rows: Vec<SubmissionRow> = Vec::new();
+    let mut rows: Vec<(SubmissionRow, Option<Environment>)> = Vec::new();
 
     for sub in submissions {
-        let results = execution_results
-            .iter()
-            .filter(|r| r.submission_id == sub.id as i64)
-            .collect::<Vec<&ExecutionResultModel>>();
+        let aggregate = aggregates.iter().find(|a| a.submission_id == sub.id);
 
-        let (sum, count) = results
+        let set = test_case_sets
             .iter()
-            .fold((0i64, 0usize), |(s, c), r| (s + r.score, c + 1));
-        let avg_score = if count > 0 {
-            sum as f64 / count as f64
-        } else {
-            0.0
-        };
-
-        let avg_time = if count > 0 {
-            results
-                .iter()
-                .map(|r| r.execution_time_ms as f64)
-                .sum::<f64>()
-                / count as f64
-        } else {
-            0.0
-        };
+            .find(|s| Some(s.id) == sub.test_case_set_id)
+            .map(|s| s.name.clone())
+            .unwrap_or_default();
 
-        rows.push(SubmissionRow {
-            submission_id: sub.id,
-            avg_score,
-            avg_time,
-        });
+        rows.push((
+            SubmissionRow {
+                submission_id: sub.id,
+                title: sub.title.clone().unwrap_or_default(),
+                tags: sub.tags.replace(',', ", "),
+                set,
+                avg_score: aggregate.map_or(0.0, |a| a.average_score),
+                avg_time: aggregate.map_or(0.0, |a| a.average_execution_time_ms),
+                failures: aggregate.map_or(0, |a| a.number_of_failures),
+                env: String::new(),
+            },
+            Environment::of(sub),
+        ));
     }
 
-    rows.sort_by(|a, b| {
+    rows.sort_by(|(a, _), (b, _)| {
         b.avg_score
             .partial_cmp(&a.avg_score)
             .unwrap_or(Ordering::Equal)
     });
+    rows.truncate(limit as usize);
 
-    let display_rows: Vec<SubmissionRow> = rows.into_iter().take(limit as usize).collect();
+    // 表示する提出の環境を、違うものだけ残して記号を振る
+    let mut environments: Vec<&Environment> = Vec::new();
+    let mut display_rows: Vec<SubmissionRow> = Vec::new();
+    for (row, env) in &rows {
+        let mut row = row.clone();
+        row.env = match env {
+            Some(env) => {
+                let index = environments
+                    .iter()
+                    .position(|e| e.differences(env).is_empty())
+                    .unwrap_or_else(|| {
+                        environments.push(env);
+                        environments.len() - 1
+                    });
+                env_label(index)
+            }
+            None => "-".to_string(),
+        };
+        display_rows.push(row);
+    }
 
     println!("\n{}", Table::new(display_rows));
+    render_environment_warning(&environments);
+}
+
+/// 違う環境で実行した提出が混ざっていれば、実行時間に依存するスコアは比べられないと警告する
+fn render_environment_warning(environments: &[&Environment]) {
+    if environments.len() < 2 {
+        return;
+    }
+    let mut fields: Vec<&str> = Vec::new();
+    for env in &environments[1..] {
+        for field in environments[0].differences(env) {
+            if !fields.contains(&field) {
+                fields.push(field);
+            }
+        }
+    }
+    println!(
+        "Warning: these submissions ran in {} different environments (differing: {}); timing-dependent scores may not be comparable.",
+        environments.len(),
+        fields.join(", ")
+    );
+    for (index, env) in environments.iter().enumerate() {
+        println!("  {}: {}", env_label(index), env.summary());
+    }
+}
+
+/// 環境の記号 (A, B, ..., Z, 27, 28, ...)
+fn env_label(index: usize) -> String {
+    match u8::try_from(index) {
+        Ok(i) if i < 26 => char::from(b'A' + i).to_string(),
+        _ => (index + 1).to_string(),
+    }
+}
+
+pub fn render_problems(problems: &[ProblemModel]) {
+    let rows: Vec<ProblemRow> = problems
+        .iter()
+        .map(|p| ProblemRow {
+            id: p.id,
+            name: p.name.clone(),
+            created_at: p.created_at.format("%Y-%m-%d %H:%M").to_string(),
+        })
+        .collect();
+
+    println!("{}", Table::new(rows));
+}
+
+pub fn render_migrations(migrations: &[MigrationState]) {
+    let rows: Vec<MigrationRow> = migrations
+        .iter()
+        .map(|m| MigrationRow {
+            name: m.name.clone(),
+            status: if m.applied { "Applied" } else { "Pending" },
+        })
+        .collect();
+
+    println!("{}", Table::new(rows));
+}
+
+/// `counts` は各セットの (ケース数, 提出数)。`active_id` のセットに印を付ける
+pub fn render_test_case_sets(
+    sets: &[TestCaseSetModel],
+    counts: &[(u64, u64)],
+    active_id: Option<i32>,
+) {
+    let rows: Vec<TestCaseSetRow> = sets
+        .iter()
+        .zip(counts)
+        .map(|(set, &(cases, submissions))| TestCaseSetRow {
+            id: set.id,
+            name: set.name.clone(),
+            cases,
+            submissions,
+            created_at: set.created_at.format("%Y-%m-%d %H:%M").to_string(),
+            status: match set.archived_at {
+                Some(at) => format!("archived {}", at.format("%Y-%m-%d %H:%M")),
+                None if Some(set.id) == active_id => "active".to_string(),
+                None => String::new(),
+            },
+        })
+        .collect();
+
+    println!("{}", Table::new(rows));
+}
+
+pub fn render_import_report(records: &[ImportRecord]) {
+    let rows: Vec<ImportRow> = records
+        .iter()
+        .map(|r| ImportRow {
+            file_name: r.filename.clone(),
+            result: match r.outcome {
+                ImportOutcome::Added => "added",
+                ImportOutcome::Skipped => "skipped",
+                ImportOutcome::Conflict => "conflict",
+            }
+            .to_string(),
+            case_id: r.test_case_id.map(|id| id.to_string()).unwrap_or_default(),
+            note: r.note.clone(),
+        })
+        .collect();
+    let count = |outcome| records.iter().filter(|r| r.outcome == outcome).count();
+
+    println!("\n{}", Table::new(rows));
+    println!(
+        "Added: {}, Skipped: {}, Conflicts: {}",
+        count(ImportOutcome::Added),
+        count(ImportOutcome::Skipped),
+        count(ImportOutcome::Conflict)
+    );
 }
@@ -0,0 +1,259 @@
+//! 複数ファイルの提出 (バンドル)
+//!
+//! cargo プロジェクトやローカルのヘッダを使う C++ のように、1 ファイルに収まらない提出を
+//! tar.gz にまとめて扱う。エントリポイントはコンパイルコマンドの `{{src}}` に入るファイルで、
+//! 実行器はワークスペースにバンドルを展開してからコンパイルする。
+
+use crate::import::relative_path;
+use flate2::Compression;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use ignore::WalkBuilder;
+use ignore::overrides::OverrideBuilder;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+type BundleError = Box<dyn std::error::Error + Send + Sync>;
+
+/// 展開後の合計サイズの上限。ビルド成果物などをうっかり含めないため
+const MAX_BUNDLE_SIZE: u64 = 32 * 1024 * 1024;
+
+/// ファイル一式とエントリポイント
+#[derive(Debug, Clone)]
+pub struct Bundle {
+    entry: String,
+    archive: Vec<u8>,
+}
+
+/// バンドル内の 1 ファイル
+#[derive(Debug, Clone)]
+pub struct BundleFile {
+    /// バンドルのルートからの相対パス (`/` 区切り)
+    pub path: String,
+    pub size: u64,
+}
+
+impl Bundle {
+    /// `root` 以下のファイルをまとめる。`entry` は `root` からの相対パス。
+    ///
+    /// `includes` が空ならディレクトリ全体 (`.gitignore` で除外したものと `.git` を除く)、
+    /// 指定があればそのいずれかの glob に一致するファイルとエントリポイントだけを含める。
+    pub fn from_dir(root: &Path, entry: &str, includes: &[String]) -> Result<Self, BundleError> {
+        let root = root.canonicalize()?;
+        // `./src/main.rs` のような指定もそろえ、root の外を指すものは受け付けない
+        let entry = root
+            .join(entry)
+            .canonicalize()
+            .ok()
+            .filter(|p| p.is_file())
+            .and_then(|p| p.strip_prefix(&root).ok().map(slash_path))
+            .ok_or_else(|| {
+                format!(
+                    "Entry point '{}' is not a file in {}",
+                    entry,
+                    root.display()
+                )
+            })?;
+
+        let mut walker = WalkBuilder::new(&root);
+        walker
+            .hidden(false)
+            // git リポジトリでなくても .gitignore (cargo の target/ など) に従う
+            .require_git(false)
+            .filter_entry(|e| e.file_name() != ".git");
+        if !includes.is_empty() {
+            let mut overrides = OverrideBuilder::new(&root);
+            for glob in includes {
+                overrides.add(glob)?;
+            }
+            walker.overrides(overrides.build()?);
+        }
+
+        let mut files: Vec<String> = vec![entry.clone()];
+        for dir_entry in walker.build() {
+            let dir_entry = dir_entry?;
+            if !dir_entry.file_type().is_some_and(|t| t.is_file()) {
+                continue;
+            }
+            let relative = slash_path(dir_entry.path().strip_prefix(&root)?);
+            if !files.contains(&relative) {
+                files.push(relative);
+            }
+        }
+        files.sort();
+
+        let total: u64 = files
+            .iter()
+            .map(|f| std::fs::metadata(root.join(f)).map_or(0, |m| m.len()))
+            .sum();
+        if total > MAX_BUNDLE_SIZE {
+            return Err(format!(
+                "Bundle is too large ({} bytes in {} files, limit {} bytes); select files with --include",
+                total,
+                files.len(),
+                MAX_BUNDLE_SIZE
+            )
+            .into());
+        }
+
+        // 同じ内容からは同じアーカイブができるよう、更新時刻や所有者は入れない
+        let mut builder = tar::Builder::new(GzEncoder::new(Vec::new(), Compression::default()));
+        builder.mode(tar::HeaderMode::Deterministic);
+        for file in &files {
+            builder.append_path_with_name(root.join(file), file)?;
+        }
+        let archive = builder.into_inner()?.finish()?;
+
+        Ok(Bundle { entry, archive })
+    }
+
+    /// 保存しておいたアーカイブから作る
+    pub fn from_archive(entry: String, archive: Vec<u8>) -> Self {
+        Bundle { entry, archive }
+    }
+
+    /// エントリポイントのパス
+    pub fn entry(&self) -> &str {
+        &self.entry
+    }
+
+    /// ファイル一式の tar.gz
+    pub fn archive(&self) -> &[u8] {
+        &self.archive
+    }
+
+    /// 含まれるファイルの一覧
+    pub fn files(&self) -> Result<Vec<BundleFile>, BundleError> {
+        let mut archive = tar::Archive::new(GzDecoder::new(self.archive.as_slice()));
+        let mut files = Vec::new();
+        for entry in archive.entries()? {
+            let entry = entry?;
+            let path = entry_path(&entry)?;
+            if entry.header().entry_type().is_file() {
+                files.push(BundleFile {
+                    path,
+                    size: entry.header().size()?,
+                });
+            }
+        }
+        Ok(files)
+    }
+
+    /// `path` のファイルの内容。含まれていなければ `None`
+    pub fn read(&self, path: &str) -> Result<Option<Vec<u8>>, BundleError> {
+        let mut archive = tar::Archive::new(GzDecoder::new(self.archive.as_slice()));
+        for entry in archive.entries()? {
+            let mut entry = entry?;
+            if entry.header().entry_type().is_file() && entry_path(&entry)? == path {
+                let mut contents = Vec::new();
+                entry.read_to_end(&mut contents)?;
+                return Ok(Some(contents));
+            }
+        }
+        Ok(None)
+    }
+
+    /// `dest` に展開し、エントリポイントのパスを返す
+    ///
+    /// 保存しておいたものやリモートから受け取ったものも展開するので、`..` や絶対パスを含む
+    /// エントリがあれば (tar のように黙って飛ばさず) 何も書き出さずにエラーにする
+    pub fn unpack(&self, dest: &Path) -> Result<PathBuf, BundleError> {
+        let entry = relative_path(Path::new(&self.entry))
+            .ok_or_else(|| format!("Entry point '{}' is outside the bundle", self.entry))?;
+        let mut archive = tar::Archive::new(GzDecoder::new(self.archive.as_slice()));
+        for archive_entry in archive.entries()? {
+            entry_path(&archive_entry?)?;
+        }
+
+        std::fs::create_dir_all(dest)?;
+        tar::Archive::new(GzDecoder::new(self.archive.as_slice())).unpack(dest)?;
+        Ok(dest.join(entry))
+    }
+}
+
+/// tar のエントリのパス。ルートの外を指していればエラー
+fn entry_path<R: Read>(entry: &tar::Entry<R>) -> Result<String, BundleError> {
+    let path = entry.path()?;
+    relative_path(&path)
+        .ok_or_else(|| format!("Bundle contains an unsafe path: {}", path.display()).into())
+}
+
+/// OS によらず `/` 区切りの相対パスにする
+fn slash_path(path: &Path) -> String {
+    path.components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn archive(files: &[(&str, &str)]) -> Vec<u8> {
+        let mut builder = tar::Builder::new(GzEncoder::new(Vec::new(), Compression::default()));
+        for (name, contents) in files {
+            let mut header = tar::Header::new_gnu();
+            // set_path は `..` や絶対パスを受け付けないので名前を直接書く
+            header.as_old_mut().name[..name.len()].copy_from_slice(name.as_bytes());
+            header.set_size(contents.len() as u64);
+            header.set_mode(0o644);
+            header.set_entry_type(tar::EntryType::Regular);
+            header.set_cksum();
+            builder.append(&header, contents.as_bytes()).unwrap();
+        }
+        builder.into_inner().unwrap().finish().unwrap()
+    }
+
+    /// テストごとの空の作業ディレクトリ
+    fn temp_dir() -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("heurs-bundle-test-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn unpack_keeps_directories() {
+        let bundle = Bundle::from_archive(
+            "main.cpp".to_string(),
+            archive(&[("main.cpp", "#include \"lib/a.h\""), ("lib/a.h", "int a;")]),
+        );
+        let paths: Vec<_> = bundle
+            .files()
+            .unwrap()
+            .into_iter()
+            .map(|f| f.path)
+            .collect();
+        assert_eq!(paths, ["main.cpp", "lib/a.h"]);
+        assert_eq!(bundle.read("lib/a.h").unwrap().unwrap(), b"int a;");
+
+        let dir = temp_dir();
+        let entry = bundle.unpack(&dir).unwrap();
+        assert_eq!(entry, dir.join("main.cpp"));
+        assert_eq!(
+            std::fs::read_to_string(dir.join("lib/a.h")).unwrap(),
+            "int a;"
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn unpack_rejects_unsafe_entries() {
+        let dir = temp_dir();
+        let dest = dir.join("dest");
+
+        let bundle = Bundle::from_archive(
+            "main.cpp".to_string(),
+            archive(&[("main.cpp", ""), ("../evil.txt", "")]),
+        );
+        assert!(bundle.files().is_err());
+        assert!(bundle.unpack(&dest).is_err());
+        // 安全なエントリも含めて何も書き出さない
+        assert!(!dest.exists());
+        assert!(!dir.join("evil.txt").exists());
+
+        let bundle = Bundle::from_archive("../main.cpp".to_string(), archive(&[("main.cpp", "")]));
+        assert!(bundle.unpack(&dest).is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,123 @@
+use crate::config::Cpu as CpuConfig;
+use crate::runner::affinity;
+use std::process::{Command, Stdio};
+use std::time::Instant;
+
+/// 速度係数 1.0 とみなす基準ベンチマークの所要時間 (ミリ秒)
+pub const REFERENCE_MS: f64 = 1000.0;
+
+/// 焼きなましに近い負荷 (乱数・ランダムアクセス・exp) をかける基準ベンチマーク。
+/// `[execution]` の compile_cmd でコンパイルし、提出コードと同じ最適化条件で測る。
+const BENCHMARK_SOURCE: &str = r#"#include <cmath>
+#include <cstdint>
+#include <cstdio>
+#include <utility>
+#include <vector>
+
+int main() {
+    uint64_t x = 88172645463325252ULL;
+    auto rnd = [&] {
+        x ^= x << 7;
+        x ^= x >> 9;
+        return x;
+    };
+    const int n = 1 << 20;
+    std::vector<uint32_t> a(n);
+    for (auto& v : a) v = rnd();
+    uint64_t sum = 0;
+    for (int it = 0; it < 50000000; it++) {
+        uint32_t i = rnd() & (n - 1), j = rnd() & (n - 1);
+        int d = (int)(a[i] & 1023) - (int)(a[j] & 1023);
+        if (d > 0 || std::exp(d * 0.01) > (rnd() & 0xffff) / 65536.0) {
+            std::swap(a[i], a[j]);
+            sum += i;
+        }
+    }
+    std::printf("%llu\n", (unsigned long long)sum);
+}
+"#;
+
+/// `heurs calibrate` の計測結果
+#[derive(Debug, Clone)]
+pub struct Calibration {
+    /// 各回の所要時間 (ミリ秒)
+    pub times_ms: Vec<u64>,
+    pub median_ms: u64,
+    /// 基準マシンに対する速さ (2.0 なら基準の 2 倍速い)
+    pub speed_factor: f64,
+}
+
+/// 基準ベンチマークを `runs` 回実行してこのマシンの速度係数を求める。
+///
+/// `[cpu]` でコア固定が有効なら、ケースの実行と同じ条件になるよう
+/// 使えるコアの先頭に固定して測る。
+pub fn calibrate(
+    compile_cmd: &str,
+    exec_cmd: &str,
+    cpu: &CpuConfig,
+    runs: u32,
+) -> Result<Calibration, Box<dyn std::error::Error + Send + Sync>> {
+    let work_dir = std::env::temp_dir().join(format!("heurs-calibrate-{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&work_dir)?;
+    let result = run_benchmark(&work_dir, compile_cmd, exec_cmd, cpu, runs);
+    let _ = std::fs::remove_dir_all(&work_dir);
+    result
+}
+
+fn run_benchmark(
+    work_dir: &std::path::Path,
+    compile_cmd: &str,
+    exec_cmd: &str,
+    cpu: &CpuConfig,
+    runs: u32,
+) -> Result<Calibration, Box<dyn std::error::Error + Send + Sync>> {
+    let source_path = work_dir.join("calibrate.cpp");
+    std::fs::write(&source_path, BENCHMARK_SOURCE)?;
+
+    let compile_cmd = compile_cmd.replace("{{src}}", &source_path.display().to_string());
+    let status = Command::new("sh")
+        .arg("-c")
+        .arg(compile_cmd)
+        .current_dir(work_dir)
+        .status()?;
+    if !status.success() {
+        return Err("Compilation of the calibration benchmark failed".into());
+    }
+
+    let core = if cpu.pin {
+        affinity::available_cores(cpu)?.first().copied()
+    } else {
+        None
+    };
+
+    let mut times_ms = Vec::new();
+    for _ in 0..runs.max(1) {
+        let mut command = Command::new("sh");
+        command
+            .arg("-c")
+            .arg(exec_cmd)
+            .current_dir(work_dir)
+            .stdout(Stdio::null());
+        if let Some(core) = core {
+            affinity::pin_command(&mut command, core);
+        }
+
+        let start = Instant::now();
+        let status = command.status()?;
+        let elapsed = start.elapsed().as_millis() as u64;
+        if !status.success() {
+            return Err("Calibration benchmark exited with an error".into());
+        }
+        times_ms.push(elapsed);
+    }
+
+    let mut sorted = times_ms.clone();
+    sorted.sort_unstable();
+    let median_ms = sorted[sorted.len() / 2];
+
+    Ok(Calibration {
+        times_ms,
+        median_ms,
+        speed_factor: REFERENCE_MS / median_ms.max(1) as f64,
+    })
+}
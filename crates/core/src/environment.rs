@@ -0,0 +1,233 @@
+//! 提出を実行した環境 (マシン・ツールチェイン・実行方法) の記録
+//!
+//! 実行時間に依存する解法はマシンによってスコアが変わるので、手元のノート PC と AWS で
+//! 実行した提出を並べたときに気付けるよう、`heurs run` ごとに環境を記録しておく。
+
+use crate::config::Container as ContainerConfig;
+use crate::runner::Source;
+use heurs_database::Environment;
+use std::process::{Command, Stdio};
+
+/// コンテナ内でワークスペースをマウントするパス (`ContainerRunner` と同じ)
+const CONTAINER_WORKDIR: &str = "/work";
+
+/// `runner` (local / container / aws / remote) で `source` を `parallelism` 並列に実行するときの環境を調べる。
+///
+/// マシンの情報はこのマシンで実行するときだけ記録する。コンテナはカーネルと CPU を
+/// ホストと共有するのでマシンの情報は記録し、コンパイラのバージョンはイメージの中で調べる。
+/// 外部コマンドを起動して待つので、ブロッキング用のスレッドで調べる。
+pub async fn capture(
+    runner: &str,
+    source: &Source,
+    compile_cmd: &str,
+    exec_cmd: &str,
+    parallelism: u32,
+    container: &ContainerConfig,
+) -> Environment {
+    let runner = runner.to_string();
+    let compile_cmd = resolve(&runner, source, compile_cmd);
+    let exec_cmd = resolve(&runner, source, exec_cmd);
+    let container = container.clone();
+    let fallback = Environment {
+        runner: runner.clone(),
+        parallelism: parallelism as i32,
+        ..Default::default()
+    };
+
+    tokio::task::spawn_blocking(move || {
+        capture_blocking(&runner, &compile_cmd, &exec_cmd, parallelism, &container)
+    })
+    .await
+    .unwrap_or(fallback)
+}
+
+fn capture_blocking(
+    runner: &str,
+    compile_cmd: &str,
+    exec_cmd: &str,
+    parallelism: u32,
+    container: &ContainerConfig,
+) -> Environment {
+    let on_this_machine = matches!(runner, "local" | "container");
+    // aws は実行側のスクリプトのコマンドでコンパイル・実行する
+    let uses_commands = runner != "aws";
+
+    Environment {
+        hostname: on_this_machine.then(hostname).flatten(),
+        cpu_model: on_this_machine.then(cpu_model).flatten(),
+        cpu_cores: on_this_machine
+            .then(|| std::thread::available_parallelism().ok())
+            .flatten()
+            .map(|n| n.get() as i32),
+        os: on_this_machine
+            .then(|| command_output("uname", &["-sr"]))
+            .flatten(),
+        compiler_version: match runner {
+            "local" => compiler_version(compile_cmd),
+            "container" => container_compiler_version(compile_cmd, container),
+            _ => None,
+        },
+        compile_cmd: uses_commands.then(|| compile_cmd.to_string()),
+        exec_cmd: uses_commands.then(|| exec_cmd.to_string()),
+        runner: runner.to_string(),
+        parallelism: parallelism as i32,
+    }
+}
+
+/// `{{src}}` を実行器が渡すソースのパスに置き換える。
+///
+/// ローカルで 1 ファイルを実行するときは指定されたパスのまま、コンテナではマウント先の
+/// パスにする。バンドルやリモートでは実行ごとの一時ディレクトリに置かれるので、
+/// 実行のたびに環境が変わったと判定されないようエントリポイントの名前にしておく。
+fn resolve(runner: &str, source: &Source, cmd: &str) -> String {
+    let src = match (runner, source) {
+        ("local", Source::File(path)) => path.display().to_string(),
+        ("container", _) => format!("{}/{}", CONTAINER_WORKDIR, source.name()),
+        _ => source.name(),
+    };
+    cmd.replace("{{src}}", &src)
+}
+
+/// このマシンのホスト名。環境の記録のほか、ワーカーの既定の名前にも使う
+pub fn hostname() -> Option<String> {
+    std::fs::read_to_string("/proc/sys/kernel/hostname")
+        .ok()
+        .map(|h| h.trim().to_string())
+        .filter(|h| !h.is_empty())
+        .or_else(|| command_output("hostname", &[]))
+}
+
+fn cpu_model() -> Option<String> {
+    // Linux は /proc/cpuinfo、macOS は sysctl から読む
+    std::fs::read_to_string("/proc/cpuinfo")
+        .ok()
+        .and_then(|info| {
+            info.lines()
+                .find(|l| l.starts_with("model name"))
+                .and_then(|l| l.split_once(':'))
+                .map(|(_, model)| model.trim().to_string())
+        })
+        .or_else(|| command_output("sysctl", &["-n", "machdep.cpu.brand_string"]))
+}
+
+/// コンパイルコマンドの先頭のプログラム
+fn compiler(compile_cmd: &str) -> Option<&str> {
+    compile_cmd
+        .split_whitespace()
+        .next()
+        .filter(|program| !program.contains("{{"))
+}
+
+/// コンパイルコマンドの先頭のプログラムの `--version` の 1 行目
+fn compiler_version(compile_cmd: &str) -> Option<String> {
+    command_output(compiler(compile_cmd)?, &["--version"])
+}
+
+/// イメージの中のコンパイラの `--version` の 1 行目。`ContainerRunner` と同じく
+/// ENTRYPOINT を上書きし、ネットワークは使わない
+fn container_compiler_version(compile_cmd: &str, container: &ContainerConfig) -> Option<String> {
+    let version_cmd = format!("{} --version", compiler(compile_cmd)?);
+    let mut args = vec!["run", "--rm", "--network=none", "--entrypoint=sh"];
+    args.extend(container.extra_args.iter().map(String::as_str));
+    args.extend([container.image.as_str(), "-c", version_cmd.as_str()]);
+    command_output(&container.runtime, &args)
+}
+
+/// 成功すれば標準出力の空でない最初の行を返す
+fn command_output(program: &str, args: &[&str]) -> Option<String> {
+    let output = Command::new(program)
+        .args(args)
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(str::trim)
+        .find(|l| !l.is_empty())
+        .map(str::to_string)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn environment(runner: &str, parallelism: i32) -> Environment {
+        Environment {
+            hostname: Some("laptop".to_string()),
+            cpu_model: Some("AMD EPYC 7763".to_string()),
+            cpu_cores: Some(4),
+            os: Some("Linux 6.1.0".to_string()),
+            compiler_version: Some("g++ 13.2.0".to_string()),
+            compile_cmd: Some("g++ -O2 main.cpp".to_string()),
+            exec_cmd: Some("./a.out".to_string()),
+            runner: runner.to_string(),
+            parallelism,
+        }
+    }
+
+    #[test]
+    fn source_paths_are_resolved_per_runner() {
+        let source = Source::File(PathBuf::from("/home/me/contest/main.cpp"));
+        let cmd = "g++ -O2 {{src}}";
+
+        assert_eq!(
+            resolve("local", &source, cmd),
+            "g++ -O2 /home/me/contest/main.cpp"
+        );
+        assert_eq!(resolve("container", &source, cmd), "g++ -O2 /work/main.cpp");
+        // リモートでは実行ごとの一時ディレクトリに置かれるので、名前だけにする
+        assert_eq!(resolve("remote", &source, cmd), "g++ -O2 main.cpp");
+    }
+
+    #[test]
+    fn machine_information_is_recorded_only_on_this_machine() {
+        let container = ContainerConfig::default();
+
+        let local = capture_blocking("local", "true {{src}}", "./a.out", 2, &container);
+        assert_eq!(local.runner, "local");
+        assert_eq!(local.parallelism, 2);
+        assert!(local.cpu_cores.is_some());
+        assert_eq!(local.compile_cmd.as_deref(), Some("true {{src}}"));
+
+        let remote = capture_blocking("remote", "g++ main.cpp", "./a.out", 4, &container);
+        assert_eq!(remote.hostname, None);
+        assert_eq!(remote.cpu_model, None);
+        assert_eq!(remote.cpu_cores, None);
+        assert_eq!(remote.compiler_version, None);
+        assert_eq!(remote.exec_cmd.as_deref(), Some("./a.out"));
+
+        // aws は実行側のスクリプトのコマンドを使うので、コマンドも記録しない
+        let aws = capture_blocking("aws", "g++ main.cpp", "./a.out", 1, &container);
+        assert_eq!(aws.cpu_cores, None);
+        assert_eq!(aws.compile_cmd, None);
+        assert_eq!(aws.exec_cmd, None);
+    }
+
+    #[test]
+    fn differences_ignore_hostnames_and_unknown_fields() {
+        let base = environment("local", 4);
+
+        // ホスト名だけが違うなら同じ環境
+        let mut other_host = base.clone();
+        other_host.hostname = Some("desktop".to_string());
+        assert!(base.differences(&other_host).is_empty());
+
+        // 片方で分からない項目は比べない
+        let mut unknown = base.clone();
+        unknown.cpu_model = None;
+        unknown.compiler_version = None;
+        assert!(base.differences(&unknown).is_empty());
+
+        let mut changed = environment("remote", 8);
+        changed.compiler_version = Some("g++ 14.1.0".to_string());
+        assert_eq!(
+            base.differences(&changed),
+            ["runner", "compiler", "parallelism"]
+        );
+    }
+}
@@ -0,0 +1,276 @@
+//! 提出したファイルのある git リポジトリの状態の記録と、その状態の復元
+//!
+//! `heurs run` では HEAD のコミット・ブランチと、HEAD からの未コミットの変更を差分として保存する。
+//! 追跡していないファイル (新しく作ったヘッダなど) も差分に含めるので、
+//! `heurs submission checkout` でコミットを取り出して差分を当てれば実行時のツリーに戻る。
+
+use heurs_database::GitState;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+type GitError = Box<dyn std::error::Error + Send + Sync>;
+
+/// これより大きい追跡外のファイルは差分に含めない (ビルド成果物などを拾わないため)
+const UNTRACKED_FILE_LIMIT: u64 = 1024 * 1024;
+
+/// 提出したファイルの場所
+#[derive(Debug, Clone)]
+pub struct SourceLocation {
+    /// git リポジトリ内ならルートからの相対パス、そうでなければファイル名
+    pub source_path: String,
+    /// git リポジトリ内でなければ (または git が使えなければ) `None`
+    pub git: Option<GitState>,
+}
+
+/// 提出したファイルの場所と、それを含む git リポジトリの状態を調べる
+pub fn locate(source: &Path) -> SourceLocation {
+    let file_name = source
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let Some((root, relative)) = repository_root(source) else {
+        return SourceLocation {
+            source_path: file_name,
+            git: None,
+        };
+    };
+    match capture(&root) {
+        Some(git) => SourceLocation {
+            source_path: relative.to_string_lossy().into_owned(),
+            git: Some(git),
+        },
+        // コミットがまだないリポジトリ
+        None => SourceLocation {
+            source_path: file_name,
+            git: None,
+        },
+    }
+}
+
+/// `source` を含むリポジトリのルートと、ルートからの相対パス
+fn repository_root(source: &Path) -> Option<(PathBuf, PathBuf)> {
+    let source = source.canonicalize().ok()?;
+    let root = git(source.parent()?, &["rev-parse", "--show-toplevel"])?;
+    let root = PathBuf::from(root.trim()).canonicalize().ok()?;
+    let relative = source.strip_prefix(&root).ok()?.to_path_buf();
+    Some((root, relative))
+}
+
+fn capture(root: &Path) -> Option<GitState> {
+    let commit = git(root, &["rev-parse", "HEAD"])?.trim().to_string();
+    let branch = git(root, &["symbolic-ref", "--short", "-q", "HEAD"])
+        .map(|b| b.trim().to_string())
+        .filter(|b| !b.is_empty());
+
+    let mut diff = git(root, &["diff", "HEAD", "--binary"])?;
+    let untracked = git(root, &["ls-files", "--others", "--exclude-standard", "-z"])?;
+    for file in untracked.split('\0').filter(|f| !f.is_empty()) {
+        let size = std::fs::metadata(root.join(file)).map_or(u64::MAX, |m| m.len());
+        if size > UNTRACKED_FILE_LIMIT {
+            eprintln!(
+                "Warning: untracked file '{}' is larger than {} bytes and is not recorded",
+                file, UNTRACKED_FILE_LIMIT
+            );
+            continue;
+        }
+        // 差分があると終了コード 1 になるので、終了コードは見ずに出力を使う
+        if let Ok(output) = Command::new("git")
+            .current_dir(root)
+            .args(["diff", "--no-index", "--binary", "--", "/dev/null", file])
+            .output()
+        {
+            diff.push_str(&String::from_utf8_lossy(&output.stdout));
+        }
+    }
+
+    Some(GitState {
+        commit,
+        branch,
+        dirty: !diff.is_empty(),
+        diff,
+    })
+}
+
+/// `repo` (リポジトリ内のどこか) を含むリポジトリから `commit` を `dest` に worktree として
+/// 取り出し、`diff` を当てる
+pub fn checkout(
+    repo: &Path,
+    dest: &Path,
+    commit: &str,
+    diff: Option<&str>,
+) -> Result<(), GitError> {
+    let root = git(repo, &["rev-parse", "--show-toplevel"])
+        .map(|root| PathBuf::from(root.trim()))
+        .ok_or_else(|| format!("{} is not inside a git repository", repo.display()))?;
+    // 相対パスは呼び出し元のカレントディレクトリを基準にする
+    let dest = std::path::absolute(dest)?;
+    let output = Command::new("git")
+        .current_dir(&root)
+        .arg("worktree")
+        .arg("add")
+        .arg("--detach")
+        .arg(&dest)
+        .arg(commit)
+        .output()?;
+    if !output.status.success() {
+        return Err(format!(
+            "git worktree add failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )
+        .into());
+    }
+
+    let Some(diff) = diff.filter(|d| !d.is_empty()) else {
+        return Ok(());
+    };
+    let mut child = Command::new("git")
+        .current_dir(&dest)
+        .args(["apply", "--binary", "--whitespace=nowarn", "-"])
+        .stdin(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    child
+        .stdin
+        .take()
+        .ok_or("failed to open stdin of git apply")?
+        .write_all(diff.as_bytes())?;
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        return Err(format!(
+            "git apply failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )
+        .into());
+    }
+    Ok(())
+}
+
+/// `dir` で git を実行し、成功すれば標準出力を返す
+fn git(dir: &Path, args: &[&str]) -> Option<String> {
+    let output = Command::new("git")
+        .current_dir(dir)
+        .args(args)
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runner::Workspace;
+
+    /// `dir` で git を実行し、失敗したらテストを落とす
+    fn run(dir: &Path, args: &[&str]) {
+        let output = Command::new("git")
+            .current_dir(dir)
+            .args([
+                "-c",
+                "user.name=heurs",
+                "-c",
+                "user.email=heurs@example.com",
+            ])
+            .args(args)
+            .output()
+            .unwrap();
+        assert!(
+            output.status.success(),
+            "git {:?} failed: {}",
+            args,
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+
+    /// `src/main.cpp` を 1 つコミットしたリポジトリを作る
+    fn repository() -> Workspace {
+        let workspace = Workspace::create("heurs-git-test").unwrap();
+        let root = workspace.path();
+        run(root, &["init", "-q", "-b", "main"]);
+        std::fs::create_dir(root.join("src")).unwrap();
+        std::fs::write(root.join("src/main.cpp"), "int main() {}\n").unwrap();
+        run(root, &["add", "."]);
+        run(root, &["commit", "-q", "-m", "initial"]);
+        workspace
+    }
+
+    #[test]
+    fn clean_tree_records_the_commit_and_branch() {
+        let repo = repository();
+        let location = locate(&repo.path().join("src/main.cpp"));
+
+        assert_eq!(location.source_path, "src/main.cpp");
+        let git = location.git.unwrap();
+        assert_eq!(
+            git.commit,
+            self::git(repo.path(), &["rev-parse", "HEAD"])
+                .unwrap()
+                .trim()
+        );
+        assert_eq!(git.branch.as_deref(), Some("main"));
+        assert!(!git.dirty);
+        assert!(git.diff.is_empty());
+    }
+
+    #[test]
+    fn dirty_tree_records_changes_and_untracked_files() {
+        let repo = repository();
+        let root = repo.path();
+        std::fs::write(root.join("src/main.cpp"), "int main() { return 0; }\n").unwrap();
+        std::fs::write(root.join("src/util.hpp"), "#pragma once\n").unwrap();
+
+        let git = locate(&root.join("src/main.cpp")).git.unwrap();
+        assert!(git.dirty);
+        assert!(git.diff.contains("+int main() { return 0; }"));
+        assert!(git.diff.contains("+#pragma once"));
+    }
+
+    #[test]
+    fn detached_head_has_no_branch() {
+        let repo = repository();
+        run(repo.path(), &["checkout", "-q", "--detach"]);
+
+        let git = locate(&repo.path().join("src/main.cpp")).git.unwrap();
+        assert_eq!(git.branch, None);
+        assert!(!git.commit.is_empty());
+    }
+
+    #[test]
+    fn files_outside_a_repository_have_no_git_state() {
+        let dir = Workspace::create("heurs-git-test").unwrap();
+        let source = dir.path().join("main.cpp");
+        std::fs::write(&source, "int main() {}\n").unwrap();
+
+        let location = locate(&source);
+        assert_eq!(location.source_path, "main.cpp");
+        assert!(location.git.is_none());
+        assert!(checkout(dir.path(), &dir.path().join("out"), "HEAD", None).is_err());
+    }
+
+    #[test]
+    fn checkout_resolves_the_root_from_a_subdirectory() {
+        let repo = repository();
+        let root = repo.path();
+        std::fs::write(root.join("src/main.cpp"), "int main() { return 1; }\n").unwrap();
+        std::fs::write(root.join("src/util.hpp"), "#pragma once\n").unwrap();
+        let git = locate(&root.join("src/main.cpp")).git.unwrap();
+
+        // `--repo` にはリポジトリ内のどこを渡してもよい
+        let out = Workspace::create("heurs-git-test").unwrap();
+        let dest = out.path().join("restored");
+        checkout(&root.join("src"), &dest, &git.commit, Some(&git.diff)).unwrap();
+
+        assert_eq!(
+            std::fs::read_to_string(dest.join("src/main.cpp")).unwrap(),
+            "int main() { return 1; }\n"
+        );
+        assert_eq!(
+            std::fs::read_to_string(dest.join("src/util.hpp")).unwrap(),
+            "#pragma once\n"
+        );
+    }
+}
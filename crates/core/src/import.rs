@@ -0,0 +1,347 @@
+//! `heurs test-case add` で取り込むケースファイルとマニフェストの読み込み
+//!
+//! ケースはディレクトリのほか、公式の `in.zip` のような zip / tar.gz アーカイブからも読める。
+//! アーカイブ内のケースはアーカイブのルートからの相対パスで区別する (`in/0000.txt` と
+//! `out/0000.txt` は別のケース)。すべてが `in/` のような 1 つのディレクトリの下にあれば、
+//! そのディレクトリは名前に含めない。
+
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::io::{Read, Seek};
+use std::path::{Component, Path};
+
+type ImportError = Box<dyn std::error::Error + Send + Sync>;
+
+/// 取り込むケースファイル 1 つ分
+#[derive(Debug, Clone)]
+pub struct CaseFile {
+    pub filename: String,
+    pub input: String,
+}
+
+/// マニフェストの 1 行。ファイル名以外はすべて省略できる
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ManifestEntry {
+    pub filename: String,
+    #[serde(default)]
+    pub seed: Option<i64>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// 既知の最良スコア (想定スコア)
+    #[serde(default, alias = "expected_score")]
+    pub best_known: Option<i64>,
+}
+
+/// CSV の 1 行。タグは `;` 区切りで 1 列に書く
+#[derive(Deserialize)]
+struct CsvRecord {
+    filename: String,
+    #[serde(default)]
+    seed: Option<i64>,
+    #[serde(default)]
+    tags: Option<String>,
+    #[serde(default, alias = "expected_score")]
+    best_known: Option<i64>,
+}
+
+/// ケースとして扱うファイルか (拡張子が `.txt` か `.in`)
+fn is_case_file(name: &str) -> bool {
+    // macOS で作ったアーカイブに混ざるリソースフォークは除く
+    if name.starts_with("._") {
+        return false;
+    }
+    Path::new(name)
+        .extension()
+        .and_then(|s| s.to_str())
+        .is_some_and(|ext| ext == "txt" || ext == "in")
+}
+
+/// ディレクトリ・zip・tar.gz (tgz)・tar からケースファイルを読み、ファイル名順に返す
+pub fn read_cases(path: &Path) -> Result<Vec<CaseFile>, ImportError> {
+    let name = path
+        .file_name()
+        .and_then(|s| s.to_str())
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+
+    let mut cases = if path.is_dir() {
+        read_dir(path)?
+    } else if name.ends_with(".zip") {
+        read_zip(std::fs::File::open(path)?)?
+    } else if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+        read_tar(flate2::read::GzDecoder::new(std::fs::File::open(path)?))?
+    } else if name.ends_with(".tar") {
+        read_tar(std::fs::File::open(path)?)?
+    } else {
+        return Err(format!(
+            "{} is not a directory or a .zip / .tar.gz / .tar archive",
+            path.display()
+        )
+        .into());
+    };
+
+    cases.sort_by(|a, b| a.filename.cmp(&b.filename));
+    Ok(cases)
+}
+
+/// アーカイブのエントリのパスを `/` 区切りの相対パスにする (`./` はルートそのものなので空)。
+/// `..` や絶対パスのようにルートの外を指すものは `None`
+pub(crate) fn relative_path(path: &Path) -> Option<String> {
+    let mut parts = Vec::new();
+    for component in path.components() {
+        match component {
+            Component::Normal(part) => parts.push(part.to_string_lossy()),
+            Component::CurDir => {}
+            Component::ParentDir | Component::RootDir | Component::Prefix(_) => return None,
+        }
+    }
+    Some(parts.join("/"))
+}
+
+/// アーカイブ内のケースファイルのパス。ケースでなければ `None`、ルートの外を指していればエラー
+fn archive_case_path(path: &Path) -> Result<Option<String>, ImportError> {
+    let relative = relative_path(path)
+        .ok_or_else(|| format!("Archive contains an unsafe path: {}", path.display()))?;
+    let is_case = relative.rsplit('/').next().is_some_and(is_case_file);
+    Ok(is_case.then_some(relative))
+}
+
+/// すべてのケースが同じディレクトリの下にあれば、名前からそのディレクトリを除く
+fn strip_common_dir(cases: &mut [CaseFile]) {
+    loop {
+        let Some(dir) = cases
+            .first()
+            .and_then(|c| c.filename.split_once('/'))
+            .map(|(dir, _)| format!("{}/", dir))
+        else {
+            return;
+        };
+        if !cases.iter().all(|c| c.filename.starts_with(&dir)) {
+            return;
+        }
+        for case in cases.iter_mut() {
+            case.filename.drain(..dir.len());
+        }
+    }
+}
+
+fn read_dir(path: &Path) -> Result<Vec<CaseFile>, ImportError> {
+    let mut cases = Vec::new();
+    for entry in std::fs::read_dir(path)? {
+        let path = entry?.path();
+        let filename = path
+            .file_name()
+            .and_then(|s| s.to_str())
+            .unwrap_or_default()
+            .to_string();
+        if path.is_file() && is_case_file(&filename) {
+            cases.push(CaseFile {
+                input: std::fs::read_to_string(&path)?,
+                filename,
+            });
+        }
+    }
+    Ok(cases)
+}
+
+fn read_zip<R: Read + Seek>(reader: R) -> Result<Vec<CaseFile>, ImportError> {
+    let mut archive = zip::ZipArchive::new(reader)?;
+    let mut cases = Vec::new();
+    for i in 0..archive.len() {
+        let mut file = archive.by_index(i)?;
+        if !file.is_file() {
+            continue;
+        }
+        let Some(filename) = archive_case_path(Path::new(file.name()))? else {
+            continue;
+        };
+        let mut input = String::new();
+        file.read_to_string(&mut input)?;
+        cases.push(CaseFile { filename, input });
+    }
+    strip_common_dir(&mut cases);
+    Ok(cases)
+}
+
+fn read_tar<R: Read>(reader: R) -> Result<Vec<CaseFile>, ImportError> {
+    let mut archive = tar::Archive::new(reader);
+    let mut cases = Vec::new();
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+        let Some(filename) = archive_case_path(&entry.path()?)? else {
+            continue;
+        };
+        let mut input = String::new();
+        entry.read_to_string(&mut input)?;
+        cases.push(CaseFile { filename, input });
+    }
+    strip_common_dir(&mut cases);
+    Ok(cases)
+}
+
+/// マニフェスト (`.json` なら配列、それ以外はヘッダ付きの CSV) を読み、ファイル名で引けるようにする
+pub fn load_manifest(path: &Path) -> Result<HashMap<String, ManifestEntry>, ImportError> {
+    let is_json = path
+        .extension()
+        .and_then(|s| s.to_str())
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("json"));
+
+    let entries: Vec<ManifestEntry> = if is_json {
+        serde_json::from_str(&std::fs::read_to_string(path)?)?
+    } else {
+        let mut reader = csv::ReaderBuilder::new()
+            .trim(csv::Trim::All)
+            .from_path(path)?;
+        let mut entries = Vec::new();
+        for record in reader.deserialize() {
+            let record: CsvRecord = record?;
+            entries.push(ManifestEntry {
+                filename: record.filename,
+                seed: record.seed,
+                tags: record
+                    .tags
+                    .unwrap_or_default()
+                    .split(';')
+                    .map(|t| t.trim().to_string())
+                    .filter(|t| !t.is_empty())
+                    .collect(),
+                best_known: record.best_known,
+            });
+        }
+        entries
+    };
+
+    let mut manifest = HashMap::new();
+    for entry in entries {
+        if manifest.contains_key(&entry.filename) {
+            return Err(format!("{} appears twice in the manifest", entry.filename).into());
+        }
+        manifest.insert(entry.filename.clone(), entry);
+    }
+    Ok(manifest)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Cursor, Write};
+
+    fn zip_archive(files: &[(&str, &str)]) -> Cursor<Vec<u8>> {
+        let mut writer = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        for (name, contents) in files {
+            writer
+                .start_file(*name, zip::write::SimpleFileOptions::default())
+                .unwrap();
+            writer.write_all(contents.as_bytes()).unwrap();
+        }
+        writer.finish().unwrap()
+    }
+
+    fn tar_archive(files: &[(&str, &str)]) -> Vec<u8> {
+        let mut builder = tar::Builder::new(Vec::new());
+        for (name, contents) in files {
+            let mut header = tar::Header::new_gnu();
+            // set_path は `..` や絶対パスを受け付けないので名前を直接書く
+            header.as_old_mut().name[..name.len()].copy_from_slice(name.as_bytes());
+            header.set_size(contents.len() as u64);
+            header.set_mode(0o644);
+            header.set_entry_type(tar::EntryType::Regular);
+            header.set_cksum();
+            builder.append(&header, contents.as_bytes()).unwrap();
+        }
+        builder.into_inner().unwrap()
+    }
+
+    fn filenames(cases: &[CaseFile]) -> Vec<&str> {
+        cases.iter().map(|c| c.filename.as_str()).collect()
+    }
+
+    #[test]
+    fn read_zip_strips_the_common_directory() {
+        let cases = read_zip(zip_archive(&[
+            ("in/0000.txt", "1"),
+            ("in/0001.txt", "2"),
+            ("in/README.md", "not a case"),
+            ("__MACOSX/in/._0000.txt", "resource fork"),
+        ]))
+        .unwrap();
+        assert_eq!(filenames(&cases), ["0000.txt", "0001.txt"]);
+        assert_eq!(cases[1].input, "2");
+    }
+
+    #[test]
+    fn read_zip_keeps_directories_that_differ() {
+        let cases = read_zip(zip_archive(&[
+            ("in/0000.txt", "in"),
+            ("out/0000.txt", "out"),
+        ]))
+        .unwrap();
+        assert_eq!(filenames(&cases), ["in/0000.txt", "out/0000.txt"]);
+    }
+
+    #[test]
+    fn read_tar_keeps_relative_paths() {
+        let archive = tar_archive(&[
+            ("./cases/a/0000.in", "a"),
+            ("./cases/b/0000.in", "b"),
+            ("./cases/b/0001.in", "c"),
+        ]);
+        let cases = read_tar(archive.as_slice()).unwrap();
+        assert_eq!(filenames(&cases), ["a/0000.in", "b/0000.in", "b/0001.in"]);
+    }
+
+    /// `name` のマニフェストを書いて読む
+    fn manifest(name: &str, contents: &str) -> Result<HashMap<String, ManifestEntry>, ImportError> {
+        let dir = std::env::temp_dir().join(format!("heurs-import-test-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(name);
+        std::fs::write(&path, contents).unwrap();
+        let manifest = load_manifest(&path);
+        std::fs::remove_dir_all(&dir).unwrap();
+        manifest
+    }
+
+    #[test]
+    fn load_manifest_reads_csv() {
+        let manifest = manifest(
+            "manifest.csv",
+            "filename,seed,tags,best_known\n0000.txt, 1, small; dense ;,100\n0001.txt,,,\n",
+        )
+        .unwrap();
+        let entry = &manifest["0000.txt"];
+        assert_eq!(entry.seed, Some(1));
+        assert_eq!(entry.tags, ["small", "dense"]);
+        assert_eq!(entry.best_known, Some(100));
+        let entry = &manifest["0001.txt"];
+        assert_eq!(entry.seed, None);
+        assert!(entry.tags.is_empty());
+    }
+
+    #[test]
+    fn load_manifest_reads_json() {
+        let manifest = manifest(
+            "manifest.JSON",
+            r#"[{"filename": "0000.txt", "tags": ["a"], "expected_score": 5}, {"filename": "0001.txt"}]"#,
+        )
+        .unwrap();
+        assert_eq!(manifest["0000.txt"].best_known, Some(5));
+        assert_eq!(manifest["0000.txt"].tags, ["a"]);
+        assert_eq!(manifest["0001.txt"].seed, None);
+    }
+
+    #[test]
+    fn load_manifest_rejects_duplicates() {
+        let err = manifest("manifest.csv", "filename\n0000.txt\n0000.txt\n").unwrap_err();
+        assert!(err.to_string().contains("0000.txt appears twice"));
+    }
+
+    #[test]
+    fn archives_with_unsafe_paths_are_rejected() {
+        assert!(read_zip(zip_archive(&[("../0000.txt", "1")])).is_err());
+        assert!(read_tar(tar_archive(&[("in/../../0000.txt", "1")]).as_slice()).is_err());
+        assert!(read_tar(tar_archive(&[("/tmp/0000.txt", "1")]).as_slice()).is_err());
+    }
+}
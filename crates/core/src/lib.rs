@@ -1,8 +1,24 @@
 // モジュール宣言
+pub mod bundle;
+pub mod calibrate;
 pub mod config;
+pub mod environment;
+pub mod git;
+pub mod import;
 pub mod macros;
+pub mod remote;
 pub mod runner;
+pub mod stats;
+pub mod worker;
 
 // Runner関連を再エクスポート
-pub use config::{Config, load_config};
-pub use runner::{AWSRunner, ExecutionResult, LocalRunner, Runner};
+pub use bundle::Bundle;
+pub use config::{
+    Aws as AwsConfig, Config, Container as ContainerConfig, Cpu as CpuConfig,
+    Remote as RemoteConfig, Sandbox as SandboxConfig, load_config, load_problem,
+    resolve_database_url,
+};
+pub use runner::{
+    AWSRunner, ContainerRunner, ExecutionResult, LocalRunner, ProgressSender, RemoteRunner, Runner,
+    Source, Workspace,
+};
@@ -0,0 +1,145 @@
+//! `RemoteRunner` とワーカー (`heurs worker`) がバックエンドを介してやり取りする型
+//!
+//! バックエンドはジョブをバッチに分けてキューに積み、ワーカーはそれを取りに来て
+//! ローカルで実行し、結果を送り返す。RemoteRunner はジョブを投入して結果を待つだけ。
+
+use crate::bundle::Bundle;
+use crate::runner::ExecutionResult;
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use serde::{Deserialize, Serialize};
+
+/// ワーカーに渡す 1 ケース分の入力
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct RemoteCase {
+    pub test_case_id: i32,
+    pub filename: String,
+    pub input: String,
+}
+
+/// ジョブ投入とワーカーの API で `Authorization: Bearer <token>` として送る共有トークンの環境変数
+pub const TOKEN_ENV: &str = "HEURS_REMOTE_TOKEN";
+
+/// `POST /api/remote/jobs` : RemoteRunner からのジョブ投入
+///
+/// コンパイル・実行のコマンドはリクエストでは受け取らず、バックエンドの設定ファイルのものを使う。
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct RemoteJobRequest {
+    pub source_code: String,
+    /// ワーカー側で保存するソースのファイル名 (拡張子をコンパイルコマンドに合わせるため)。
+    /// ディレクトリを含まないファイル名でなければならない ([`is_plain_file_name`])
+    pub source_name: String,
+    /// 複数ファイルの提出なら、そのファイル一式 (`source_code` はエントリポイントの内容)
+    #[serde(default)]
+    pub bundle: Option<RemoteBundle>,
+    pub timeout: u32,
+    /// 各ケースを実行する回数 (回ごとに HEURS_RNG_SEED を変える)
+    #[serde(default = "default_repeat")]
+    pub repeat: u32,
+    /// 1 回の貸し出しでワーカーに渡すケース数
+    pub batch_size: u32,
+    pub cases: Vec<RemoteCase>,
+}
+
+fn default_repeat() -> u32 {
+    1
+}
+
+/// `main.cpp` のような、ディレクトリを含まない 1 つのファイル名か。
+/// ワーカーは作業ディレクトリにこの名前でソースを書くので、`../.bashrc` などは受け付けない
+pub fn is_plain_file_name(name: &str) -> bool {
+    !name.is_empty() && name != "." && name != ".." && !name.contains(['/', '\\', '\0'])
+}
+
+/// JSON で送るためにアーカイブを base64 にしたバンドル
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct RemoteBundle {
+    pub entry: String,
+    pub archive: String,
+}
+
+impl From<&Bundle> for RemoteBundle {
+    fn from(bundle: &Bundle) -> Self {
+        RemoteBundle {
+            entry: bundle.entry().to_string(),
+            archive: BASE64.encode(bundle.archive()),
+        }
+    }
+}
+
+impl TryFrom<&RemoteBundle> for Bundle {
+    type Error = base64::DecodeError;
+
+    fn try_from(bundle: &RemoteBundle) -> Result<Self, Self::Error> {
+        Ok(Bundle::from_archive(
+            bundle.entry.clone(),
+            BASE64.decode(&bundle.archive)?,
+        ))
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct RemoteJobCreated {
+    pub job_id: String,
+}
+
+/// `GET /api/remote/jobs/{id}/results?after=N` : N 件目以降に届いた結果
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct RemoteJobResults {
+    pub results: Vec<ExecutionResult>,
+    pub total: usize,
+    pub done: bool,
+}
+
+/// `POST /api/workers/register`
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct WorkerRegistration {
+    pub name: String,
+    pub parallel: u32,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct WorkerRegistered {
+    pub worker_id: String,
+}
+
+/// ワーカーに貸し出す実行単位
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct RemoteBatch {
+    pub batch_id: String,
+    pub job_id: String,
+    pub source_code: String,
+    pub source_name: String,
+    #[serde(default)]
+    pub bundle: Option<RemoteBundle>,
+    pub compile_cmd: String,
+    pub exec_cmd: String,
+    pub timeout: u32,
+    #[serde(default = "default_repeat")]
+    pub repeat: u32,
+    pub cases: Vec<RemoteCase>,
+}
+
+/// `POST /api/workers/{id}/lease` : 仕事がなければ `batch` は `None`
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct LeaseResponse {
+    pub batch: Option<RemoteBatch>,
+}
+
+/// `POST /api/workers/{id}/batches/{batch_id}/results`
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct BatchResults {
+    pub results: Vec<ExecutionResult>,
+}
+
+/// `GET /api/workers` : 登録済みワーカーの一覧
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct WorkerInfo {
+    pub worker_id: String,
+    pub name: String,
+    pub parallel: u32,
+    /// 最後に貸し出し要求が来てからの秒数
+    pub last_seen_secs: u64,
+    /// 現在貸し出し中のバッチ数
+    pub leased_batches: usize,
+}
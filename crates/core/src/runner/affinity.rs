@@ -0,0 +1,144 @@
+use crate::config::Cpu as CpuConfig;
+use std::collections::BTreeSet;
+use std::io;
+use std::os::unix::process::CommandExt;
+use std::process::Command;
+use std::sync::{Arc, Mutex};
+
+/// ケースの実行に使えるコアの一覧を返す。
+///
+/// 自プロセスに許可されているコア (taskset などで絞られていればその範囲) から
+/// `reserved_cores` を除き、`avoid_smt_siblings` なら物理コアごとに 1 スレッドだけ残す。
+pub(crate) fn available_cores(config: &CpuConfig) -> io::Result<Vec<usize>> {
+    // SAFETY: set はゼロ初期化した cpu_set_t で、サイズも正しく渡している
+    let allowed = unsafe {
+        let mut set: libc::cpu_set_t = std::mem::zeroed();
+        if libc::sched_getaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &mut set) != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        (0..libc::CPU_SETSIZE as usize)
+            .filter(|&cpu| libc::CPU_ISSET(cpu, &set))
+            .collect::<Vec<_>>()
+    };
+
+    let mut cores = Vec::new();
+    let mut used_siblings = BTreeSet::new();
+    for cpu in allowed {
+        if config.reserved_cores.contains(&cpu) {
+            continue;
+        }
+        if config.avoid_smt_siblings {
+            // 同じ物理コアの別スレッドを既に採用していれば飛ばす
+            let siblings = thread_siblings(cpu);
+            if siblings
+                .iter()
+                .any(|sibling| used_siblings.contains(sibling))
+            {
+                continue;
+            }
+            used_siblings.extend(siblings);
+        }
+        cores.push(cpu);
+    }
+
+    if cores.is_empty() {
+        return Err(io::Error::other(
+            "No CPU cores left for execution after applying [cpu] settings",
+        ));
+    }
+    Ok(cores)
+}
+
+/// 同じ物理コアを共有する論理 CPU の一覧 (自身を含む)
+fn thread_siblings(cpu: usize) -> Vec<usize> {
+    let path = format!(
+        "/sys/devices/system/cpu/cpu{}/topology/thread_siblings_list",
+        cpu
+    );
+    match std::fs::read_to_string(path) {
+        Ok(list) => parse_cpu_list(list.trim()),
+        Err(_) => vec![cpu],
+    }
+}
+
+/// "0-3,8,10-11" 形式の CPU リストを展開する
+fn parse_cpu_list(list: &str) -> Vec<usize> {
+    let mut cpus = Vec::new();
+    for part in list.split(',').filter(|part| !part.is_empty()) {
+        match part.split_once('-') {
+            Some((start, end)) => {
+                if let (Ok(start), Ok(end)) = (start.parse::<usize>(), end.parse::<usize>()) {
+                    cpus.extend(start..=end);
+                }
+            }
+            None => cpus.extend(part.parse::<usize>().ok()),
+        }
+    }
+    cpus
+}
+
+/// `command` で起動するプロセスを `core` だけで動くように固定する
+pub(crate) fn pin_command(command: &mut Command, core: usize) {
+    // SAFETY: fork 後の子プロセスでは sched_setaffinity を呼ぶだけ
+    unsafe {
+        command.pre_exec(move || {
+            let mut set: libc::cpu_set_t = std::mem::zeroed();
+            libc::CPU_SET(core, &mut set);
+            if libc::sched_setaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &set) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(())
+        });
+    }
+}
+
+/// 並列に走るケースへ専用のコアを 1 つずつ貸し出す
+pub(crate) struct CorePool {
+    free: Mutex<Vec<usize>>,
+}
+
+impl CorePool {
+    pub(crate) fn new(cores: Vec<usize>) -> Self {
+        CorePool {
+            free: Mutex::new(cores),
+        }
+    }
+
+    /// 空いているコアを借りる。並列数はコア数以下に抑えているので枯渇しない
+    pub(crate) fn acquire(self: &Arc<Self>) -> Option<CoreLease> {
+        let core = self.free.lock().unwrap().pop()?;
+        Some(CoreLease {
+            pool: self.clone(),
+            core,
+        })
+    }
+}
+
+/// 借りたコア。drop で返却される
+pub(crate) struct CoreLease {
+    pool: Arc<CorePool>,
+    pub(crate) core: usize,
+}
+
+impl Drop for CoreLease {
+    fn drop(&mut self) {
+        self.pool.free.lock().unwrap().push(self.core);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_cpu_list_expands_ranges() {
+        assert_eq!(parse_cpu_list("0-3,8,10-11"), [0, 1, 2, 3, 8, 10, 11]);
+        assert_eq!(parse_cpu_list("5"), [5]);
+        assert!(parse_cpu_list("").is_empty());
+    }
+
+    #[test]
+    fn parse_cpu_list_skips_invalid_parts() {
+        assert_eq!(parse_cpu_list("a,2,3-x,,4-5"), [2, 4, 5]);
+    }
+}
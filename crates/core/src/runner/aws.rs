@@ -1,261 +1,883 @@
-use crate::runner::{ExecutionResult, Runner};
+use crate::config::Aws as AwsConfig;
+use crate::runner::{ExecutionResult, ProgressSender, Runner, Source};
 use async_trait::async_trait;
-use aws_config::{self, BehaviorVersion};
+use aws_config::retry::RetryConfig;
+use aws_config::{self, BehaviorVersion, Region, SdkConfig};
 use aws_sdk_batch::types::JobStatus;
 use aws_sdk_batch::{
     Client as BatchClient,
-    types::{ContainerOverrides, KeyValuePair},
+    types::{
+        ArrayProperties, ContainerOverrides, EvaluateOnExit, KeyValuePair, RetryAction,
+        RetryStrategy,
+    },
 };
 use aws_sdk_s3::Client;
+use aws_sdk_s3::config::Credentials;
 use aws_sdk_s3::primitives::ByteStream;
+use aws_sdk_s3::types::{Delete, ObjectIdentifier};
 use heurs_database::TestCaseModel;
-use std::path::Path;
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use tokio::sync::Semaphore;
+use tokio::task::{Id as TaskId, JoinError, JoinSet};
 use tokio::time::{Duration, sleep};
+use uuid::Uuid;
+
+/// 実行ごとの S3 名前空間の親プレフィックス。
+///
+/// 各実行は `runs/<run_id>/` 以下にのみ書き込むため、同時実行や過去の実行の
+/// 残骸と結果が混ざることはない。消し損ねたものはバケットのライフサイクル
+/// ルール (`infra/aws_runner/s3_lifecycle.sh`) で期限切れにする想定。
+const RUNS_PREFIX: &str = "runs";
+
+/// バンドルをアップロードするときのファイル名
+const BUNDLE_NAME: &str = "bundle.tar.gz";
+
+/// 配列ジョブの子ジョブが担当するケース ID を 1 行 1 件で並べたファイルの名前
+const CASE_LIST_NAME: &str = "cases.txt";
+
+/// 子ジョブの完了を確認する間隔
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// S3 からの結果ダウンロードの既定の同時実行数
+const DEFAULT_DOWNLOAD_CONCURRENCY: usize = 16;
+
+/// SDK 呼び出し 1 回あたりの既定の最大試行回数 (初回を含む)
+const DEFAULT_MAX_ATTEMPTS: u32 = 5;
+
+/// SDK 呼び出しの再試行の既定の初回待ち時間 (以降は指数バックオフ)
+const DEFAULT_INITIAL_BACKOFF_MS: u64 = 500;
+
+/// 子ジョブ 1 件あたりの既定の Batch 試行回数 (初回を含む)。Batch の上限は 10。
+const DEFAULT_JOB_ATTEMPTS: u32 = 3;
+
+/// ジョブ側でコンパイルに失敗したときの終了コード。何度やっても同じなので再試行しない。
+const COMPILE_ERROR_EXIT_CODE: &str = "3";
+
+/// コンパイルに失敗したとき、ジョブ側がコンパイラの標準エラー出力を置くファイルの名前
+const COMPILE_ERROR_NAME: &str = "compile_error.txt";
+
+/// この回数だけ続けて状態確認に失敗したら、ジョブを見失ったとみなして打ち切る
+const MAX_CONSECUTIVE_POLL_FAILURES: u32 = 10;
+
+/// Batch の配列ジョブの子ジョブ数の上限
+const MAX_ARRAY_SIZE: usize = 10_000;
+
+/// 完了した子ジョブ 1 件分の情報
+#[derive(Debug, Clone)]
+struct FinishedChild {
+    /// 配列インデックス。`index / repeat` がケース一覧の行、`index % repeat` が繰り返しの番号
+    index: usize,
+    succeeded: bool,
+    status_reason: Option<String>,
+}
+
+/// 1 ケース分の結果が置かれる S3 キー
+struct CaseKeys {
+    output: String,
+    error: String,
+    /// プログラムの終了コード。これがあればジョブ自体の成否に関係なくケースは実行済み。
+    status: String,
+}
+
+/// 実行の名前空間 (`runs/<run_id>/`) を必ず削除するためのガード。
+///
+/// 通常は [`PrefixCleanup::finish`] で削除を待つ。途中の `?` で抜けた場合も
+/// 呼び出し側で `finish` を通すが、future ごと破棄された場合は `Drop` で
+/// 削除をバックグラウンドに投げる。
+struct PrefixCleanup {
+    client: Client,
+    bucket: String,
+    prefix: String,
+    done: bool,
+}
+
+impl PrefixCleanup {
+    fn new(client: &Client, bucket: &str, prefix: &str) -> Self {
+        PrefixCleanup {
+            client: client.clone(),
+            bucket: bucket.to_string(),
+            prefix: prefix.to_string(),
+            done: false,
+        }
+    }
+
+    /// 名前空間を削除し終えるまで待つ。
+    async fn finish(mut self) {
+        self.done = true;
+        Self::cleanup(
+            self.client.clone(),
+            std::mem::take(&mut self.bucket),
+            std::mem::take(&mut self.prefix),
+        )
+        .await;
+    }
+
+    /// 失敗してもライフサイクルルールで期限切れになるため警告に留める。
+    async fn cleanup(client: Client, bucket: String, prefix: String) {
+        if let Err(e) = AWSRunner::cleanup_prefix(&client, &bucket, &prefix).await {
+            eprintln!("Failed to clean up s3://{}/{}: {}", bucket, prefix, e);
+        }
+    }
+}
+
+impl Drop for PrefixCleanup {
+    fn drop(&mut self) {
+        if self.done {
+            return;
+        }
+        if let Ok(handle) = tokio::runtime::Handle::try_current() {
+            handle.spawn(Self::cleanup(
+                self.client.clone(),
+                std::mem::take(&mut self.bucket),
+                std::mem::take(&mut self.prefix),
+            ));
+        }
+    }
+}
 
 /// AWS実行器の実装
 ///
 /// このRunnerは、コンパイルと実行をAWS上で行います。
 /// AWSのサービス（例: S3, AWS Batch）と連携することを想定しています。
 pub struct AWSRunner {
-    // 現時点では状態を保持しない。必要になったらクライアントやバケット名を追加予定。
+    config: AwsConfig,
 }
 
 impl AWSRunner {
-    /// 新しい `AWSRunner` を生成する。
+    /// `[aws]` セクションの設定から `AWSRunner` を生成する。
     ///
-    /// 現在は特別な初期化は不要なので即座に構造体を返すだけ。
-    pub fn new() -> Self {
-        AWSRunner {}
+    /// クライアントは実行ごとに作るので、ここでは設定を保持するだけ。
+    pub fn new(config: AwsConfig) -> Self {
+        AWSRunner { config }
     }
-}
 
-#[async_trait]
-impl Runner for AWSRunner {
-    async fn execute(
-        &self,
-        source_path: &Path,
-        _compile_cmd: &str,
-        _exec_cmd: &str,
-        parallel: u32,
-        test_cases: Vec<TestCaseModel>,
-        _timeout: u32,
-    ) -> Result<Vec<ExecutionResult>, Box<dyn std::error::Error + Send + Sync>> {
-        // ---- AWS SDK 初期化 ----
-        let config = aws_config::load_defaults(BehaviorVersion::latest()).await;
-        let client = Client::new(&config);
+    /// 入出力を置く S3 バケット名。設定 → `HEURS_S3_BUCKET` → 既定値の順に解決する。
+    pub fn bucket(&self) -> String {
+        Self::resolve(&self.config.bucket, "HEURS_S3_BUCKET", "heurs-fs")
+    }
 
-        // ---- バケット名決定 ----
-        let bucket_name =
-            std::env::var("HEURS_S3_BUCKET").unwrap_or_else(|_| "heurs-fs".to_string());
-
-        // ---- オブジェクトキー決定 ----
-        let filename = source_path
-            .file_name()
-            .and_then(|s| s.to_str())
-            .unwrap_or("source")
-            .to_string();
-        // 秒単位の UNIX タイムスタンプを付与して衝突を回避
-        let ts = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .as_secs();
-        let key = format!("{}-{}", ts, filename);
-
-        // ---- ファイルを ByteStream に変換 ----
-        let body = ByteStream::from_path(source_path).await?;
-
-        // ---- アップロード実行 ----
+    /// ジョブを投入する Batch キュー名。設定 → `HEURS_BATCH_QUEUE` → 既定値の順に解決する。
+    pub fn job_queue(&self) -> String {
+        Self::resolve(
+            &self.config.job_queue,
+            "HEURS_BATCH_QUEUE",
+            "aws-runner-queue",
+        )
+    }
+
+    /// Batch のジョブ定義名。設定 → `HEURS_JOB_DEFINITION` → 既定値の順に解決する。
+    pub fn job_definition(&self) -> String {
+        Self::resolve(
+            &self.config.job_definition,
+            "HEURS_JOB_DEFINITION",
+            "aws-runner",
+        )
+    }
+
+    /// 子ジョブ 1 件あたりの Batch 試行回数。Batch の制約に合わせて 1..=10 に収める。
+    fn job_attempts(&self) -> u32 {
+        self.config
+            .job_attempts
+            .unwrap_or(DEFAULT_JOB_ATTEMPTS)
+            .clamp(1, 10)
+    }
+
+    /// 入力のアップロードと結果のダウンロードの同時実行数。0 が指定された場合も 1 として扱う。
+    fn download_concurrency(&self) -> usize {
+        self.config
+            .download_concurrency
+            .unwrap_or(DEFAULT_DOWNLOAD_CONCURRENCY)
+            .max(1)
+    }
+
+    fn resolve(value: &Option<String>, env_key: &str, default: &str) -> String {
+        value
+            .clone()
+            .or_else(|| std::env::var(env_key).ok())
+            .unwrap_or_else(|| default.to_string())
+    }
+
+    /// 設定を反映した共通の SDK 設定を読み込む。
+    ///
+    /// 指定がない項目は AWS SDK の既定のチェーン (環境変数・プロファイル等) に任せる。
+    async fn sdk_config(&self) -> SdkConfig {
+        // スロットリングや 5xx などの一時的なエラーは SDK 側で指数バックオフしつつ再試行する
+        let retry_config = RetryConfig::standard()
+            .with_max_attempts(
+                self.config
+                    .max_attempts
+                    .unwrap_or(DEFAULT_MAX_ATTEMPTS)
+                    .max(1),
+            )
+            .with_initial_backoff(Duration::from_millis(
+                self.config
+                    .initial_backoff_ms
+                    .unwrap_or(DEFAULT_INITIAL_BACKOFF_MS),
+            ));
+
+        let mut loader = aws_config::defaults(BehaviorVersion::latest()).retry_config(retry_config);
+        if let Some(region) = &self.config.region {
+            loader = loader.region(Region::new(region.clone()));
+        }
+        if let Some(endpoint_url) = &self.config.endpoint_url {
+            loader = loader.endpoint_url(endpoint_url);
+        }
+        if let Some(profile) = &self.config.profile {
+            loader = loader.profile_name(profile);
+        }
+        if let (Some(access_key_id), Some(secret_access_key)) =
+            (&self.config.access_key_id, &self.config.secret_access_key)
+        {
+            loader = loader.credentials_provider(Credentials::new(
+                access_key_id,
+                secret_access_key,
+                None,
+                None,
+                "heurs-config",
+            ));
+        }
+        loader.load().await
+    }
+
+    /// S3 クライアントを生成する。MinIO 等向けにパス形式のアドレッシングも切り替えられる。
+    pub async fn s3_client(&self) -> Client {
+        let sdk_config = self.sdk_config().await;
+        let s3_config = aws_sdk_s3::config::Builder::from(&sdk_config)
+            .force_path_style(self.config.force_path_style)
+            .build();
+        Client::from_conf(s3_config)
+    }
+
+    /// AWS Batch クライアントを生成する。
+    pub async fn batch_client(&self) -> BatchClient {
+        BatchClient::new(&self.sdk_config().await)
+    }
+
+    /// ソースファイル (バンドルならその tar.gz) を実行の名前空間にアップロードし、そのキーを返す。
+    pub async fn upload_source(
+        client: &Client,
+        bucket: &str,
+        run_prefix: &str,
+        source: &Source,
+    ) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+        let (key, body) = match source {
+            Source::File(path) => (
+                format!("{}{}", run_prefix, source.name()),
+                ByteStream::from_path(path).await?,
+            ),
+            Source::Bundle(bundle) => (
+                format!("{}{}", run_prefix, BUNDLE_NAME),
+                ByteStream::from(bundle.archive().to_vec()),
+            ),
+        };
         client
             .put_object()
-            .bucket(&bucket_name)
+            .bucket(bucket)
             .key(&key)
             .body(body)
             .send()
             .await?;
 
-        // ---- Batch ジョブ送信 ----
-        let job_queue =
-            std::env::var("HEURS_BATCH_QUEUE").unwrap_or_else(|_| "aws-runner-queue".to_string());
-        let job_definition =
-            std::env::var("HEURS_JOB_DEFINITION").unwrap_or_else(|_| "aws-runner".to_string());
+        Ok(key)
+    }
+
+    /// 各ケースの入力を `<run_prefix>inputs/<ケース ID>.txt` に、実行順のケース ID の一覧を
+    /// `<run_prefix>cases.txt` にアップロードし、一覧のキーを返す。
+    ///
+    /// ジョブ側は配列インデックスから一覧の行を引いてケース ID を決めるので、
+    /// 入力も結果もテストケースの ID で対応付けられる。
+    pub async fn upload_inputs(
+        client: &Client,
+        bucket: &str,
+        run_prefix: &str,
+        test_cases: &[TestCaseModel],
+        concurrency: usize,
+    ) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+        let semaphore = Arc::new(Semaphore::new(concurrency));
+        let mut uploads = JoinSet::new();
+        for test_case in test_cases {
+            let semaphore = semaphore.clone();
+            let client = client.clone();
+            let bucket = bucket.to_string();
+            let key = format!("{}inputs/{}.txt", run_prefix, test_case.id);
+            let body = test_case.input.clone().into_bytes();
+            uploads.spawn(async move {
+                let _permit = semaphore.acquire_owned().await.expect("semaphore closed");
+                client
+                    .put_object()
+                    .bucket(bucket)
+                    .key(key)
+                    .body(ByteStream::from(body))
+                    .send()
+                    .await
+            });
+        }
+        while let Some(joined) = uploads.join_next().await {
+            joined??;
+        }
+
+        let key = format!("{}{}", run_prefix, CASE_LIST_NAME);
+        let case_list: String = test_cases
+            .iter()
+            .map(|test_case| format!("{}\n", test_case.id))
+            .collect();
+        client
+            .put_object()
+            .bucket(bucket)
+            .key(&key)
+            .body(ByteStream::from(case_list.into_bytes()))
+            .send()
+            .await?;
+
+        Ok(key)
+    }
+
+    /// S3 オブジェクトを文字列として取得する。UTF-8 として不正なバイトは置換される。
+    pub async fn fetch_object(
+        client: &Client,
+        bucket: &str,
+        key: &str,
+    ) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+        let obj = client.get_object().bucket(bucket).key(key).send().await?;
+        let bytes = obj.body.collect().await?;
+        Ok(String::from_utf8_lossy(&bytes.into_bytes()).to_string())
+    }
+
+    /// 実行 ID から、その実行専用の S3 プレフィックス (`runs/<run_id>/`) を作る。
+    pub fn run_prefix(run_id: &str) -> String {
+        format!("{}/{}/", RUNS_PREFIX, run_id)
+    }
+
+    /// 指定プレフィックス以下のオブジェクトをすべて削除する。
+    ///
+    /// 後片付けの失敗で実行結果を捨てたくないので、呼び出し側ではエラーを
+    /// 警告として扱うこと。
+    pub async fn cleanup_prefix(
+        client: &Client,
+        bucket: &str,
+        prefix: &str,
+    ) -> Result<usize, Box<dyn std::error::Error + Send + Sync>> {
+        let mut deleted = 0;
+        let mut continuation_token: Option<String> = None;
 
-        let batch_client = BatchClient::new(&config);
+        loop {
+            let list_out = client
+                .list_objects_v2()
+                .bucket(bucket)
+                .prefix(prefix)
+                .set_continuation_token(continuation_token.take())
+                .send()
+                .await?;
 
-        let mut results: Vec<ExecutionResult> = Vec::new();
+            // list_objects_v2 は 1 ページ最大 1000 件で、delete_objects の上限と一致する
+            let mut objects = Vec::new();
+            for obj in list_out.contents() {
+                if let Some(key) = obj.key() {
+                    objects.push(ObjectIdentifier::builder().key(key).build()?);
+                }
+            }
 
-        let total = test_cases.len();
+            if !objects.is_empty() {
+                deleted += objects.len();
+                client
+                    .delete_objects()
+                    .bucket(bucket)
+                    .delete(
+                        Delete::builder()
+                            .set_objects(Some(objects))
+                            .quiet(true)
+                            .build()?,
+                    )
+                    .send()
+                    .await?;
+            }
 
-        if total == 0 {
-            return Ok(results);
+            match list_out.next_continuation_token() {
+                Some(token) if list_out.is_truncated().unwrap_or(false) => {
+                    continuation_token = Some(token.to_string());
+                }
+                _ => break,
+            }
         }
 
-        // ----- チャンク分割 -----
-        let chunk_count = std::cmp::min(parallel as usize, total);
-        let chunk_size = (total + chunk_count - 1) / chunk_count; // ceiling div
+        Ok(deleted)
+    }
+
+    /// ケース数ぶんの Batch 配列ジョブを投入し、親ジョブの ID を返す。
+    ///
+    /// 配列ジョブのサイズは 2 以上でなければならないので、1 件のときは通常のジョブにする。
+    /// その場合 `AWS_BATCH_JOB_ARRAY_INDEX` は設定されず、ジョブ側で 0 とみなす。
+    ///
+    /// 失敗した子ジョブは Batch が `attempts` 回まで個別に再投入する (スポットの回収など)。
+    /// ただしコンパイルエラーは何度やっても同じなので再投入しない。
+    async fn submit_array_job(
+        batch_client: &BatchClient,
+        job_queue: &str,
+        job_definition: &str,
+        run_id: &str,
+        size: usize,
+        attempts: u32,
+        env: Vec<(&str, String)>,
+    ) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+        let env_vars = env
+            .into_iter()
+            .map(|(name, value)| KeyValuePair::builder().name(name).value(value).build())
+            .collect();
+
+        let container_overrides = ContainerOverrides::builder()
+            .set_environment(Some(env_vars))
+            .build();
+
+        let array_properties =
+            (size > 1).then(|| ArrayProperties::builder().size(size as i32).build());
+
+        let retry_strategy = RetryStrategy::builder()
+            .attempts(attempts as i32)
+            .evaluate_on_exit(
+                EvaluateOnExit::builder()
+                    .on_exit_code(COMPILE_ERROR_EXIT_CODE)
+                    .action(RetryAction::Exit)
+                    .build()?,
+            )
+            .evaluate_on_exit(
+                EvaluateOnExit::builder()
+                    .on_reason("*")
+                    .action(RetryAction::Retry)
+                    .build()?,
+            )
+            .build();
+
+        let submit_out = batch_client
+            .submit_job()
+            .job_name(format!("aws-runner-{}", run_id))
+            .job_queue(job_queue)
+            .job_definition(job_definition)
+            .set_array_properties(array_properties)
+            .retry_strategy(retry_strategy)
+            .set_container_overrides(Some(container_overrides))
+            .send()
+            .await?;
+
+        match submit_out.job_id() {
+            Some(job_id) => Ok(job_id.to_string()),
+            None => Err("Failed to retrieve job_id on submission".into()),
+        }
+    }
 
-        // job_id -> (start_idx, end_idx)
-        let mut pending: std::collections::HashMap<String, (usize, usize)> =
-            std::collections::HashMap::new();
+    /// 終了済みの子ジョブを列挙する。すでに返したものも含むので、呼び出し側で重複を除くこと。
+    ///
+    /// 親ジョブが終了しているのに一覧に現れない子ジョブ (起動前に親ごと失敗した等) は、
+    /// 親の失敗理由で失敗扱いにする。
+    async fn poll_finished(
+        batch_client: &BatchClient,
+        job_id: &str,
+        size: usize,
+    ) -> Result<Vec<FinishedChild>, Box<dyn std::error::Error + Send + Sync>> {
+        // 子の一覧より先に親を見ることで、親が終了済みなら子の一覧は確定している
+        let describe_out = batch_client.describe_jobs().jobs(job_id).send().await?;
+        let parent = describe_out.jobs().first();
+        let parent_status = parent.and_then(|job| job.status()).cloned();
+        let parent_done = matches!(
+            parent_status,
+            Some(JobStatus::Succeeded) | Some(JobStatus::Failed)
+        );
+        let parent_reason = parent
+            .and_then(|job| job.status_reason())
+            .map(str::to_string);
 
-        for chunk_idx in 0..chunk_count {
-            let start_idx = chunk_idx * chunk_size;
-            if start_idx >= total {
-                break;
+        // 通常のジョブ (ケース 1 件) は親の状態がそのまま結果になる
+        if size <= 1 {
+            if !parent_done {
+                return Ok(Vec::new());
             }
-            let end_idx = std::cmp::min(start_idx + chunk_size - 1, total - 1);
-
-            let seed_start = start_idx as u32; // enumeration indexをseedとする
-            let seed_end = end_idx as u32;
-
-            let env_vars = vec![
-                KeyValuePair::builder()
-                    .name("CODE_BUCKET")
-                    .value(bucket_name.clone())
-                    .build(),
-                KeyValuePair::builder()
-                    .name("CODE_KEY")
-                    .value(key.clone())
-                    .build(),
-                KeyValuePair::builder()
-                    .name("IO_BUCKET")
-                    .value(bucket_name.clone())
-                    .build(),
-                KeyValuePair::builder()
-                    .name("SEED_START")
-                    .value(seed_start.to_string())
-                    .build(),
-                KeyValuePair::builder()
-                    .name("SEED_END")
-                    .value(seed_end.to_string())
-                    .build(),
-            ];
+            return Ok(vec![FinishedChild {
+                index: 0,
+                succeeded: parent_status == Some(JobStatus::Succeeded),
+                status_reason: parent_reason,
+            }]);
+        }
 
-            let container_overrides = ContainerOverrides::builder()
-                .set_environment(Some(env_vars))
-                .build();
+        let mut finished = Vec::new();
+        for status in [JobStatus::Succeeded, JobStatus::Failed] {
+            let mut next_token: Option<String> = None;
+            loop {
+                let list_out = batch_client
+                    .list_jobs()
+                    .array_job_id(job_id)
+                    .job_status(status.clone())
+                    .set_next_token(next_token.take())
+                    .send()
+                    .await?;
 
-            let submit_out = batch_client
-                .submit_job()
-                .job_name(format!("aws-runner-chunk-{}", chunk_idx))
-                .job_queue(job_queue.clone())
-                .job_definition(job_definition.clone())
-                .set_container_overrides(Some(container_overrides))
-                .send()
-                .await?;
+                for summary in list_out.job_summary_list() {
+                    let index = summary.array_properties().and_then(|p| p.index());
+                    if let Some(index) = index {
+                        finished.push(FinishedChild {
+                            index: index as usize,
+                            succeeded: status == JobStatus::Succeeded,
+                            status_reason: summary.status_reason().map(str::to_string),
+                        });
+                    }
+                }
+
+                match list_out.next_token() {
+                    Some(token) => next_token = Some(token.to_string()),
+                    None => break,
+                }
+            }
+        }
 
-            if let Some(job_id) = submit_out.job_id() {
-                pending.insert(job_id.to_string(), (start_idx, end_idx));
+        if parent_done {
+            let listed: HashSet<usize> = finished.iter().map(|c| c.index).collect();
+            for index in (0..size).filter(|i| !listed.contains(i)) {
+                finished.push(FinishedChild {
+                    index,
+                    succeeded: false,
+                    status_reason: parent_reason.clone(),
+                });
+            }
+        }
+
+        Ok(finished)
+    }
+
+    /// 終了した子ジョブ 1 件分の stdout / stderr を S3 から取得して結果にまとめる。
+    ///
+    /// ケースの成否はジョブの成否ではなく、ジョブ側が書き出した終了コードで判定する。
+    /// そのためアップロード後にジョブが落ちた場合でも、実行済みのケースは自分の結果を持つ。
+    async fn fetch_case_result(
+        client: &Client,
+        bucket_name: &str,
+        keys: &CaseKeys,
+        test_case_id: u32,
+        repeat: u32,
+        child: FinishedChild,
+    ) -> ExecutionResult {
+        let exit_code = Self::fetch_object(client, bucket_name, &keys.status)
+            .await
+            .ok()
+            .and_then(|s| s.trim().parse::<i32>().ok());
+
+        let mut stdout_data = String::new();
+        let mut success = exit_code.map_or(child.succeeded, |code| code == 0);
+        let mut stderr_data = String::new();
+
+        if exit_code.is_some() || child.succeeded {
+            // fetch stdout
+            match Self::fetch_object(client, bucket_name, &keys.output).await {
+                Ok(data) => stdout_data = data,
+                Err(e) => {
+                    success = false;
+                    stderr_data = format!("Failed to fetch output from S3: {}", e);
+                }
+            }
+
+            // fetch stderr file if exists
+            if let Ok(data) = Self::fetch_object(client, bucket_name, &keys.error).await {
+                stderr_data = data;
+            }
+        } else {
+            // even on failure try to fetch stderr file
+            if let Ok(data) = Self::fetch_object(client, bucket_name, &keys.error).await {
+                stderr_data = data;
             } else {
-                return Err("Failed to retrieve job_id on submission".into());
+                stderr_data = child
+                    .status_reason
+                    .unwrap_or_else(|| "Job failed".to_string());
             }
         }
 
-        // ----- ジョブ完了待ち & 結果収集 -----
-        while !pending.is_empty() {
-            let job_ids: Vec<String> = pending.keys().cloned().collect();
+        // スコアと実行時間をパース
+        let (score, execution_time_ms) = crate::extract_heurs_markers!(&stderr_data);
 
-            let describe_out = batch_client
-                .describe_jobs()
-                .set_jobs(Some(job_ids.clone()))
-                .send()
-                .await?;
+        ExecutionResult {
+            test_case_id,
+            repeat,
+            success,
+            stdout: stdout_data,
+            stderr: stderr_data,
+            execution_time_ms,
+            score,
+        }
+    }
 
-            for job in describe_out.jobs() {
-                if let Some(status_ref) = job.status() {
-                    let status = status_ref.clone();
-                    if status == JobStatus::Succeeded || status == JobStatus::Failed {
-                        let job_id = job.job_id().unwrap_or_default();
-                        let (start_idx, end_idx) = match pending.remove(job_id) {
-                            Some(v) => v,
-                            None => continue,
-                        };
-
-                        // 各 seed / test case を処理
-                        for idx in start_idx..=end_idx {
-                            let tc = &test_cases[idx];
-                            let seed_num = idx as u32;
-                            let output_key = format!("outputs/output_{}.txt", seed_num);
-                            let error_key = format!("errors/error_{}.txt", seed_num);
-
-                            let mut stdout_data = String::new();
-                            let mut success = status == JobStatus::Succeeded;
-                            let mut stderr_data = String::new();
-
-                            if success {
-                                // fetch stdout
-                                match client
-                                    .get_object()
-                                    .bucket(&bucket_name)
-                                    .key(&output_key)
-                                    .send()
-                                    .await
-                                {
-                                    Ok(obj) => {
-                                        let bytes = obj.body.collect().await?;
-                                        stdout_data = String::from_utf8_lossy(&bytes.into_bytes())
-                                            .to_string();
-                                    }
-                                    Err(e) => {
-                                        success = false;
-                                        stderr_data =
-                                            format!("Failed to fetch output from S3: {}", e);
-                                    }
-                                }
-
-                                // fetch stderr file if exists
-                                if let Ok(err_obj) = client
-                                    .get_object()
-                                    .bucket(&bucket_name)
-                                    .key(&error_key)
-                                    .send()
-                                    .await
-                                {
-                                    let err_bytes = err_obj.body.collect().await?;
-                                    stderr_data = String::from_utf8_lossy(&err_bytes.into_bytes())
-                                        .to_string();
-                                }
-                            } else {
-                                // even on failure try to fetch stderr file
-                                if let Ok(err_obj) = client
-                                    .get_object()
-                                    .bucket(&bucket_name)
-                                    .key(&error_key)
-                                    .send()
-                                    .await
-                                {
-                                    let err_bytes = err_obj.body.collect().await?;
-                                    stderr_data = String::from_utf8_lossy(&err_bytes.into_bytes())
-                                        .to_string();
-                                } else {
-                                    stderr_data =
-                                        job.status_reason().unwrap_or("Job failed").to_string();
-                                }
-                            }
+    /// 打ち切った実行のジョブを止める。配列ジョブの親を指定すれば、待機中の子ジョブは
+    /// 取り消され、実行中の子ジョブは終了させられる。
+    async fn terminate_job(
+        batch_client: &BatchClient,
+        job_id: &str,
+        reason: &str,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        batch_client
+            .terminate_job()
+            .job_id(job_id)
+            .reason(reason)
+            .send()
+            .await?;
+        Ok(())
+    }
+
+    /// ダウンロードタスクの結果を取り出す。タスクが panic した場合は、実行全体を
+    /// 止めずにそのケースだけを失敗として記録する。
+    fn joined_result(
+        joined: Result<(TaskId, ExecutionResult), JoinError>,
+        pending: &mut HashMap<TaskId, (u32, u32)>,
+    ) -> ExecutionResult {
+        match joined {
+            Ok((id, result)) => {
+                pending.remove(&id);
+                result
+            }
+            Err(e) => {
+                let (test_case_id, repeat) = pending
+                    .remove(&e.id())
+                    .expect("every download task is tracked");
+                ExecutionResult {
+                    test_case_id,
+                    repeat,
+                    success: false,
+                    stdout: String::new(),
+                    stderr: format!("Failed to collect result: {}", e),
+                    execution_time_ms: 0,
+                    score: 0,
+                }
+            }
+        }
+    }
+
+    /// 1 ケース分の結果を進捗チャネルへ流し、手元の一覧にも積む。
+    fn emit(
+        result: ExecutionResult,
+        progress: &Option<ProgressSender>,
+        results: &mut Vec<ExecutionResult>,
+    ) {
+        if let Some(progress) = progress {
+            let _ = progress.send(result.clone());
+        }
+        results.push(result);
+    }
+}
+
+#[async_trait]
+impl Runner for AWSRunner {
+    async fn execute_with_progress(
+        &self,
+        source: &Source,
+        _compile_cmd: &str,
+        _exec_cmd: &str,
+        _parallel: u32,
+        test_cases: Vec<TestCaseModel>,
+        repeat: u32,
+        _timeout: u32,
+        progress: Option<ProgressSender>,
+    ) -> Result<Vec<ExecutionResult>, Box<dyn std::error::Error + Send + Sync>> {
+        let repeat = repeat.max(1) as usize;
+        let total = test_cases.len() * repeat;
+        let mut results: Vec<ExecutionResult> = Vec::with_capacity(total);
+
+        if total == 0 {
+            return Ok(results);
+        }
+        // 子ジョブ 1 件 = 1 回の実行なので、上限を超える場合はジョブを投入する前に断る。
+        // 1 件だけのとき (配列ジョブは 2 件以上が必要) は submit_array_job が通常のジョブにする
+        if total > MAX_ARRAY_SIZE {
+            return Err(format!(
+                "{} cases x {} repeats = {} runs exceeds the AWS Batch array size limit of {}",
+                test_cases.len(),
+                repeat,
+                total,
+                MAX_ARRAY_SIZE
+            )
+            .into());
+        }
+
+        // ---- AWS SDK 初期化 ----
+        let client = self.s3_client().await;
+        let batch_client = self.batch_client().await;
+
+        // ---- バケット名決定 ----
+        let bucket_name = self.bucket();
 
-                            // スコアと実行時間をパース
-                            let (score, execution_time_ms) =
-                                crate::extract_heurs_markers!(&stderr_data);
-
-                            results.push(ExecutionResult {
-                                test_case_id: tc.id as u32,
-                                success,
-                                stdout: stdout_data,
-                                stderr: stderr_data,
-                                execution_time_ms,
-                                score,
-                            });
+        // ---- 実行ごとの名前空間決定 ----
+        // ソース・出力・エラーはすべて `runs/<run_id>/` 以下に置き、
+        // 同時に走る他の実行や過去の実行の残骸を読まないようにする
+        let run_id = Uuid::new_v4().to_string();
+        let run_prefix = Self::run_prefix(&run_id);
+        let output_prefix = format!("{}outputs/output_", run_prefix);
+        let error_prefix = format!("{}errors/error_", run_prefix);
+        let status_prefix = format!("{}status/status_", run_prefix);
+        let compile_error_key = format!("{}{}", run_prefix, COMPILE_ERROR_NAME);
+
+        // ---- 後片付けの予約 ----
+        // 成功したときだけでなく、アップロードや投入に失敗したときや
+        // コンパイルエラーで打ち切ったときも、この実行の名前空間を残さない
+        let cleanup = PrefixCleanup::new(&client, &bucket_name, &run_prefix);
+
+        let outcome: Result<(), Box<dyn std::error::Error + Send + Sync>> = async {
+            // ---- ソースと入力のアップロード ----
+            let key = Self::upload_source(&client, &bucket_name, &run_prefix, source).await?;
+            let case_list_key = Self::upload_inputs(
+                &client,
+                &bucket_name,
+                &run_prefix,
+                &test_cases,
+                self.download_concurrency(),
+            )
+            .await?;
+            let mut env = vec![
+                ("CODE_BUCKET", bucket_name.clone()),
+                ("CODE_KEY", key),
+                ("IO_BUCKET", bucket_name.clone()),
+                ("CASE_LIST_KEY", case_list_key),
+                ("INPUT_PREFIX", format!("{}inputs/", run_prefix)),
+                ("REPEAT", repeat.to_string()),
+                ("RUN_ID", run_id.clone()),
+                ("OUTPUT_PREFIX", output_prefix.clone()),
+                ("ERROR_PREFIX", error_prefix.clone()),
+                ("STATUS_PREFIX", status_prefix.clone()),
+                ("COMPILE_ERROR_KEY", compile_error_key.clone()),
+            ];
+            // ジョブ側はバンドルを展開し、エントリポイントをコンパイルする
+            if let Source::Bundle(bundle) = source {
+                env.push(("CODE_ENTRY", bundle.entry().to_string()));
+            }
+
+            // ---- Batch 配列ジョブ送信 ----
+            // 子ジョブ i がケース一覧の i / repeat 行目 (= test_cases[i / repeat]) を
+            // HEURS_RNG_SEED = i % repeat で 1 回だけ実行する
+            let job_id = Self::submit_array_job(
+                &batch_client,
+                &self.job_queue(),
+                &self.job_definition(),
+                &run_id,
+                total,
+                self.job_attempts(),
+                env,
+            )
+            .await?;
+
+            // ----- 子ジョブの完了待ち & 結果収集 -----
+            // 終わった子ジョブから順に、同時実行数を絞って S3 から結果を取ってくる
+            let semaphore = Arc::new(Semaphore::new(self.download_concurrency()));
+            let mut downloads: JoinSet<ExecutionResult> = JoinSet::new();
+            // タスクが panic しても、どのケースの結果だったかわかるようにしておく
+            let mut pending: HashMap<TaskId, (u32, u32)> = HashMap::new();
+            let mut seen: HashSet<usize> = HashSet::new();
+            let mut poll_failures = 0;
+
+            loop {
+                // 状態確認に失敗しても、回収済みの結果を捨てずに次の確認で取り返す
+                let finished = match Self::poll_finished(&batch_client, &job_id, total).await {
+                    Ok(finished) => {
+                        poll_failures = 0;
+                        finished
+                    }
+                    Err(e) => {
+                        poll_failures += 1;
+                        eprintln!(
+                            "Failed to poll Batch job {} ({}/{}): {}",
+                            job_id, poll_failures, MAX_CONSECUTIVE_POLL_FAILURES, e
+                        );
+                        if poll_failures < MAX_CONSECUTIVE_POLL_FAILURES {
+                            Vec::new()
+                        } else {
+                            // 残りの子ジョブが動き続けないように止めてから、
+                            // 見失ったケースだけを失敗扱いにして打ち切る
+                            if let Err(e) = Self::terminate_job(
+                                &batch_client,
+                                &job_id,
+                                "heurs lost track of the job",
+                            )
+                            .await
+                            {
+                                eprintln!("Failed to terminate Batch job {}: {}", job_id, e);
+                            }
+                            (0..total)
+                                .filter(|i| !seen.contains(i))
+                                .map(|index| FinishedChild {
+                                    index,
+                                    succeeded: false,
+                                    status_reason: Some(format!("Lost track of Batch job: {}", e)),
+                                })
+                                .collect()
                         }
                     }
+                };
+
+                // コンパイルエラーはどの子ジョブでも同じなので、1 件見つけたら全体を止めて
+                // LocalRunner と同じくエラーとして返す
+                let new_failure = finished
+                    .iter()
+                    .any(|child| !child.succeeded && !seen.contains(&child.index));
+                if new_failure
+                    && let Ok(compile_error) =
+                        Self::fetch_object(&client, &bucket_name, &compile_error_key).await
+                {
+                    if let Err(e) =
+                        Self::terminate_job(&batch_client, &job_id, "compilation failed").await
+                    {
+                        eprintln!("Failed to terminate Batch job {}: {}", job_id, e);
+                    }
+                    return Err(format!("Compilation failed:\n{}", compile_error).into());
+                }
+
+                for child in finished {
+                    if child.index >= total || !seen.insert(child.index) {
+                        continue;
+                    }
+
+                    let semaphore = semaphore.clone();
+                    let client = client.clone();
+                    let bucket_name = bucket_name.clone();
+                    let (case_index, repeat_index) = (child.index / repeat, child.index % repeat);
+                    let test_case_id = test_cases[case_index].id as u32;
+                    let result_id = format!("{}_{}", test_case_id, repeat_index);
+                    let keys = CaseKeys {
+                        output: format!("{}{}.txt", output_prefix, result_id),
+                        error: format!("{}{}.txt", error_prefix, result_id),
+                        status: format!("{}{}.txt", status_prefix, result_id),
+                    };
+
+                    let handle = downloads.spawn(async move {
+                        let _permit = semaphore.acquire_owned().await.expect("semaphore closed");
+                        Self::fetch_case_result(
+                            &client,
+                            &bucket_name,
+                            &keys,
+                            test_case_id,
+                            repeat_index as u32,
+                            child,
+                        )
+                        .await
+                    });
+                    pending.insert(handle.id(), (test_case_id, repeat_index as u32));
                 }
+
+                // ダウンロードが終わったものから順に流す
+                while let Some(joined) = downloads.try_join_next_with_id() {
+                    let result = Self::joined_result(joined, &mut pending);
+                    Self::emit(result, &progress, &mut results);
+                }
+
+                if seen.len() >= total {
+                    break;
+                }
+                sleep(POLL_INTERVAL).await;
             }
 
-            // まだ未完了ジョブが残っていればスリープして次の describe へ
-            if !pending.is_empty() {
-                sleep(Duration::from_secs(5)).await;
+            while let Some(joined) = downloads.join_next_with_id().await {
+                let result = Self::joined_result(joined, &mut pending);
+                Self::emit(result, &progress, &mut results);
             }
+
+            Ok(())
         }
+        .await;
+
+        // ----- 後片付け -----
+        cleanup.finish().await;
 
-        Ok(results)
+        outcome.map(|()| results)
     }
 }
@@ -0,0 +1,187 @@
+use crate::config::Container as ContainerConfig;
+use crate::runner::{ExecutionResult, LocalRunner, ProgressSender, Runner, Source, Workspace};
+use async_trait::async_trait;
+use heurs_database::TestCaseModel;
+use std::os::unix::fs::MetadataExt;
+use std::path::Path;
+
+/// コンテナ内でワークスペースをマウントするパス
+const CONTAINER_WORKDIR: &str = "/work";
+
+/// ホストの値をそのままコンテナへ渡す環境変数
+const PASSTHROUGH_ENV: &[&str] = &["HEURS_RNG_SEED", "HEURS_SPEED_FACTOR"];
+
+/// Docker / Podman のコンテナ内でコンパイル・実行する実行器。
+///
+/// 実行ごとにホスト側へワークスペースを作ってソースをコピー (バンドルなら展開) し、それを
+/// `/work` にマウントしたコンテナでコンパイルと各ケースの実行を行う。
+/// コマンドをコンテナ起動のコマンドに包んで `LocalRunner` に渡すため、
+/// 並列実行や結果の集計はローカル実行と同じ振る舞いになる。
+pub struct ContainerRunner {
+    config: ContainerConfig,
+}
+
+impl ContainerRunner {
+    pub fn new(config: ContainerConfig) -> Self {
+        ContainerRunner { config }
+    }
+
+    /// `cmd` をワークスペースをマウントしたコンテナ内で実行するシェルコマンドに変換する
+    fn wrap(&self, workspace: &Path, cmd: &str, interactive: bool) -> String {
+        let mut args = vec![
+            self.config.runtime.clone(),
+            "run".to_string(),
+            "--rm".to_string(),
+        ];
+        if interactive {
+            args.push("-i".to_string());
+        }
+        if !self.config.network {
+            args.push("--network=none".to_string());
+        }
+        if let Some(cpus) = self.config.cpus {
+            args.push(format!("--cpus={}", cpus));
+        }
+        if let Some(memory) = &self.config.memory {
+            args.push(format!("--memory={}", memory));
+        }
+        // Docker はコンテナ内の root で書いたファイルがホストに root 所有で残るため、
+        // ワークスペースの所有者で実行する (rootless Podman は自動で対応付けられる)
+        if self.is_docker()
+            && let Ok(meta) = std::fs::metadata(workspace)
+        {
+            args.push(format!("--user={}:{}", meta.uid(), meta.gid()));
+        }
+        args.push(format!(
+            "--volume={}:{}",
+            workspace.display(),
+            CONTAINER_WORKDIR
+        ));
+        args.push(format!("--workdir={}", CONTAINER_WORKDIR));
+        // LocalRunner がホスト側で設定する実行ごとの値をコンテナ内へ引き継ぐ
+        for name in PASSTHROUGH_ENV {
+            args.push(format!("--env={}", name));
+        }
+        // ジャッジ用イメージは ENTRYPOINT を持つことがあるので上書きする
+        args.push("--entrypoint=sh".to_string());
+        args.extend(self.config.extra_args.iter().cloned());
+        args.push(self.config.image.clone());
+        args.push("-c".to_string());
+        args.push(cmd.to_string());
+
+        args.iter()
+            .map(|arg| shell_quote(arg))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    fn is_docker(&self) -> bool {
+        Path::new(&self.config.runtime)
+            .file_name()
+            .and_then(|s| s.to_str())
+            .is_some_and(|name| name == "docker")
+    }
+}
+
+/// `sh -c` に渡しても 1 引数のまま解釈されるようにクォートする
+fn shell_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', r"'\''"))
+}
+
+#[async_trait]
+impl Runner for ContainerRunner {
+    async fn execute_with_progress(
+        &self,
+        source: &Source,
+        compile_cmd: &str,
+        exec_cmd: &str,
+        parallel: u32,
+        test_cases: Vec<TestCaseModel>,
+        repeat: u32,
+        timeout: u32,
+        progress: Option<ProgressSender>,
+    ) -> Result<Vec<ExecutionResult>, Box<dyn std::error::Error + Send + Sync>> {
+        // 実行ごとのワークスペースを用意してソースを置く
+        let workspace = Workspace::create("heurs-container")?;
+        let workspace_source = source.place(workspace.path())?;
+
+        // プレースホルダはコンテナ内のパスに置き換えてから包む
+        let container_source = format!(
+            "{}/{}",
+            CONTAINER_WORKDIR,
+            workspace_source
+                .strip_prefix(workspace.path())?
+                .to_string_lossy()
+        );
+        let compile_cmd = self.wrap(
+            workspace.path(),
+            &compile_cmd.replace("{{src}}", &container_source),
+            false,
+        );
+        let exec_cmd = self.wrap(workspace.path(), exec_cmd, true);
+
+        LocalRunner::new()
+            .execute_with_progress(
+                &Source::File(workspace_source),
+                &compile_cmd,
+                &exec_cmd,
+                parallel,
+                test_cases,
+                repeat,
+                timeout,
+                progress,
+            )
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shell_quote_round_trips_through_sh() {
+        for arg in ["plain", "with space", "it's", "$HOME `id` \"q\"", "'", ""] {
+            let output = std::process::Command::new("sh")
+                .arg("-c")
+                .arg(format!("printf %s {}", shell_quote(arg)))
+                .output()
+                .unwrap();
+            assert_eq!(String::from_utf8(output.stdout).unwrap(), arg);
+        }
+    }
+
+    #[test]
+    fn wrap_applies_limits_and_overrides_the_entrypoint() {
+        let runner = ContainerRunner::new(ContainerConfig {
+            runtime: "podman".to_string(),
+            image: "judge:latest".to_string(),
+            cpus: Some(1.5),
+            memory: Some("1g".to_string()),
+            network: false,
+            extra_args: vec!["--pids-limit=64".to_string()],
+        });
+        assert_eq!(
+            runner.wrap(Path::new("/tmp/ws"), "./a.out < in.txt", true),
+            "'podman' 'run' '--rm' '-i' '--network=none' '--cpus=1.5' '--memory=1g' \
+             '--volume=/tmp/ws:/work' '--workdir=/work' '--env=HEURS_RNG_SEED' \
+             '--env=HEURS_SPEED_FACTOR' '--entrypoint=sh' '--pids-limit=64' 'judge:latest' \
+             '-c' './a.out < in.txt'"
+        );
+    }
+
+    #[test]
+    fn wrap_runs_docker_as_the_workspace_owner() {
+        let workspace = std::env::temp_dir();
+        let meta = std::fs::metadata(&workspace).unwrap();
+        let runner = ContainerRunner::new(ContainerConfig {
+            runtime: "/usr/bin/docker".to_string(),
+            network: true,
+            ..Default::default()
+        });
+        let wrapped = runner.wrap(&workspace, "true", false);
+        assert!(wrapped.contains(&format!("'--user={}:{}'", meta.uid(), meta.gid())));
+        assert!(!wrapped.contains("--network"));
+        assert!(!wrapped.contains("'-i'"));
+    }
+}
@@ -1,54 +1,176 @@
-use crate::runner::{ExecutionResult, Runner};
+use crate::config::{Config, Cpu as CpuConfig, Sandbox as SandboxConfig};
+use crate::runner::affinity::{self, CorePool};
+use crate::runner::sandbox::Sandbox;
+use crate::runner::{ExecutionResult, ProgressSender, Runner, Source, Workspace};
 use async_trait::async_trait;
 use heurs_database::TestCaseModel;
 use std::io::Write;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::process::{Command, Stdio};
-use std::sync::mpsc;
+use std::sync::Arc;
 use threadpool::ThreadPool;
+use tokio::sync::mpsc;
 
 /// ローカル実行器の実装
-pub struct LocalRunner;
+pub struct LocalRunner {
+    sandbox: Option<SandboxConfig>,
+    cpu: CpuConfig,
+}
 
 impl LocalRunner {
     pub fn new() -> Self {
-        LocalRunner
+        LocalRunner {
+            sandbox: None,
+            cpu: CpuConfig::default(),
+        }
+    }
+
+    /// 設定ファイルの `[sandbox]` と `[cpu]` を反映した実行器を作る。
+    /// `[sandbox]` の `enabled` が true なら、コンパイルと実行をサンドボックス内で行う。
+    pub fn from_config(config: &Config) -> Self {
+        LocalRunner {
+            sandbox: config.sandbox.enabled.then(|| config.sandbox.clone()),
+            cpu: config.cpu.clone(),
+        }
+    }
+}
+
+impl Default for LocalRunner {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
+/// `sh -c` でコマンドを起動する `Command` を作る。
+/// `work_dir` があればそこで、`core` が指定されていればそのコアに固定して、
+/// サンドボックスが有効ならその中で動かす。
+fn shell_command(
+    cmd: &str,
+    work_dir: Option<&Path>,
+    sandbox: Option<&Arc<Sandbox>>,
+    core: Option<usize>,
+) -> Command {
+    let mut command = Command::new("sh");
+    command.arg("-c").arg(cmd);
+    if let Some(work_dir) = work_dir {
+        command.current_dir(work_dir);
+    }
+    // サンドボックス内で fork した子にも引き継がれるよう、先に固定する
+    if let Some(core) = core {
+        affinity::pin_command(&mut command, core);
+    }
+    if let Some(sandbox) = sandbox {
+        sandbox.apply(&mut command);
+    }
+    command
+}
+
 #[async_trait]
 impl Runner for LocalRunner {
-    async fn execute(
+    async fn execute_with_progress(
         &self,
-        source_path: &Path,
+        source: &Source,
         compile_cmd: &str,
         exec_cmd: &str,
         parallel: u32,
         test_cases: Vec<TestCaseModel>,
+        repeat: u32,
         _timeout: u32,
+        progress: Option<ProgressSender>,
     ) -> Result<Vec<ExecutionResult>, Box<dyn std::error::Error + Send + Sync>> {
+        // サンドボックスではスクラッチにソースを置き、そこでコンパイル・実行する
+        let sandbox = match &self.sandbox {
+            Some(config) => Some(Arc::new(Sandbox::prepare(config)?)),
+            None => None,
+        };
+        // バンドルはサンドボックスがなくても一時ワークスペースに展開し、そこでコンパイル・実行する。
+        // ワークスペースは実行が終わるまで保持し、drop で消す
+        let (_workspace, work_dir, source_path): (Option<Workspace>, Option<PathBuf>, PathBuf) =
+            match (&sandbox, source) {
+                (Some(sandbox), _) => (
+                    None,
+                    Some(sandbox.scratch().to_path_buf()),
+                    source.place(sandbox.scratch())?,
+                ),
+                (None, Source::File(path)) => (None, None, path.clone()),
+                (None, Source::Bundle(bundle)) => {
+                    let workspace = Workspace::create("heurs-bundle")?;
+                    let entry = bundle.unpack(workspace.path())?;
+                    let dir = workspace.path().to_path_buf();
+                    (Some(workspace), Some(dir), entry)
+                }
+            };
+
         // プレースホルダ置換
         let compile_cmd = compile_cmd.replace("{{src}}", &source_path.display().to_string());
 
         // コンパイルを実行
-        let status = Command::new("sh").arg("-c").arg(compile_cmd).status()?;
+        let status = tokio::process::Command::from(shell_command(
+            &compile_cmd,
+            work_dir.as_deref(),
+            sandbox.as_ref(),
+            None,
+        ))
+        .status()
+        .await?;
         if !status.success() {
             return Err("Compilation failed".into());
         }
 
-        let pool = ThreadPool::new(parallel as usize);
-        let (tx, rx) = mpsc::channel();
+        // コア固定時は 1 ケース 1 コアになるよう並列数をコア数で頭打ちにする
+        let mut parallel = parallel.max(1) as usize;
+        let cores = if self.cpu.pin {
+            let cores = affinity::available_cores(&self.cpu)?;
+            if parallel > cores.len() {
+                eprintln!(
+                    "Parallelism reduced from {} to {} to give each case a dedicated core",
+                    parallel,
+                    cores.len()
+                );
+                parallel = cores.len();
+            }
+            Some(Arc::new(CorePool::new(cores)))
+        } else {
+            None
+        };
+        let speed_factor = self.cpu.speed_factor.map(|factor| factor.to_string());
+
+        let pool = ThreadPool::new(parallel);
+        // 子プロセスの完了はプールのスレッドで待ち、こちらは非同期に受け取るだけにして
+        // 実行中もランタイムのスレッドを塞がないようにする
+        let (tx, mut rx) = mpsc::unbounded_channel();
 
-        for test_case in test_cases {
+        // 各ケースを repeat 回ずつ、回ごとに異なる HEURS_RNG_SEED で実行する
+        let jobs = test_cases
+            .into_iter()
+            .flat_map(|test_case| (0..repeat.max(1)).map(move |r| (test_case.clone(), r)));
+
+        for (test_case, repeat_index) in jobs {
             let tx = tx.clone();
             let exec_cmd = exec_cmd.to_string();
+            let sandbox = sandbox.clone();
+            let work_dir = work_dir.clone();
+            let cores = cores.clone();
+            let speed_factor = speed_factor.clone();
 
             pool.execute(move || {
                 let input = test_case.input;
+                // 子プロセスが終わるまでコアを借りたままにする
+                let lease = cores.as_ref().and_then(|cores| cores.acquire());
+
+                let mut command = shell_command(
+                    &exec_cmd,
+                    work_dir.as_deref(),
+                    sandbox.as_ref(),
+                    lease.as_ref().map(|l| l.core),
+                );
+                command.env("HEURS_RNG_SEED", repeat_index.to_string());
+                // 時間制限付きの焼きなましなどが持ち時間を調整できるよう速度係数を渡す
+                if let Some(speed_factor) = &speed_factor {
+                    command.env("HEURS_SPEED_FACTOR", speed_factor);
+                }
 
-                let mut child = match Command::new("sh")
-                    .arg("-c")
-                    .arg(&exec_cmd)
+                let mut child = match command
                     .stdin(Stdio::piped())
                     .stdout(Stdio::piped())
                     .stderr(Stdio::piped())
@@ -59,13 +181,14 @@ impl Runner for LocalRunner {
                         eprintln!("Error spawning process: {}", e);
                         let result = ExecutionResult {
                             test_case_id: test_case.id as u32,
+                            repeat: repeat_index,
                             success: false,
                             stdout: String::new(),
                             stderr: format!("Error spawning process: {}", e),
                             execution_time_ms: 0,
                             score: 0,
                         };
-                        tx.send(result).unwrap();
+                        let _ = tx.send(result);
                         return;
                     }
                 };
@@ -87,6 +210,7 @@ impl Runner for LocalRunner {
 
                 let result = ExecutionResult {
                     test_case_id: test_case.id as u32,
+                    repeat: repeat_index,
                     success,
                     stdout,
                     stderr,
@@ -94,7 +218,7 @@ impl Runner for LocalRunner {
                     score,
                 };
 
-                tx.send(result).unwrap();
+                let _ = tx.send(result);
             });
         }
 
@@ -102,10 +226,49 @@ impl Runner for LocalRunner {
 
         // 全ケースの結果を回収
         let mut results = Vec::new();
-        for result in rx.iter() {
+        while let Some(result) = rx.recv().await {
+            if let Some(progress) = &progress {
+                let _ = progress.send(result.clone());
+            }
             results.push(result);
         }
 
         Ok(results)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, Instant};
+
+    #[tokio::test]
+    async fn execution_does_not_block_the_runtime() {
+        let runner = LocalRunner::new();
+        let source = Source::File(PathBuf::from("main.cpp"));
+        let test_case = TestCaseModel {
+            id: 1,
+            problem_id: 0,
+            content_hash: String::new(),
+            input: String::new(),
+            filename: "sleep.txt".to_string(),
+            created_at: chrono::Utc::now(),
+            seed: None,
+            tags: String::new(),
+            best_known_score: None,
+        };
+
+        // シングルスレッドのランタイムなので、実行中に結果待ちでスレッドを塞ぐと
+        // 並行するタイマーは実行が終わるまで進まない
+        let execution = runner.execute(&source, "true", "sleep 1", 1, vec![test_case], 1, 10);
+        let ticker = async {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            Instant::now()
+        };
+        let (results, ticked) = tokio::join!(execution, ticker);
+        let finished = Instant::now();
+
+        assert!(results.unwrap()[0].success);
+        assert!(finished.duration_since(ticked) > Duration::from_millis(500));
+    }
+}
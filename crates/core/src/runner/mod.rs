@@ -1,17 +1,90 @@
+pub(crate) mod affinity;
 pub mod aws;
+pub mod container;
 pub mod local;
+pub mod remote;
+mod sandbox;
 
+use crate::bundle::Bundle;
 use async_trait::async_trait;
 use heurs_database::TestCaseModel;
-use std::path::Path;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
 
 pub use aws::AWSRunner;
+pub use container::ContainerRunner;
 pub use local::LocalRunner;
+pub use remote::RemoteRunner;
 
-/// 実行結果を表す構造体
+/// 実行する提出のソース
 #[derive(Debug, Clone)]
+pub enum Source {
+    /// 1 ファイルのソース
+    File(PathBuf),
+    /// 複数ファイルのバンドル。実行器がワークスペースに展開してからコンパイルする
+    Bundle(Bundle),
+}
+
+impl Source {
+    /// `{{src}}` に入るファイルの名前 (バンドルならエントリポイントの相対パス)
+    pub fn name(&self) -> String {
+        match self {
+            Source::File(path) => path
+                .file_name()
+                .map(|n| n.to_string_lossy().into_owned())
+                .unwrap_or_else(|| "source".to_string()),
+            Source::Bundle(bundle) => bundle.entry().to_string(),
+        }
+    }
+
+    /// `dir` にソースを置き、置いたファイル (バンドルならエントリポイント) のパスを返す
+    pub fn place(&self, dir: &Path) -> Result<PathBuf, Box<dyn std::error::Error + Send + Sync>> {
+        match self {
+            Source::File(path) => {
+                let file_name = path.file_name().ok_or("Source path has no file name")?;
+                let placed = dir.join(file_name);
+                std::fs::copy(path, &placed)?;
+                Ok(placed)
+            }
+            Source::Bundle(bundle) => bundle.unpack(dir),
+        }
+    }
+}
+
+/// 実行ごとに一時ディレクトリに作るワークスペース。drop で削除する
+pub struct Workspace {
+    path: PathBuf,
+}
+
+impl Workspace {
+    pub fn create(prefix: &str) -> std::io::Result<Self> {
+        let path = std::env::temp_dir().join(format!("{}-{}", prefix, uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&path)?;
+        Ok(Workspace {
+            path: path.canonicalize()?,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for Workspace {
+    fn drop(&mut self) {
+        if let Err(e) = std::fs::remove_dir_all(&self.path) {
+            eprintln!("Failed to remove workspace {}: {}", self.path.display(), e);
+        }
+    }
+}
+
+/// 実行結果を表す構造体
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ExecutionResult {
     pub test_case_id: u32,
+    /// 同じケースを繰り返し実行したときの何回目か (0 始まり)。`HEURS_RNG_SEED` にも同じ値が渡る
+    #[serde(default)]
+    pub repeat: u32,
     pub success: bool,
     pub stdout: String,
     pub stderr: String,
@@ -19,29 +92,82 @@ pub struct ExecutionResult {
     pub score: i64,
 }
 
-impl From<&heurs_database::ExecutionResultModel> for ExecutionResult {
-    fn from(model: &heurs_database::ExecutionResultModel) -> Self {
+/// 保存用の変換 (`SubmissionRepository::create_with_results` に渡す)
+impl From<ExecutionResult> for heurs_database::NewExecutionResult {
+    fn from(result: ExecutionResult) -> Self {
+        heurs_database::NewExecutionResult {
+            test_case_id: result.test_case_id as i32,
+            repeat_index: result.repeat,
+            success: result.success,
+            stdout: result.stdout,
+            stderr: result.stderr,
+            score: result.score,
+            execution_time_ms: result.execution_time_ms,
+        }
+    }
+}
+
+/// 集計用の変換。stdout / stderr は読み込まないので空になる
+impl From<&heurs_database::ExecutionResultSummary> for ExecutionResult {
+    fn from(summary: &heurs_database::ExecutionResultSummary) -> Self {
         ExecutionResult {
-            test_case_id: model.test_case_id as u32,
-            success: model.success,
-            stdout: model.stdout.clone(),
-            stderr: model.stderr.clone(),
-            execution_time_ms: model.execution_time_ms as u32,
-            score: model.score,
+            test_case_id: summary.test_case_id as u32,
+            repeat: summary.repeat_index as u32,
+            success: summary.success,
+            stdout: String::new(),
+            stderr: String::new(),
+            execution_time_ms: summary.execution_time_ms as u32,
+            score: summary.score,
         }
     }
 }
 
+/// 実行中の結果を 1 ケースずつ受け取るための送信口
+///
+/// Runner はケースが終わるたびに結果を送る。受信側が閉じていても実行は続ける。
+pub type ProgressSender = tokio::sync::mpsc::UnboundedSender<ExecutionResult>;
+
 /// コマンド実行器のトレイト
 #[async_trait]
-pub trait Runner {
-    async fn execute(
+pub trait Runner: Send + Sync {
+    /// 全ケースを `repeat` 回ずつ実行し、終わったものから順に `progress` へ結果を流す。
+    /// 各回には環境変数 `HEURS_RNG_SEED` として回数 (0 始まり) を渡す。
+    /// 戻り値には全ケースの結果がまとめて入る。
+    #[allow(clippy::too_many_arguments)]
+    async fn execute_with_progress(
         &self,
-        source_path: &Path,
+        source: &Source,
         compile_cmd: &str,
         exec_cmd: &str,
         parallel: u32,
         test_cases: Vec<TestCaseModel>,
+        repeat: u32,
         timeout: u32,
+        progress: Option<ProgressSender>,
     ) -> Result<Vec<ExecutionResult>, Box<dyn std::error::Error + Send + Sync>>;
+
+    /// 全ケースを `repeat` 回ずつ実行し、結果をまとめて返す。
+    #[allow(clippy::too_many_arguments)]
+    async fn execute(
+        &self,
+        source: &Source,
+        compile_cmd: &str,
+        exec_cmd: &str,
+        parallel: u32,
+        test_cases: Vec<TestCaseModel>,
+        repeat: u32,
+        timeout: u32,
+    ) -> Result<Vec<ExecutionResult>, Box<dyn std::error::Error + Send + Sync>> {
+        self.execute_with_progress(
+            source,
+            compile_cmd,
+            exec_cmd,
+            parallel,
+            test_cases,
+            repeat,
+            timeout,
+            None,
+        )
+        .await
+    }
 }
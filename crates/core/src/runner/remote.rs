@@ -0,0 +1,205 @@
+use crate::config::Remote as RemoteConfig;
+use crate::remote::{
+    RemoteCase, RemoteJobCreated, RemoteJobRequest, RemoteJobResults, TOKEN_ENV, WorkerInfo,
+};
+use crate::runner::{ExecutionResult, ProgressSender, Runner, Source};
+use async_trait::async_trait;
+use heurs_database::TestCaseModel;
+use tokio::time::{Duration, Instant, sleep};
+
+/// 結果をバックエンドに問い合わせる間隔
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// `[remote] job_timeout_secs` を指定しなかったときの、全結果が揃うまで待つ時間の上限
+const DEFAULT_JOB_TIMEOUT: Duration = Duration::from_secs(3600);
+
+/// 貸し出し要求がこの秒数より前に途絶え、バッチも借りていないワーカーは止まっているとみなす
+/// (ワーカーは手が空いていれば 1 秒ごとに貸し出しを求める)
+const WORKER_STALE_SECS: u64 = 30;
+
+/// リモート実行器の実装
+///
+/// バックエンドにジョブを投入し、登録済みのワーカー (`heurs worker`) が
+/// バッチ単位で取り出して実行した結果を受け取ります。
+/// コンパイル・実行のコマンドはバックエンドの設定ファイルのものが使われます。
+pub struct RemoteRunner {
+    config: RemoteConfig,
+    client: reqwest::Client,
+}
+
+impl RemoteRunner {
+    /// `[remote]` セクションの設定から `RemoteRunner` を生成する。
+    pub fn new(config: RemoteConfig) -> Self {
+        RemoteRunner {
+            config,
+            client: reqwest::Client::new(),
+        }
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("{}{}", self.config.server_url.trim_end_matches('/'), path)
+    }
+
+    /// `[remote]` の `token` (なければ環境変数 `HEURS_REMOTE_TOKEN`) を付けたリクエスト
+    fn request(&self, method: reqwest::Method, path: &str) -> reqwest::RequestBuilder {
+        let request = self.client.request(method, self.url(path));
+        match self
+            .config
+            .token
+            .clone()
+            .or_else(|| std::env::var(TOKEN_ENV).ok())
+        {
+            Some(token) => request.bearer_auth(token),
+            None => request,
+        }
+    }
+
+    /// ワーカーが返した順に結果を受け取り、そのまま進捗へ流す。
+    /// `timeout` までに揃わなければ、受け取った分は捨てて失敗させる
+    async fn collect_results(
+        &self,
+        job_id: &str,
+        mut results: Vec<ExecutionResult>,
+        progress: Option<&ProgressSender>,
+        timeout: Duration,
+    ) -> Result<Vec<ExecutionResult>, Box<dyn std::error::Error + Send + Sync>> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            let batch: RemoteJobResults = self
+                .request(
+                    reqwest::Method::GET,
+                    &format!("/api/remote/jobs/{}/results", job_id),
+                )
+                .query(&[("after", results.len())])
+                .send()
+                .await?
+                .error_for_status()?
+                .json()
+                .await?;
+
+            for result in batch.results {
+                if let Some(progress) = progress {
+                    let _ = progress.send(result.clone());
+                }
+                results.push(result);
+            }
+
+            if batch.done && results.len() >= batch.total {
+                return Ok(results);
+            }
+            if Instant::now() >= deadline {
+                return Err(format!(
+                    "Remote job {} timed out after {}s with {}/{} results",
+                    job_id,
+                    timeout.as_secs(),
+                    results.len(),
+                    batch.total
+                )
+                .into());
+            }
+            sleep(POLL_INTERVAL).await;
+        }
+    }
+}
+
+#[async_trait]
+impl Runner for RemoteRunner {
+    async fn execute_with_progress(
+        &self,
+        source: &Source,
+        _compile_cmd: &str,
+        _exec_cmd: &str,
+        _parallel: u32,
+        test_cases: Vec<TestCaseModel>,
+        repeat: u32,
+        timeout: u32,
+        progress: Option<ProgressSender>,
+    ) -> Result<Vec<ExecutionResult>, Box<dyn std::error::Error + Send + Sync>> {
+        let repeat = repeat.max(1);
+        let total = test_cases.len() * repeat as usize;
+        let results: Vec<ExecutionResult> = Vec::with_capacity(total);
+
+        if total == 0 {
+            return Ok(results);
+        }
+
+        // ---- ジョブ投入 ----
+        // バンドルは丸ごと送り、source_code にはエントリポイントの内容を入れる
+        let (source_code, bundle) = match source {
+            Source::File(path) => (tokio::fs::read_to_string(path).await?, None),
+            Source::Bundle(bundle) => (
+                String::from_utf8_lossy(&bundle.read(bundle.entry())?.unwrap_or_default())
+                    .into_owned(),
+                Some(bundle.into()),
+            ),
+        };
+
+        let request = RemoteJobRequest {
+            source_code,
+            source_name: source.name(),
+            bundle,
+            timeout,
+            repeat,
+            batch_size: self.config.batch_size.max(1),
+            cases: test_cases
+                .into_iter()
+                .map(|tc| RemoteCase {
+                    test_case_id: tc.id,
+                    filename: tc.filename,
+                    input: tc.input,
+                })
+                .collect(),
+        };
+
+        // 動いているワーカーがいなければ、いつまでも結果が来ないので投入せずに失敗させる
+        let workers: Vec<WorkerInfo> = self
+            .request(reqwest::Method::GET, "/api/workers")
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        if !workers
+            .iter()
+            .any(|w| w.leased_batches > 0 || w.last_seen_secs <= WORKER_STALE_SECS)
+        {
+            return Err(format!(
+                "No active remote workers on {}; start one with `heurs worker`",
+                self.config.server_url
+            )
+            .into());
+        }
+
+        let created: RemoteJobCreated = self
+            .request(reqwest::Method::POST, "/api/remote/jobs")
+            .json(&request)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        let job_timeout = self
+            .config
+            .job_timeout_secs
+            .map_or(DEFAULT_JOB_TIMEOUT, Duration::from_secs);
+        let collected = self
+            .collect_results(&created.job_id, results, progress.as_ref(), job_timeout)
+            .await;
+
+        // ----- 後片付け -----
+        // 失敗や時間切れで抜けたときも、残ったバッチがワーカーに配られないよう消しておく
+        if let Err(e) = self
+            .request(
+                reqwest::Method::DELETE,
+                &format!("/api/remote/jobs/{}", created.job_id),
+            )
+            .send()
+            .await
+        {
+            eprintln!("Failed to remove remote job {}: {}", created.job_id, e);
+        }
+
+        collected
+    }
+}
@@ -0,0 +1,592 @@
+use crate::config::Sandbox as SandboxConfig;
+use seccompiler::{
+    BpfProgram, SeccompAction, SeccompCmpArgLen, SeccompCmpOp, SeccompCondition, SeccompFilter,
+    SeccompRule,
+};
+use std::collections::BTreeMap;
+use std::ffi::CString;
+use std::io;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::process::CommandExt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// サンドボックス内でのユーザー / グループ ID (nobody)。
+/// 0 以外にしておくことで、exec 時に名前空間内の特権も落ちる。
+const SANDBOX_ID: u32 = 65534;
+
+/// `[sandbox]` で上限を指定しなかったときの既定値
+const DEFAULT_MEMORY_LIMIT_MB: u64 = 4096;
+const DEFAULT_MAX_PROCESSES: u64 = 256;
+const DEFAULT_MAX_FILE_SIZE_MB: u64 = 1024;
+
+/// サンドボックス内で呼べないようにするシステムコール。
+/// 名前空間の作り直しやマウント、他プロセスの覗き見、カーネルへの干渉を塞ぐ。
+const DENIED_SYSCALLS: &[libc::c_long] = &[
+    libc::SYS_ptrace,
+    libc::SYS_process_vm_readv,
+    libc::SYS_process_vm_writev,
+    libc::SYS_mount,
+    libc::SYS_umount2,
+    libc::SYS_pivot_root,
+    libc::SYS_chroot,
+    libc::SYS_unshare,
+    libc::SYS_setns,
+    libc::SYS_keyctl,
+    libc::SYS_add_key,
+    libc::SYS_request_key,
+    libc::SYS_bpf,
+    libc::SYS_perf_event_open,
+    libc::SYS_userfaultfd,
+    libc::SYS_open_by_handle_at,
+    libc::SYS_kexec_load,
+    libc::SYS_init_module,
+    libc::SYS_finit_module,
+    libc::SYS_delete_module,
+    libc::SYS_reboot,
+    libc::SYS_swapon,
+    libc::SYS_swapoff,
+    libc::SYS_acct,
+    libc::SYS_settimeofday,
+    libc::SYS_clock_settime,
+    libc::SYS_sethostname,
+    libc::SYS_setdomainname,
+];
+
+/// `clone` でこれらのフラグのどれかが立っていれば拒否する (`unshare` を塞いだのと同じ理由)。
+/// `CLONE_NEWTIME` は `clone` では使えない (`unshare` と `clone3` のみ) ので含めない。
+const DENIED_CLONE_FLAGS: &[libc::c_int] = &[
+    libc::CLONE_NEWNS,
+    libc::CLONE_NEWUTS,
+    libc::CLONE_NEWIPC,
+    libc::CLONE_NEWUSER,
+    libc::CLONE_NEWPID,
+    libc::CLONE_NEWNET,
+    libc::CLONE_NEWCGROUP,
+];
+
+/// 信頼できない提出コードを閉じ込めるための Linux 名前空間 + seccomp サンドボックス。
+///
+/// 各コマンドは user / mount / pid / ipc / uts (と既定では net) 名前空間の中で
+/// nobody として動き、ルートファイルシステムはすべて読み取り専用になる。
+/// 書き込めるのは実行ごとに作るスクラッチディレクトリだけで、コンパイル結果や
+/// 一時ファイル (`TMPDIR`) もそこに置かれる。
+/// `$HOME` や設定ファイル・DB のあるディレクトリには空の tmpfs をかぶせて中身を隠し、
+/// メモリ・プロセス数・ファイルサイズには rlimit で上限を設ける。
+///
+/// サーバー自体を root で動かすと、nobody に見えていても root 所有のファイルを
+/// 読めてしまうため、バックエンドは専用の一般ユーザーで動かすこと。
+pub struct Sandbox {
+    scratch: PathBuf,
+    scratch_c: CString,
+    unshare_flags: libc::c_int,
+    uid_map: Vec<u8>,
+    gid_map: Vec<u8>,
+    readonly_mounts: Vec<(CString, libc::c_ulong)>,
+    hidden_mounts: Vec<CString>,
+    memory_limit: libc::rlim_t,
+    max_processes: libc::rlim_t,
+    max_file_size: libc::rlim_t,
+    filters: Vec<BpfProgram>,
+}
+
+impl Sandbox {
+    /// スクラッチディレクトリを作り、子プロセス内で使うものをすべて前もって用意する。
+    ///
+    /// fork 後の子プロセスではメモリ確保を避けたいので、パス文字列やマウント一覧、
+    /// seccomp の BPF プログラムはここで組み立てておく。
+    pub fn prepare(config: &SandboxConfig) -> io::Result<Self> {
+        let base = config
+            .scratch_dir
+            .clone()
+            .unwrap_or_else(std::env::temp_dir);
+        let scratch = base.join(format!("heurs-sandbox-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&scratch)?;
+        let scratch = scratch.canonicalize()?;
+        let scratch_c = path_to_cstring(&scratch)?;
+        let hidden_mounts = hidden_mounts(config, &scratch).inspect_err(|_| {
+            let _ = std::fs::remove_dir_all(&scratch);
+        })?;
+
+        let mut unshare_flags = libc::CLONE_NEWUSER
+            | libc::CLONE_NEWNS
+            | libc::CLONE_NEWPID
+            | libc::CLONE_NEWIPC
+            | libc::CLONE_NEWUTS;
+        if !config.allow_network {
+            unshare_flags |= libc::CLONE_NEWNET;
+        }
+
+        // SAFETY: getuid / getgid は常に成功する
+        let (uid, gid) = unsafe { (libc::getuid(), libc::getgid()) };
+
+        const MIB: u64 = 1024 * 1024;
+        let memory_limit = config.memory_limit_mb.unwrap_or(DEFAULT_MEMORY_LIMIT_MB) * MIB;
+        let max_file_size = config.max_file_size_mb.unwrap_or(DEFAULT_MAX_FILE_SIZE_MB) * MIB;
+
+        Ok(Sandbox {
+            scratch,
+            scratch_c,
+            unshare_flags,
+            uid_map: format!("{} {} 1", SANDBOX_ID, uid).into_bytes(),
+            gid_map: format!("{} {} 1", SANDBOX_ID, gid).into_bytes(),
+            readonly_mounts: read_mounts()?,
+            hidden_mounts,
+            memory_limit: memory_limit as libc::rlim_t,
+            max_processes: config.max_processes.unwrap_or(DEFAULT_MAX_PROCESSES) as libc::rlim_t,
+            max_file_size: max_file_size as libc::rlim_t,
+            filters: build_filters()?,
+        })
+    }
+
+    /// 書き込み可能なスクラッチディレクトリ
+    pub fn scratch(&self) -> &Path {
+        &self.scratch
+    }
+
+    /// `command` をサンドボックス内で起動するように設定する
+    pub fn apply(self: &std::sync::Arc<Self>, command: &mut Command) {
+        command
+            .current_dir(&self.scratch)
+            .env("TMPDIR", &self.scratch);
+        let sandbox = self.clone();
+        // SAFETY: enter は fork 後の子プロセスで呼ばれ、前もって用意したデータと
+        // システムコールしか使わない
+        unsafe {
+            command.pre_exec(move || sandbox.enter());
+        }
+    }
+
+    /// fork 後の子プロセスで名前空間に入り、ファイルシステムと seccomp を設定する
+    fn enter(&self) -> io::Result<()> {
+        // SAFETY: 渡しているポインタはすべて self が所有する NUL 終端文字列か null
+        unsafe {
+            check(libc::unshare(self.unshare_flags))?;
+
+            // 自分の uid / gid だけを名前空間内の nobody に対応付ける
+            write_proc(c"/proc/self/setgroups", b"deny")?;
+            write_proc(c"/proc/self/uid_map", &self.uid_map)?;
+            write_proc(c"/proc/self/gid_map", &self.gid_map)?;
+
+            // ホスト側へマウントの変更が伝播しないようにする
+            check(libc::mount(
+                std::ptr::null(),
+                c"/".as_ptr(),
+                std::ptr::null(),
+                libc::MS_REC | libc::MS_PRIVATE,
+                std::ptr::null(),
+            ))?;
+
+            // スクラッチだけは独立したマウントにして書き込み可能なまま残す
+            check(libc::mount(
+                self.scratch_c.as_ptr(),
+                self.scratch_c.as_ptr(),
+                std::ptr::null(),
+                libc::MS_BIND | libc::MS_REC,
+                std::ptr::null(),
+            ))?;
+
+            for (target, flags) in &self.readonly_mounts {
+                let ret = libc::mount(
+                    std::ptr::null(),
+                    target.as_ptr(),
+                    std::ptr::null(),
+                    libc::MS_BIND | libc::MS_REMOUNT | libc::MS_RDONLY | flags,
+                    std::ptr::null(),
+                );
+                // /proc や /sys の中の特殊なマウントは名前空間内から触れないことがあるが、
+                // もともと一般ユーザーは書き込めないので読み取り専用化に失敗しても続ける
+                if ret != 0 && !is_pseudo_fs(target) {
+                    return Err(io::Error::last_os_error());
+                }
+            }
+
+            // 秘密を含みうるディレクトリは空の読み取り専用 tmpfs で覆う。
+            // seccomp で umount を塞ぐので、中から元の中身を覗き直すことはできない
+            for target in &self.hidden_mounts {
+                check(libc::mount(
+                    c"tmpfs".as_ptr(),
+                    target.as_ptr(),
+                    c"tmpfs".as_ptr(),
+                    libc::MS_NOSUID | libc::MS_NODEV | libc::MS_NOEXEC | libc::MS_RDONLY,
+                    c"mode=0555".as_ptr().cast(),
+                ))?;
+            }
+
+            // current_dir による chdir はこの処理より前に行われ、バインドマウントの下の
+            // ディレクトリを指したままなので入り直す
+            check(libc::chdir(self.scratch_c.as_ptr()))?;
+
+            // pid 名前空間は unshare した後に作った子から有効になるので、もう一段 fork する。
+            // 親はコマンドの終了を待ってその終了コードで抜けるだけの中継役になる。
+            let pid = check(libc::fork())?;
+            if pid > 0 {
+                // 中継役は exec しないので、親から引き継いだ CLOEXEC 付きの fd
+                // (他ケースの stdin パイプなど) も自分で閉じないと EOF が届かなくなる
+                close_all_fds();
+                libc::_exit(wait_exit_code(pid));
+            }
+
+            // 中継役が殺されたらコマンドも道連れにする
+            libc::prctl(libc::PR_SET_PDEATHSIG, libc::SIGKILL);
+
+            // 上限は exec 後のコマンドとその子にだけ掛ける (中継役は対象外)
+            for (resource, limit) in [
+                (libc::RLIMIT_AS, self.memory_limit),
+                (libc::RLIMIT_NPROC, self.max_processes),
+                (libc::RLIMIT_FSIZE, self.max_file_size),
+            ] {
+                let rlimit = libc::rlimit {
+                    rlim_cur: limit,
+                    rlim_max: limit,
+                };
+                check(libc::setrlimit(resource, &rlimit))?;
+            }
+
+            // 名前空間内のプロセスだけが見える /proc に差し替える。
+            // コンテナ内などで許可されない場合はホストの (読み取り専用の) /proc のまま続ける
+            libc::mount(
+                c"proc".as_ptr(),
+                c"/proc".as_ptr(),
+                c"proc".as_ptr(),
+                libc::MS_NOSUID | libc::MS_NODEV | libc::MS_NOEXEC,
+                std::ptr::null(),
+            );
+        }
+
+        for filter in &self.filters {
+            seccompiler::apply_filter(filter).map_err(io::Error::other)?;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for Sandbox {
+    fn drop(&mut self) {
+        if let Err(e) = std::fs::remove_dir_all(&self.scratch) {
+            eprintln!(
+                "Failed to remove sandbox scratch {}: {}",
+                self.scratch.display(),
+                e
+            );
+        }
+    }
+}
+
+fn check<T: Default + PartialOrd>(ret: T) -> io::Result<T> {
+    if ret < T::default() {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(ret)
+    }
+}
+
+/// `/proc/self/*` に書き込む (setgroups は古いカーネルには無いので無視する)
+unsafe fn write_proc(path: &std::ffi::CStr, data: &[u8]) -> io::Result<()> {
+    // SAFETY: path は NUL 終端、data は有効なスライス
+    unsafe {
+        let fd = libc::open(path.as_ptr(), libc::O_WRONLY | libc::O_CLOEXEC);
+        if fd < 0 {
+            let err = io::Error::last_os_error();
+            if err.raw_os_error() == Some(libc::ENOENT) && path == c"/proc/self/setgroups" {
+                return Ok(());
+            }
+            return Err(err);
+        }
+        let written = libc::write(fd, data.as_ptr().cast(), data.len());
+        let err = io::Error::last_os_error();
+        libc::close(fd);
+        if written != data.len() as isize {
+            return Err(err);
+        }
+    }
+    Ok(())
+}
+
+/// 標準入出力を含むすべての fd を閉じる
+unsafe fn close_all_fds() {
+    // SAFETY: 引数は整数のみ
+    unsafe {
+        if libc::syscall(libc::SYS_close_range, 0, libc::c_uint::MAX, 0) != 0 {
+            // close_range の無い古いカーネル向け
+            for fd in 0..libc::sysconf(libc::_SC_OPEN_MAX).clamp(0, 65536) as libc::c_int {
+                libc::close(fd);
+            }
+        }
+    }
+}
+
+/// 子プロセスの終了を待ち、シェルと同じ規則で終了コードに変換する
+unsafe fn wait_exit_code(pid: libc::pid_t) -> libc::c_int {
+    let mut status = 0;
+    loop {
+        // SAFETY: status は有効なポインタ
+        let ret = unsafe { libc::waitpid(pid, &mut status, 0) };
+        if ret == pid {
+            break;
+        }
+        if ret < 0 && io::Error::last_os_error().raw_os_error() != Some(libc::EINTR) {
+            return 127;
+        }
+    }
+    if libc::WIFEXITED(status) {
+        libc::WEXITSTATUS(status)
+    } else if libc::WIFSIGNALED(status) {
+        128 + libc::WTERMSIG(status)
+    } else {
+        1
+    }
+}
+
+fn is_pseudo_fs(target: &CString) -> bool {
+    let bytes = target.as_bytes();
+    ["/proc", "/sys", "/dev"].iter().any(|prefix| {
+        bytes.starts_with(prefix.as_bytes()) && matches!(bytes.get(prefix.len()), None | Some(b'/'))
+    })
+}
+
+/// 空の tmpfs で覆うディレクトリを決める。
+///
+/// `$HOME` と現在のディレクトリ (既定の heurs.toml / heurs.db の置き場所) に
+/// 設定の `hidden_paths` を加え、存在しないものと、既に隠す他のディレクトリの下に
+/// あるものは除く。スクラッチを含むディレクトリは隠せないのでエラーにするが、
+/// サービスとして `/` などで起動された場合の現在のディレクトリだけは読み飛ばす。
+fn hidden_mounts(config: &SandboxConfig, scratch: &Path) -> io::Result<Vec<CString>> {
+    let mut paths = Vec::new();
+    if let Ok(dir) = std::env::current_dir()
+        && !scratch.starts_with(&dir)
+    {
+        paths.push(dir);
+    }
+    paths.extend(std::env::var_os("HOME").map(PathBuf::from));
+    paths.extend(config.hidden_paths.iter().cloned());
+
+    let mut dirs = Vec::new();
+    for path in paths {
+        let Ok(dir) = path.canonicalize() else {
+            continue;
+        };
+        if !dir.is_dir() {
+            return Err(io::Error::other(format!(
+                "Sandbox hidden path {} is not a directory",
+                dir.display()
+            )));
+        }
+        if scratch.starts_with(&dir) {
+            return Err(io::Error::other(format!(
+                "Sandbox hidden path {} contains the scratch directory {}",
+                dir.display(),
+                scratch.display()
+            )));
+        }
+        dirs.push(dir);
+    }
+
+    // 親が先に来るように並べ、隠した親の下にあるものは覆う必要がないので捨てる
+    dirs.sort();
+    dirs.dedup();
+    let mut hidden: Vec<PathBuf> = Vec::new();
+    for dir in dirs {
+        if !hidden.iter().any(|parent| dir.starts_with(parent)) {
+            hidden.push(dir);
+        }
+    }
+    hidden.iter().map(|dir| path_to_cstring(dir)).collect()
+}
+
+fn path_to_cstring(path: &Path) -> io::Result<CString> {
+    CString::new(path.as_os_str().as_bytes()).map_err(io::Error::other)
+}
+
+/// `/proc/self/mountinfo` からマウントポイントと、読み取り専用で付け直す際に
+/// 維持しなければならないフラグ (nosuid など) を読み取る。
+///
+/// user 名前空間内からの再マウントでは、元のマウントに付いていたこれらのフラグを
+/// 外そうとすると EPERM になる。
+fn read_mounts() -> io::Result<Vec<(CString, libc::c_ulong)>> {
+    let mountinfo = std::fs::read_to_string("/proc/self/mountinfo")?;
+    let mut mounts = Vec::new();
+    for line in mountinfo.lines() {
+        let fields: Vec<&str> = line.split(' ').collect();
+        let (Some(mount_point), Some(options)) = (fields.get(4), fields.get(5)) else {
+            continue;
+        };
+
+        let mut flags = 0;
+        for option in options.split(',') {
+            flags |= match option {
+                "nosuid" => libc::MS_NOSUID,
+                "nodev" => libc::MS_NODEV,
+                "noexec" => libc::MS_NOEXEC,
+                "noatime" => libc::MS_NOATIME,
+                "nodiratime" => libc::MS_NODIRATIME,
+                "relatime" => libc::MS_RELATIME,
+                "strictatime" => libc::MS_STRICTATIME,
+                _ => 0,
+            };
+        }
+
+        let mount_point = unescape_mount_point(mount_point);
+        mounts.push((CString::new(mount_point).map_err(io::Error::other)?, flags));
+    }
+    Ok(mounts)
+}
+
+/// mountinfo では空白などが `\040` のような 8 進エスケープになっているので戻す
+fn unescape_mount_point(s: &str) -> Vec<u8> {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'\\'
+            && let Some(code) = bytes
+                .get(i + 1..i + 4)
+                .and_then(|digits| std::str::from_utf8(digits).ok())
+                .and_then(|digits| u8::from_str_radix(digits, 8).ok())
+        {
+            out.push(code);
+            i += 4;
+            continue;
+        }
+        out.push(bytes[i]);
+        i += 1;
+    }
+    out
+}
+
+/// seccomp の BPF プログラムを組み立てる。
+///
+/// 一つのフィルタでは一致したときの動作を一つしか持てないので、EPERM を返すものと
+/// `clone3` に ENOSYS を返すものの二つに分ける。`clone3` はフラグを構造体で渡すため
+/// seccomp からは中身を検査できない。ENOSYS にしておけば glibc などは `clone` に
+/// フォールバックし、そちらで `CLONE_NEW*` を弾ける。
+fn build_filters() -> io::Result<Vec<BpfProgram>> {
+    let arch: seccompiler::TargetArch = std::env::consts::ARCH
+        .try_into()
+        .map_err(io::Error::other)?;
+
+    let mut denied: BTreeMap<i64, Vec<SeccompRule>> = DENIED_SYSCALLS
+        .iter()
+        .map(|&syscall| (syscall, Vec::new()))
+        .collect();
+    // 同じシステムコールに複数のルールがあるときは、どれか一つに一致すれば拒否される
+    let clone_rules = DENIED_CLONE_FLAGS
+        .iter()
+        .map(|&flag| {
+            let flag = flag as u64;
+            SeccompCondition::new(
+                0,
+                SeccompCmpArgLen::Qword,
+                SeccompCmpOp::MaskedEq(flag),
+                flag,
+            )
+            .and_then(|condition| SeccompRule::new(vec![condition]))
+            .map_err(io::Error::other)
+        })
+        .collect::<io::Result<Vec<_>>>()?;
+    denied.insert(libc::SYS_clone, clone_rules);
+
+    let no_clone3 = BTreeMap::from([(libc::SYS_clone3, Vec::new())]);
+
+    [(denied, libc::EPERM), (no_clone3, libc::ENOSYS)]
+        .into_iter()
+        .map(|(rules, errno)| {
+            let filter = SeccompFilter::new(
+                rules,
+                SeccompAction::Allow,
+                SeccompAction::Errno(errno as u32),
+                arch,
+            )
+            .map_err(io::Error::other)?;
+            filter.try_into().map_err(io::Error::other)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unescape_mount_point_decodes_octal_escapes() {
+        assert_eq!(unescape_mount_point("/mnt/a\\040b"), b"/mnt/a b");
+        assert_eq!(unescape_mount_point("/mnt/tab\\011"), b"/mnt/tab\t");
+        assert_eq!(
+            unescape_mount_point("/mnt/back\\134slash"),
+            b"/mnt/back\\slash"
+        );
+    }
+
+    #[test]
+    fn unescape_mount_point_keeps_invalid_escapes() {
+        assert_eq!(unescape_mount_point("/plain"), b"/plain");
+        // 桁が足りない / 8 進数でない / u8 に収まらないものはそのまま
+        assert_eq!(unescape_mount_point("/a\\04"), b"/a\\04");
+        assert_eq!(unescape_mount_point("/a\\089"), b"/a\\089");
+        assert_eq!(unescape_mount_point("/a\\777"), b"/a\\777");
+        assert_eq!(unescape_mount_point("/a\\"), b"/a\\");
+    }
+
+    #[test]
+    fn is_pseudo_fs_matches_whole_components() {
+        for path in [
+            "/proc",
+            "/proc/sys/fs",
+            "/sys",
+            "/sys/fs/cgroup",
+            "/dev",
+            "/dev/shm",
+        ] {
+            assert!(is_pseudo_fs(&CString::new(path).unwrap()), "{}", path);
+        }
+        for path in [
+            "/",
+            "/process",
+            "/system",
+            "/devices",
+            "/home/proc",
+            "/var/dev",
+        ] {
+            assert!(!is_pseudo_fs(&CString::new(path).unwrap()), "{}", path);
+        }
+    }
+
+    #[test]
+    fn hidden_mounts_skip_nested_and_missing_paths() {
+        let base = std::env::temp_dir().join(format!("heurs-hidden-{}", uuid::Uuid::new_v4()));
+        let (secrets, nested, scratch) = (
+            base.join("secrets"),
+            base.join("secrets/db"),
+            base.join("scratch"),
+        );
+        for dir in [&nested, &scratch] {
+            std::fs::create_dir_all(dir).unwrap();
+        }
+        let config = SandboxConfig {
+            hidden_paths: vec![nested.clone(), secrets.clone(), base.join("missing")],
+            ..Default::default()
+        };
+
+        let hidden = hidden_mounts(&config, &scratch.canonicalize().unwrap()).unwrap();
+        let secrets = path_to_cstring(&secrets.canonicalize().unwrap()).unwrap();
+        let nested = path_to_cstring(&nested.canonicalize().unwrap()).unwrap();
+        assert!(hidden.contains(&secrets));
+        assert!(!hidden.contains(&nested));
+
+        // スクラッチを含むディレクトリは隠せない
+        let config = SandboxConfig {
+            hidden_paths: vec![base.clone()],
+            ..Default::default()
+        };
+        assert!(hidden_mounts(&config, &scratch.canonicalize().unwrap()).is_err());
+
+        std::fs::remove_dir_all(&base).unwrap();
+    }
+
+    #[test]
+    fn filters_build_for_host_arch() {
+        let filters = build_filters().unwrap();
+        assert_eq!(filters.len(), 2);
+        assert!(filters.iter().all(|filter| !filter.is_empty()));
+    }
+}
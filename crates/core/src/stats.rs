@@ -0,0 +1,135 @@
+//! `--repeat` で同じケースを複数回実行したときの集計
+//!
+//! スコアは大きいほど良いものとして扱う (リーダーボードの並び順と同じ)。
+
+use crate::runner::ExecutionResult;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// 1 ケース分の繰り返し実行の要約
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CaseStats {
+    pub test_case_id: u32,
+    /// 実行回数
+    pub runs: usize,
+    pub mean_score: f64,
+    /// スコアの標本標準偏差 (1 回だけなら 0)
+    pub std_dev_score: f64,
+    /// 最も悪いスコア
+    pub worst_score: i64,
+    /// 最も良いスコア
+    pub best_score: i64,
+    pub mean_execution_time_ms: f64,
+}
+
+/// 既知の最良スコアに対する割合 (%)。最良スコアがないか 0 以下なら None
+pub fn relative_score(score: f64, best_known: Option<i64>) -> Option<f64> {
+    match best_known {
+        Some(best) if best > 0 => Some(score / best as f64 * 100.0),
+        _ => None,
+    }
+}
+
+/// 結果をケースごとにまとめ、ケース ID 順に返す
+pub fn case_stats(results: &[ExecutionResult]) -> Vec<CaseStats> {
+    let mut by_case: BTreeMap<u32, Vec<&ExecutionResult>> = BTreeMap::new();
+    for result in results {
+        by_case.entry(result.test_case_id).or_default().push(result);
+    }
+
+    by_case
+        .into_iter()
+        .map(|(test_case_id, runs)| {
+            let n = runs.len() as f64;
+            let mean_score = runs.iter().map(|r| r.score as f64).sum::<f64>() / n;
+            let std_dev_score = if runs.len() > 1 {
+                let variance = runs
+                    .iter()
+                    .map(|r| (r.score as f64 - mean_score).powi(2))
+                    .sum::<f64>()
+                    / (n - 1.0);
+                variance.sqrt()
+            } else {
+                0.0
+            };
+
+            CaseStats {
+                test_case_id,
+                runs: runs.len(),
+                mean_score,
+                std_dev_score,
+                worst_score: runs.iter().map(|r| r.score).min().unwrap_or(0),
+                best_score: runs.iter().map(|r| r.score).max().unwrap_or(0),
+                mean_execution_time_ms: runs
+                    .iter()
+                    .map(|r| r.execution_time_ms as f64)
+                    .sum::<f64>()
+                    / n,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn result(
+        test_case_id: u32,
+        repeat: u32,
+        score: i64,
+        execution_time_ms: u32,
+    ) -> ExecutionResult {
+        ExecutionResult {
+            test_case_id,
+            repeat,
+            success: true,
+            stdout: String::new(),
+            stderr: String::new(),
+            execution_time_ms,
+            score,
+        }
+    }
+
+    #[test]
+    fn case_stats_summarize_each_case() {
+        // ケース 2 を先に並べても、ケース ID 順に返る
+        let results = vec![
+            result(2, 0, 7, 5),
+            result(1, 0, 10, 100),
+            result(1, 1, 20, 200),
+            result(1, 2, 30, 300),
+        ];
+
+        let stats = case_stats(&results);
+        assert_eq!(stats.len(), 2);
+
+        let first = &stats[0];
+        assert_eq!(first.test_case_id, 1);
+        assert_eq!(first.runs, 3);
+        assert_eq!(first.mean_score, 20.0);
+        // 標本標準偏差: sqrt(((-10)^2 + 0 + 10^2) / 2) = 10
+        assert!((first.std_dev_score - 10.0).abs() < 1e-9);
+        assert_eq!(first.worst_score, 10);
+        assert_eq!(first.best_score, 30);
+        assert_eq!(first.mean_execution_time_ms, 200.0);
+
+        // 1 回だけなら標準偏差は 0 で、最良と最悪は同じ
+        let second = &stats[1];
+        assert_eq!(second.test_case_id, 2);
+        assert_eq!(second.runs, 1);
+        assert_eq!(second.mean_score, 7.0);
+        assert_eq!(second.std_dev_score, 0.0);
+        assert_eq!(second.worst_score, 7);
+        assert_eq!(second.best_score, 7);
+    }
+
+    #[test]
+    fn relative_score_needs_a_positive_best() {
+        assert_eq!(relative_score(50.0, Some(200)), Some(25.0));
+        assert_eq!(relative_score(300.0, Some(200)), Some(150.0));
+        assert_eq!(relative_score(50.0, Some(0)), None);
+        assert_eq!(relative_score(50.0, Some(-10)), None);
+        assert_eq!(relative_score(50.0, None), None);
+    }
+}
@@ -0,0 +1,221 @@
+//! `heurs worker` の本体
+//!
+//! バックエンドに自分を登録し、RemoteRunner が投入したジョブをバッチ単位で借りて
+//! `LocalRunner` で実行し、結果を送り返すことを繰り返す。
+
+use crate::bundle::Bundle;
+use crate::remote::{
+    BatchResults, LeaseResponse, RemoteBatch, WorkerRegistered, WorkerRegistration,
+    is_plain_file_name,
+};
+use crate::runner::{ExecutionResult, LocalRunner, Runner, Source};
+use heurs_database::TestCaseModel;
+use std::path::PathBuf;
+use tokio::time::{Duration, sleep};
+
+/// 仕事がないとき・バックエンドに繋がらないときの待ち時間
+const IDLE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// ワーカーを起動し、止められるまで仕事を取り続ける。
+///
+/// 作業ディレクトリ `work_dir` に移動してから実行するので、同じマシンで複数起動する場合は
+/// それぞれ別のディレクトリを指定すること。`token` はバックエンドと共有するトークンで、
+/// `runner` は設定ファイルの `[sandbox]` などを反映した実行器。
+pub async fn run_worker(
+    server_url: &str,
+    name: &str,
+    parallel: u32,
+    work_dir: PathBuf,
+    token: &str,
+    runner: LocalRunner,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let client = authorized_client(token)?;
+    let server_url = server_url.trim_end_matches('/').to_string();
+
+    // コンパイル成果物 (./a.out など) が他のワーカーと衝突しないように専用ディレクトリで動く
+    std::fs::create_dir_all(&work_dir)?;
+    std::env::set_current_dir(&work_dir)?;
+
+    let mut worker_id = register(&client, &server_url, name, parallel).await?;
+    println!(
+        "Worker '{}' registered as {} (parallel: {}, dir: {})",
+        name,
+        worker_id,
+        parallel,
+        work_dir.display()
+    );
+
+    loop {
+        // ---- 仕事を借りる ----
+        let response = match client
+            .post(format!("{}/api/workers/{}/lease", server_url, worker_id))
+            .send()
+            .await
+        {
+            Ok(response) => response,
+            Err(e) => {
+                eprintln!("Failed to reach {}: {}", server_url, e);
+                sleep(IDLE_INTERVAL).await;
+                continue;
+            }
+        };
+
+        // バックエンドが再起動して登録が消えていたら登録し直す
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            worker_id = register(&client, &server_url, name, parallel).await?;
+            println!("Re-registered as {}", worker_id);
+            continue;
+        }
+
+        let lease: LeaseResponse = match response.error_for_status() {
+            Ok(response) => response.json().await?,
+            Err(e) => {
+                eprintln!("Failed to lease a batch: {}", e);
+                sleep(IDLE_INTERVAL).await;
+                continue;
+            }
+        };
+
+        let Some(batch) = lease.batch else {
+            sleep(IDLE_INTERVAL).await;
+            continue;
+        };
+
+        // ---- 実行 ----
+        println!(
+            "Running batch {} ({} cases x {}) of job {}",
+            batch.batch_id,
+            batch.cases.len(),
+            batch.repeat,
+            batch.job_id
+        );
+        let results = run_batch(&runner, &batch, parallel).await;
+
+        // ---- 結果の返却 ----
+        if let Err(e) = client
+            .post(format!(
+                "{}/api/workers/{}/batches/{}/results",
+                server_url, worker_id, batch.batch_id
+            ))
+            .json(&BatchResults { results })
+            .send()
+            .await
+            .and_then(|r| r.error_for_status())
+        {
+            // 返せなかったバッチは貸し出し期限切れで他のワーカーに回る
+            eprintln!("Failed to report batch {}: {}", batch.batch_id, e);
+        }
+    }
+}
+
+/// すべてのリクエストに `Authorization: Bearer <token>` を付けるクライアント
+fn authorized_client(
+    token: &str,
+) -> Result<reqwest::Client, Box<dyn std::error::Error + Send + Sync>> {
+    let mut value = reqwest::header::HeaderValue::from_str(&format!("Bearer {}", token))?;
+    value.set_sensitive(true);
+    let mut headers = reqwest::header::HeaderMap::new();
+    headers.insert(reqwest::header::AUTHORIZATION, value);
+    Ok(reqwest::Client::builder()
+        .default_headers(headers)
+        .build()?)
+}
+
+async fn register(
+    client: &reqwest::Client,
+    server_url: &str,
+    name: &str,
+    parallel: u32,
+) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+    let registered: WorkerRegistered = client
+        .post(format!("{}/api/workers/register", server_url))
+        .json(&WorkerRegistration {
+            name: name.to_string(),
+            parallel,
+        })
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    Ok(registered.worker_id)
+}
+
+/// 1 バッチ分をローカルで実行する。コンパイルに失敗した場合は全ケースを失敗として返す。
+async fn run_batch(
+    runner: &LocalRunner,
+    batch: &RemoteBatch,
+    parallel: u32,
+) -> Vec<ExecutionResult> {
+    let source = match &batch.bundle {
+        Some(bundle) => match Bundle::try_from(bundle) {
+            Ok(bundle) => Source::Bundle(bundle),
+            Err(e) => return failed_results(batch, &format!("Failed to decode bundle: {}", e)),
+        },
+        None => {
+            // 作業ディレクトリの外には書かない
+            if !is_plain_file_name(&batch.source_name) {
+                return failed_results(
+                    batch,
+                    &format!("Invalid source file name '{}'", batch.source_name),
+                );
+            }
+            let source_path = PathBuf::from(&batch.source_name);
+            if let Err(e) = std::fs::write(&source_path, &batch.source_code) {
+                return failed_results(batch, &format!("Failed to write source: {}", e));
+            }
+            Source::File(source_path)
+        }
+    };
+
+    let test_cases = batch
+        .cases
+        .iter()
+        .map(|case| TestCaseModel {
+            id: case.test_case_id,
+            // 実行には使わないので、どの問題のケースかはワーカーへ渡していない
+            problem_id: 0,
+            content_hash: String::new(),
+            input: case.input.clone(),
+            filename: case.filename.clone(),
+            created_at: chrono::Utc::now(),
+            seed: None,
+            tags: String::new(),
+            best_known_score: None,
+        })
+        .collect();
+
+    match runner
+        .execute(
+            &source,
+            &batch.compile_cmd,
+            &batch.exec_cmd,
+            parallel,
+            test_cases,
+            batch.repeat,
+            batch.timeout,
+        )
+        .await
+    {
+        Ok(results) => results,
+        Err(e) => failed_results(batch, &e.to_string()),
+    }
+}
+
+fn failed_results(batch: &RemoteBatch, message: &str) -> Vec<ExecutionResult> {
+    batch
+        .cases
+        .iter()
+        .flat_map(|case| {
+            (0..batch.repeat.max(1)).map(|repeat| ExecutionResult {
+                test_case_id: case.test_case_id as u32,
+                repeat,
+                success: false,
+                stdout: String::new(),
+                stderr: message.to_string(),
+                execution_time_ms: 0,
+                score: 0,
+            })
+        })
+        .collect()
+}
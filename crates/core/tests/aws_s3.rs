@@ -0,0 +1,100 @@
+//! `AWSRunner` の S3 側の処理を MinIO / LocalStack に対して確認する結合テスト。
+//!
+//! 実際の AWS には接続しないが、S3 互換サーバーが必要なので既定では無視される。
+//! `just test-aws-local` で MinIO を起動してから実行する:
+//!
+//! ```bash
+//! HEURS_TEST_S3_ENDPOINT=http://localhost:9000 \
+//!   cargo test -p heurs-core --test aws_s3 -- --ignored
+//! ```
+
+use heurs_core::{AWSRunner, AwsConfig, Source};
+use uuid::Uuid;
+
+fn test_config() -> AwsConfig {
+    let endpoint_url = std::env::var("HEURS_TEST_S3_ENDPOINT")
+        .expect("HEURS_TEST_S3_ENDPOINT must point to a MinIO/LocalStack endpoint");
+    AwsConfig {
+        region: Some("us-east-1".to_string()),
+        endpoint_url: Some(endpoint_url),
+        access_key_id: Some(
+            std::env::var("HEURS_TEST_S3_ACCESS_KEY").unwrap_or_else(|_| "minioadmin".into()),
+        ),
+        secret_access_key: Some(
+            std::env::var("HEURS_TEST_S3_SECRET_KEY").unwrap_or_else(|_| "minioadmin".into()),
+        ),
+        bucket: Some(format!("heurs-test-{}", Uuid::new_v4().simple())),
+        force_path_style: true,
+        ..Default::default()
+    }
+}
+
+#[tokio::test]
+#[ignore = "requires a local S3 endpoint (HEURS_TEST_S3_ENDPOINT)"]
+async fn s3_roundtrip_is_scoped_to_run_prefix() {
+    let runner = AWSRunner::new(test_config());
+    let client = runner.s3_client().await;
+    let bucket = runner.bucket();
+
+    client.create_bucket().bucket(&bucket).send().await.unwrap();
+
+    // ---- ソースのアップロード ----
+    let source_path = std::env::temp_dir().join(format!("{}.cpp", Uuid::new_v4()));
+    std::fs::write(&source_path, "int main() {}\n").unwrap();
+
+    let run_a = AWSRunner::run_prefix(&Uuid::new_v4().to_string());
+    let run_b = AWSRunner::run_prefix(&Uuid::new_v4().to_string());
+
+    let key =
+        AWSRunner::upload_source(&client, &bucket, &run_a, &Source::File(source_path.clone()))
+            .await
+            .unwrap();
+    assert!(key.starts_with(&run_a));
+    assert_eq!(
+        AWSRunner::fetch_object(&client, &bucket, &key)
+            .await
+            .unwrap(),
+        "int main() {}\n"
+    );
+
+    // ---- ジョブが書き出す結果を模擬 ----
+    for (prefix, stderr) in [(&run_a, "@@HEURS_SCORE=1"), (&run_b, "@@HEURS_SCORE=2")] {
+        client
+            .put_object()
+            .bucket(&bucket)
+            .key(format!("{}errors/error_0.txt", prefix))
+            .body(stderr.as_bytes().to_vec().into())
+            .send()
+            .await
+            .unwrap();
+    }
+
+    // 同じ seed でも実行ごとに別のキーを読む
+    let stderr_a =
+        AWSRunner::fetch_object(&client, &bucket, &format!("{}errors/error_0.txt", run_a))
+            .await
+            .unwrap();
+    assert_eq!(heurs_core::extract_heurs_markers!(&stderr_a).0, 1);
+
+    // ---- 後片付けは自分の名前空間だけを消す ----
+    let deleted = AWSRunner::cleanup_prefix(&client, &bucket, &run_a)
+        .await
+        .unwrap();
+    assert_eq!(deleted, 2);
+    assert!(
+        AWSRunner::fetch_object(&client, &bucket, &key)
+            .await
+            .is_err()
+    );
+    assert!(
+        AWSRunner::fetch_object(&client, &bucket, &format!("{}errors/error_0.txt", run_b))
+            .await
+            .is_ok()
+    );
+
+    AWSRunner::cleanup_prefix(&client, &bucket, &run_b)
+        .await
+        .unwrap();
+    client.delete_bucket().bucket(&bucket).send().await.unwrap();
+    let _ = std::fs::remove_file(&source_path);
+}
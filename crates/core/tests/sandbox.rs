@@ -0,0 +1,147 @@
+//! `[sandbox]` を有効にした `LocalRunner` から抜け出せないことを確認する結合テスト。
+//!
+//! 非特権ユーザーの user 名前空間が必要で、Docker の既定の seccomp プロファイル下などでは
+//! 動かないため既定では無視される:
+//!
+//! ```bash
+//! cargo test -p heurs-core --test sandbox -- --ignored
+//! ```
+//!
+//! テストバイナリ自身をスクラッチにコピーし、`sandbox_probe` だけを中で動かして検査する。
+
+use heurs_core::{Config, LocalRunner, Runner, Source};
+use heurs_database::TestCaseModel;
+use std::io::ErrorKind;
+use std::net::{SocketAddr, TcpStream};
+use std::time::Duration;
+
+/// サンドボックス内で動いていることを `sandbox_probe` に伝える環境変数
+const PROBE_ENV: &str = "HEURS_SANDBOX_PROBE";
+
+fn sandbox_config() -> Config {
+    toml::from_str(
+        r#"
+        [execution]
+        compile_cmd = "mv {{src}} ./probe"
+        exec_cmd = "HEURS_SANDBOX_PROBE=1 ./probe --exact sandbox_probe --ignored --test-threads=1"
+
+        [sandbox]
+        enabled = true
+        memory_limit_mb = 2048
+        max_processes = 64
+        max_file_size_mb = 16
+        "#,
+    )
+    .unwrap()
+}
+
+#[tokio::test]
+#[ignore = "requires unprivileged user namespaces"]
+async fn sandbox_blocks_escapes() {
+    let config = sandbox_config();
+    let runner = LocalRunner::from_config(&config);
+    let probe = Source::File(std::env::current_exe().unwrap());
+    let test_case = TestCaseModel {
+        id: 1,
+        problem_id: 0,
+        content_hash: String::new(),
+        input: String::new(),
+        filename: "probe.txt".to_string(),
+        created_at: chrono::Utc::now(),
+        seed: None,
+        tags: String::new(),
+        best_known_score: None,
+    };
+
+    let results = runner
+        .execute(
+            &probe,
+            &config.execution.compile_cmd,
+            &config.execution.exec_cmd,
+            1,
+            vec![test_case],
+            1,
+            10,
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(results.len(), 1);
+    let result = &results[0];
+    assert!(
+        result.success,
+        "probe failed:\n{}\n{}",
+        result.stdout, result.stderr
+    );
+    assert!(result.stdout.contains("sandbox_probe ... ok"));
+}
+
+/// サンドボックスの中でだけ意味を持つ検査。`sandbox_blocks_escapes` から起動される
+#[test]
+#[ignore = "run inside the sandbox by sandbox_blocks_escapes"]
+fn sandbox_probe() {
+    if std::env::var_os(PROBE_ENV).is_none() {
+        return;
+    }
+
+    // ---- スクラッチ以外には書き込めない ----
+    std::fs::write("written-in-scratch", "ok").unwrap();
+    for path in ["/tmp/heurs-sandbox-escape", "/var/tmp/heurs-sandbox-escape"] {
+        assert!(
+            std::fs::write(path, "escape").is_err(),
+            "{} was writable",
+            path
+        );
+    }
+
+    // ---- $HOME と起動時のディレクトリ (テストではクレートのディレクトリ) は空に見える ----
+    let home = std::env::var("HOME").unwrap();
+    let manifest_dir = env!("CARGO_MANIFEST_DIR");
+    for dir in [home.as_str(), manifest_dir] {
+        let entries = std::fs::read_dir(dir).unwrap().count();
+        assert_eq!(entries, 0, "{} is visible", dir);
+    }
+
+    // ---- ネットワークには出られない ----
+    let addr: SocketAddr = "1.1.1.1:53".parse().unwrap();
+    assert!(TcpStream::connect_timeout(&addr, Duration::from_secs(2)).is_err());
+
+    // ---- 名前空間を作り直す unshare / clone は拒否される ----
+    // SAFETY: 引数は整数のみ。成功した場合は子がすぐに終了する
+    unsafe {
+        assert_eq!(libc::unshare(libc::CLONE_NEWUSER), -1);
+        assert_eq!(
+            std::io::Error::last_os_error().kind(),
+            ErrorKind::PermissionDenied
+        );
+        for flag in [libc::CLONE_NEWUSER, libc::CLONE_NEWNS, libc::CLONE_NEWNET] {
+            let pid = libc::syscall(
+                libc::SYS_clone,
+                (flag | libc::SIGCHLD) as libc::c_ulong,
+                0usize,
+                0usize,
+                0usize,
+                0usize,
+            );
+            if pid == 0 {
+                libc::_exit(0);
+            }
+            assert_eq!(pid, -1, "clone with {:#x} succeeded", flag);
+        }
+    }
+
+    // ---- rlimit が掛かっている ----
+    for (resource, expected) in [
+        (libc::RLIMIT_AS, 2048 * 1024 * 1024),
+        (libc::RLIMIT_NPROC, 64),
+        (libc::RLIMIT_FSIZE, 16 * 1024 * 1024),
+    ] {
+        let mut rlimit = libc::rlimit {
+            rlim_cur: 0,
+            rlim_max: 0,
+        };
+        // SAFETY: rlimit は有効なポインタ
+        assert_eq!(unsafe { libc::getrlimit(resource, &mut rlimit) }, 0);
+        assert_eq!(rlimit.rlim_max, expected);
+    }
+}
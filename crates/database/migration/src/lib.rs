@@ -3,6 +3,19 @@ pub use sea_orm_migration::prelude::*;
 mod m20250626_000001_create_submissions;
 mod m20250626_000002_create_test_cases;
 mod m20250626_000003_create_execution_results;
+mod m20250715_000004_add_repeat_index_to_execution_results;
+mod m20250720_000005_create_problems;
+mod m20250722_000006_add_foreign_keys;
+mod m20250725_000007_create_test_case_sets;
+mod m20250728_000008_add_test_case_metadata;
+mod m20250730_000009_backfill_best_known_scores;
+mod m20250801_000010_use_timestamptz_on_postgres;
+mod m20250803_000011_compress_execution_outputs;
+mod m20250805_000012_add_submission_tags;
+mod m20250807_000013_add_submission_metadata;
+mod m20250809_000014_add_submission_git_state;
+mod m20250811_000015_create_submission_bundles;
+mod m20250813_000016_add_submission_environment;
 
 pub struct Migrator;
 
@@ -13,6 +26,19 @@ impl MigratorTrait for Migrator {
             Box::new(m20250626_000001_create_submissions::Migration),
             Box::new(m20250626_000002_create_test_cases::Migration),
             Box::new(m20250626_000003_create_execution_results::Migration),
+            Box::new(m20250715_000004_add_repeat_index_to_execution_results::Migration),
+            Box::new(m20250720_000005_create_problems::Migration),
+            Box::new(m20250722_000006_add_foreign_keys::Migration),
+            Box::new(m20250725_000007_create_test_case_sets::Migration),
+            Box::new(m20250728_000008_add_test_case_metadata::Migration),
+            Box::new(m20250730_000009_backfill_best_known_scores::Migration),
+            Box::new(m20250801_000010_use_timestamptz_on_postgres::Migration),
+            Box::new(m20250803_000011_compress_execution_outputs::Migration),
+            Box::new(m20250805_000012_add_submission_tags::Migration),
+            Box::new(m20250807_000013_add_submission_metadata::Migration),
+            Box::new(m20250809_000014_add_submission_git_state::Migration),
+            Box::new(m20250811_000015_create_submission_bundles::Migration),
+            Box::new(m20250813_000016_add_submission_environment::Migration),
         ]
     }
 }
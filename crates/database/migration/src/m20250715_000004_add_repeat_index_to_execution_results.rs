@@ -0,0 +1,41 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // 同じ提出・ケースを `--repeat` で複数回実行したときの何回目か (0 始まり)
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(ExecutionResults::Table)
+                    .add_column(
+                        ColumnDef::new(ExecutionResults::RepeatIndex)
+                            .integer()
+                            .not_null()
+                            .default(0),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(ExecutionResults::Table)
+                    .drop_column(ExecutionResults::RepeatIndex)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum ExecutionResults {
+    Table,
+    RepeatIndex,
+}
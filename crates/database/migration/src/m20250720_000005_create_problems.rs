@@ -0,0 +1,151 @@
+use sea_orm_migration::prelude::*;
+
+/// 既存のデータを引き継ぐ問題の名前。CLI で `--problem` を省略したときもこれを使う
+const DEFAULT_PROBLEM: &str = "default";
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(Problems::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(Problems::Id)
+                            .big_integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(Problems::Name)
+                            .string()
+                            .not_null()
+                            .unique_key(),
+                    )
+                    .col(ColumnDef::new(Problems::CreatedAt).timestamp().not_null())
+                    .to_owned(),
+            )
+            .await?;
+
+        // 空のテーブルへの最初の挿入なので id は 1 になる
+        manager
+            .exec_stmt(
+                Query::insert()
+                    .into_table(Problems::Table)
+                    .columns([Problems::Name, Problems::CreatedAt])
+                    .values_panic([DEFAULT_PROBLEM.into(), Expr::current_timestamp().into()])
+                    .to_owned(),
+            )
+            .await?;
+
+        // これまでの提出とテストケースはすべて既定の問題 (id 1) に属させる
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Submissions::Table)
+                    .add_column(
+                        ColumnDef::new(Submissions::ProblemId)
+                            .integer()
+                            .not_null()
+                            .default(1),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(TestCases::Table)
+                    .add_column(
+                        ColumnDef::new(TestCases::ProblemId)
+                            .integer()
+                            .not_null()
+                            .default(1),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_submissions_problem_id")
+                    .table(Submissions::Table)
+                    .col(Submissions::ProblemId)
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_test_cases_problem_id")
+                    .table(TestCases::Table)
+                    .col(TestCases::ProblemId)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_index(
+                Index::drop()
+                    .name("idx_test_cases_problem_id")
+                    .table(TestCases::Table)
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .drop_index(
+                Index::drop()
+                    .name("idx_submissions_problem_id")
+                    .table(Submissions::Table)
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(TestCases::Table)
+                    .drop_column(TestCases::ProblemId)
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Submissions::Table)
+                    .drop_column(Submissions::ProblemId)
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .drop_table(Table::drop().table(Problems::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum Problems {
+    Table,
+    Id,
+    Name,
+    CreatedAt,
+}
+
+#[derive(DeriveIden)]
+enum Submissions {
+    Table,
+    ProblemId,
+}
+
+#[derive(DeriveIden)]
+enum TestCases {
+    Table,
+    ProblemId,
+}
@@ -0,0 +1,530 @@
+use sea_orm_migration::prelude::*;
+use sea_orm_migration::sea_orm::{ConnectionTrait, DatabaseBackend, Statement};
+
+/// 外部キー制約・索引を付け、id 列をすべて `integer` に揃える。
+///
+/// SQLite は既存の表に外部キーを追加できないため、表を `*_old` に退避して
+/// 作り直し、親の表から順に行を移す。
+///
+/// 参照先のない実行結果は制約を満たせないので、消さずに
+/// [`ORPHANED_EXECUTION_RESULTS`] へ移す。down ではそこから戻す。
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+/// 参照先の提出やテストケースがない実行結果の退避先
+const ORPHANED_EXECUTION_RESULTS: &str = "orphaned_execution_results";
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        rebuild(manager, true).await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        rebuild(manager, false).await
+    }
+}
+
+/// 4 つの表を作り直す。`foreign_keys` が false なら制約と実行結果の索引を付けない
+async fn rebuild(manager: &SchemaManager<'_>, foreign_keys: bool) -> Result<(), DbErr> {
+    // 索引は表と一緒に退避されて名前が衝突するので先に消す
+    for (name, table) in [
+        ("idx_submissions_problem_id", Submissions::Table.to_string()),
+        ("idx_test_cases_problem_id", TestCases::Table.to_string()),
+    ] {
+        manager
+            .drop_index(Index::drop().name(name).table(Alias::new(table)).to_owned())
+            .await?;
+    }
+    if !foreign_keys {
+        for name in [
+            "idx_execution_results_submission_id_test_case_id",
+            "idx_execution_results_test_case_id",
+        ] {
+            manager
+                .drop_index(
+                    Index::drop()
+                        .name(name)
+                        .table(ExecutionResults::Table)
+                        .to_owned(),
+                )
+                .await?;
+        }
+    }
+
+    for table in [
+        Problems::Table.to_string(),
+        Submissions::Table.to_string(),
+        TestCases::Table.to_string(),
+        ExecutionResults::Table.to_string(),
+    ] {
+        manager
+            .rename_table(
+                Table::rename()
+                    .table(Alias::new(&table), old_table(&table))
+                    .to_owned(),
+            )
+            .await?;
+    }
+
+    create_tables(manager, foreign_keys).await?;
+
+    copy_rows(
+        manager,
+        Problems::Table,
+        &[Problems::Id, Problems::Name, Problems::CreatedAt],
+        None,
+    )
+    .await?;
+    copy_rows(
+        manager,
+        Submissions::Table,
+        &[
+            Submissions::Id,
+            Submissions::ProblemId,
+            Submissions::SourceCode,
+            Submissions::Timestamp,
+        ],
+        None,
+    )
+    .await?;
+    copy_rows(
+        manager,
+        TestCases::Table,
+        &[
+            TestCases::Id,
+            TestCases::ProblemId,
+            TestCases::Input,
+            TestCases::Filename,
+            TestCases::CreatedAt,
+        ],
+        None,
+    )
+    .await?;
+    let execution_result_columns = [
+        ExecutionResults::Id,
+        ExecutionResults::SubmissionId,
+        ExecutionResults::TestCaseId,
+        ExecutionResults::RepeatIndex,
+        ExecutionResults::Success,
+        ExecutionResults::Stdout,
+        ExecutionResults::Stderr,
+        ExecutionResults::Score,
+        ExecutionResults::ExecutionTimeMs,
+        ExecutionResults::CreatedAt,
+    ];
+    if foreign_keys {
+        // これまでにテストケースを一括削除していると参照先のない実行結果が残っており、
+        // 制約を満たせないので別の表へ移す
+        let has_parents = Expr::col(ExecutionResults::SubmissionId)
+            .in_subquery(
+                Query::select()
+                    .column(Submissions::Id)
+                    .from(Submissions::Table)
+                    .to_owned(),
+            )
+            .and(
+                Expr::col(ExecutionResults::TestCaseId).in_subquery(
+                    Query::select()
+                        .column(TestCases::Id)
+                        .from(TestCases::Table)
+                        .to_owned(),
+                ),
+            );
+        let old = old_table(&ExecutionResults::Table.to_string());
+        let orphans = count_rows(manager, old.clone(), has_parents.clone().not()).await?;
+        if orphans > 0 {
+            manager
+                .create_table(execution_results_table(
+                    Alias::new(ORPHANED_EXECUTION_RESULTS),
+                    false,
+                ))
+                .await?;
+            insert_rows(
+                manager,
+                old,
+                Alias::new(ORPHANED_EXECUTION_RESULTS),
+                &execution_result_columns,
+                Some(has_parents.clone().not()),
+            )
+            .await?;
+            eprintln!(
+                "{} execution results reference missing submissions or test cases; \
+                 moved them to {}",
+                orphans, ORPHANED_EXECUTION_RESULTS
+            );
+        }
+        copy_rows(
+            manager,
+            ExecutionResults::Table,
+            &execution_result_columns,
+            Some(has_parents),
+        )
+        .await?;
+    } else {
+        copy_rows(
+            manager,
+            ExecutionResults::Table,
+            &execution_result_columns,
+            None,
+        )
+        .await?;
+        // up で退避した実行結果を戻す
+        if manager.has_table(ORPHANED_EXECUTION_RESULTS).await? {
+            insert_rows(
+                manager,
+                Alias::new(ORPHANED_EXECUTION_RESULTS),
+                ExecutionResults::Table,
+                &execution_result_columns,
+                None,
+            )
+            .await?;
+            manager
+                .drop_table(
+                    Table::drop()
+                        .table(Alias::new(ORPHANED_EXECUTION_RESULTS))
+                        .to_owned(),
+                )
+                .await?;
+        }
+    }
+
+    // PostgreSQL は id を明示して移しても連番が進まないので、移した行の続きから振り直す
+    if manager.get_database_backend() == DatabaseBackend::Postgres {
+        for table in [
+            Problems::Table.to_string(),
+            Submissions::Table.to_string(),
+            TestCases::Table.to_string(),
+            ExecutionResults::Table.to_string(),
+        ] {
+            manager
+                .get_connection()
+                .execute(Statement::from_string(
+                    DatabaseBackend::Postgres,
+                    format!(
+                        "SELECT setval(pg_get_serial_sequence('{table}', 'id'), \
+                         COALESCE(MAX(id), 0) + 1, false) FROM {table}"
+                    ),
+                ))
+                .await?;
+        }
+    }
+
+    // 子の表から消す (down では退避した表同士に制約が残っている)
+    for table in [
+        ExecutionResults::Table.to_string(),
+        TestCases::Table.to_string(),
+        Submissions::Table.to_string(),
+        Problems::Table.to_string(),
+    ] {
+        manager
+            .drop_table(Table::drop().table(old_table(&table)).to_owned())
+            .await?;
+    }
+
+    create_indexes(manager, foreign_keys).await
+}
+
+fn old_table(table: &str) -> Alias {
+    Alias::new(format!("{}_old", table))
+}
+
+/// 退避した表から同名の列を移す
+async fn copy_rows<T>(
+    manager: &SchemaManager<'_>,
+    table: T,
+    columns: &[T],
+    condition: Option<SimpleExpr>,
+) -> Result<(), DbErr>
+where
+    T: Iden + Copy + 'static,
+{
+    insert_rows(
+        manager,
+        old_table(&table.to_string()),
+        table,
+        columns,
+        condition,
+    )
+    .await
+}
+
+/// `from` の行のうち `condition` を満たすものを `into` へ写す
+async fn insert_rows<T>(
+    manager: &SchemaManager<'_>,
+    from: impl IntoTableRef,
+    into: impl IntoTableRef,
+    columns: &[T],
+    condition: Option<SimpleExpr>,
+) -> Result<(), DbErr>
+where
+    T: Iden + Copy + 'static,
+{
+    let mut select = Query::select();
+    select.columns(columns.iter().copied()).from(from);
+    if let Some(condition) = condition {
+        select.and_where(condition);
+    }
+
+    let insert = Query::insert()
+        .into_table(into)
+        .columns(columns.iter().copied())
+        .select_from(select)
+        .map_err(|e| DbErr::Migration(e.to_string()))?
+        .to_owned();
+    manager.exec_stmt(insert).await
+}
+
+async fn count_rows(
+    manager: &SchemaManager<'_>,
+    table: impl IntoTableRef,
+    condition: SimpleExpr,
+) -> Result<i64, DbErr> {
+    let select = Query::select()
+        .expr(Expr::col(Asterisk).count())
+        .from(table)
+        .and_where(condition)
+        .to_owned();
+    let row = manager
+        .get_connection()
+        .query_one(manager.get_database_backend().build(&select))
+        .await?;
+    match row {
+        Some(row) => row.try_get_by_index(0),
+        None => Ok(0),
+    }
+}
+
+async fn create_tables(manager: &SchemaManager<'_>, foreign_keys: bool) -> Result<(), DbErr> {
+    manager
+        .create_table(
+            Table::create()
+                .table(Problems::Table)
+                .col(
+                    ColumnDef::new(Problems::Id)
+                        .integer()
+                        .not_null()
+                        .auto_increment()
+                        .primary_key(),
+                )
+                .col(
+                    ColumnDef::new(Problems::Name)
+                        .string()
+                        .not_null()
+                        .unique_key(),
+                )
+                .col(ColumnDef::new(Problems::CreatedAt).timestamp().not_null())
+                .to_owned(),
+        )
+        .await?;
+
+    let mut submissions = Table::create()
+        .table(Submissions::Table)
+        .col(
+            ColumnDef::new(Submissions::Id)
+                .integer()
+                .not_null()
+                .auto_increment()
+                .primary_key(),
+        )
+        .col(ColumnDef::new(Submissions::ProblemId).integer().not_null())
+        .col(ColumnDef::new(Submissions::SourceCode).text().not_null())
+        .col(
+            ColumnDef::new(Submissions::Timestamp)
+                .timestamp()
+                .not_null(),
+        )
+        .to_owned();
+    let mut test_cases = Table::create()
+        .table(TestCases::Table)
+        .col(
+            ColumnDef::new(TestCases::Id)
+                .integer()
+                .not_null()
+                .auto_increment()
+                .primary_key(),
+        )
+        .col(ColumnDef::new(TestCases::ProblemId).integer().not_null())
+        .col(ColumnDef::new(TestCases::Input).text().not_null())
+        .col(ColumnDef::new(TestCases::Filename).string().not_null())
+        .col(ColumnDef::new(TestCases::CreatedAt).timestamp().not_null())
+        .to_owned();
+    let mut execution_results = execution_results_table(ExecutionResults::Table, true);
+
+    if foreign_keys {
+        // 問題は提出やケースが残っている限り消せない
+        submissions.foreign_key(
+            ForeignKey::create()
+                .name("fk_submissions_problem_id")
+                .from(Submissions::Table, Submissions::ProblemId)
+                .to(Problems::Table, Problems::Id)
+                .on_delete(ForeignKeyAction::Restrict),
+        );
+        test_cases.foreign_key(
+            ForeignKey::create()
+                .name("fk_test_cases_problem_id")
+                .from(TestCases::Table, TestCases::ProblemId)
+                .to(Problems::Table, Problems::Id)
+                .on_delete(ForeignKeyAction::Restrict),
+        );
+        // 提出を消せばその実行結果も消える
+        execution_results.foreign_key(
+            ForeignKey::create()
+                .name("fk_execution_results_submission_id")
+                .from(ExecutionResults::Table, ExecutionResults::SubmissionId)
+                .to(Submissions::Table, Submissions::Id)
+                .on_delete(ForeignKeyAction::Cascade),
+        );
+        // 実行結果から参照されているケースは消せない (過去の提出の結果が壊れるため)
+        execution_results.foreign_key(
+            ForeignKey::create()
+                .name("fk_execution_results_test_case_id")
+                .from(ExecutionResults::Table, ExecutionResults::TestCaseId)
+                .to(TestCases::Table, TestCases::Id)
+                .on_delete(ForeignKeyAction::Restrict),
+        );
+    }
+
+    manager.create_table(submissions).await?;
+    manager.create_table(test_cases).await?;
+    manager.create_table(execution_results).await
+}
+
+/// 実行結果の表の定義。`auto_increment` が false なら id を振らない (移した行の id をそのまま持つ)
+fn execution_results_table(table: impl IntoTableRef, auto_increment: bool) -> TableCreateStatement {
+    let mut id = ColumnDef::new(ExecutionResults::Id);
+    id.integer().not_null().primary_key();
+    if auto_increment {
+        id.auto_increment();
+    }
+    Table::create()
+        .table(table)
+        .col(id)
+        .col(
+            ColumnDef::new(ExecutionResults::SubmissionId)
+                .integer()
+                .not_null(),
+        )
+        .col(
+            ColumnDef::new(ExecutionResults::TestCaseId)
+                .integer()
+                .not_null(),
+        )
+        .col(
+            ColumnDef::new(ExecutionResults::RepeatIndex)
+                .integer()
+                .not_null()
+                .default(0),
+        )
+        .col(
+            ColumnDef::new(ExecutionResults::Success)
+                .boolean()
+                .not_null(),
+        )
+        .col(ColumnDef::new(ExecutionResults::Stdout).text().not_null())
+        .col(ColumnDef::new(ExecutionResults::Stderr).text().not_null())
+        .col(
+            ColumnDef::new(ExecutionResults::Score)
+                .big_integer()
+                .not_null(),
+        )
+        .col(
+            ColumnDef::new(ExecutionResults::ExecutionTimeMs)
+                .integer()
+                .not_null(),
+        )
+        .col(
+            ColumnDef::new(ExecutionResults::CreatedAt)
+                .timestamp()
+                .not_null(),
+        )
+        .to_owned()
+}
+
+async fn create_indexes(manager: &SchemaManager<'_>, foreign_keys: bool) -> Result<(), DbErr> {
+    manager
+        .create_index(
+            Index::create()
+                .name("idx_submissions_problem_id")
+                .table(Submissions::Table)
+                .col(Submissions::ProblemId)
+                .to_owned(),
+        )
+        .await?;
+    manager
+        .create_index(
+            Index::create()
+                .name("idx_test_cases_problem_id")
+                .table(TestCases::Table)
+                .col(TestCases::ProblemId)
+                .to_owned(),
+        )
+        .await?;
+
+    if !foreign_keys {
+        return Ok(());
+    }
+    // 提出ごとの結果の取得 (submission_id だけの検索もこの索引を使う)
+    manager
+        .create_index(
+            Index::create()
+                .name("idx_execution_results_submission_id_test_case_id")
+                .table(ExecutionResults::Table)
+                .col(ExecutionResults::SubmissionId)
+                .col(ExecutionResults::TestCaseId)
+                .to_owned(),
+        )
+        .await?;
+    // ケースごとの結果の取得と、ケース削除時の制約の検査
+    manager
+        .create_index(
+            Index::create()
+                .name("idx_execution_results_test_case_id")
+                .table(ExecutionResults::Table)
+                .col(ExecutionResults::TestCaseId)
+                .to_owned(),
+        )
+        .await
+}
+
+#[derive(DeriveIden, Clone, Copy)]
+enum Problems {
+    Table,
+    Id,
+    Name,
+    CreatedAt,
+}
+
+#[derive(DeriveIden, Clone, Copy)]
+enum Submissions {
+    Table,
+    Id,
+    ProblemId,
+    SourceCode,
+    Timestamp,
+}
+
+#[derive(DeriveIden, Clone, Copy)]
+enum TestCases {
+    Table,
+    Id,
+    ProblemId,
+    Input,
+    Filename,
+    CreatedAt,
+}
+
+#[derive(DeriveIden, Clone, Copy)]
+enum ExecutionResults {
+    Table,
+    Id,
+    SubmissionId,
+    TestCaseId,
+    RepeatIndex,
+    Success,
+    Stdout,
+    Stderr,
+    Score,
+    ExecutionTimeMs,
+    CreatedAt,
+}
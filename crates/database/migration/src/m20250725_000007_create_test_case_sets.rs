@@ -0,0 +1,331 @@
+use sea_orm_migration::prelude::*;
+use sea_orm_migration::sea_orm::{ConnectionTrait, Statement};
+use sha2::{Digest, Sha256};
+
+/// 既存のテストケースをまとめるセットの名前
+const INITIAL_SET: &str = "v1";
+
+/// テストケースを内容のハッシュで識別し、名前付きのセット (版) にまとめる。
+///
+/// 提出はどのセットで実行したかを記録し、セットの廃止は `archived_at` を
+/// 入れるだけにして、過去の提出の結果を参照できるまま残す。
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // 既存のケースには入力から計算したハッシュを入れる
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(TestCases::Table)
+                    .add_column(
+                        ColumnDef::new(TestCases::ContentHash)
+                            .string()
+                            .not_null()
+                            .default(""),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        let db = manager.get_connection();
+        let builder = db.get_database_backend();
+        let rows = db
+            .query_all(
+                builder.build(
+                    &Query::select()
+                        .columns([TestCases::Id, TestCases::Input])
+                        .from(TestCases::Table)
+                        .to_owned(),
+                ),
+            )
+            .await?;
+        for row in rows {
+            let id: i32 = row.try_get("", &TestCases::Id.to_string())?;
+            let input: String = row.try_get("", &TestCases::Input.to_string())?;
+            manager
+                .exec_stmt(
+                    Query::update()
+                        .table(TestCases::Table)
+                        .value(TestCases::ContentHash, content_hash(&input))
+                        .and_where(Expr::col(TestCases::Id).eq(id))
+                        .to_owned(),
+                )
+                .await?;
+        }
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_test_cases_problem_id_content_hash")
+                    .table(TestCases::Table)
+                    .col(TestCases::ProblemId)
+                    .col(TestCases::ContentHash)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(TestCaseSets::Table)
+                    .col(
+                        ColumnDef::new(TestCaseSets::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(TestCaseSets::ProblemId).integer().not_null())
+                    .col(ColumnDef::new(TestCaseSets::Name).string().not_null())
+                    .col(
+                        ColumnDef::new(TestCaseSets::CreatedAt)
+                            .timestamp()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(TestCaseSets::ArchivedAt).timestamp().null())
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_test_case_sets_problem_id")
+                            .from(TestCaseSets::Table, TestCaseSets::ProblemId)
+                            .to(Problems::Table, Problems::Id)
+                            .on_delete(ForeignKeyAction::Restrict),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_test_case_sets_problem_id_name")
+                    .table(TestCaseSets::Table)
+                    .col(TestCaseSets::ProblemId)
+                    .col(TestCaseSets::Name)
+                    .unique()
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(TestCaseSetCases::Table)
+                    .col(
+                        ColumnDef::new(TestCaseSetCases::TestCaseSetId)
+                            .integer()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(TestCaseSetCases::TestCaseId)
+                            .integer()
+                            .not_null(),
+                    )
+                    .primary_key(
+                        Index::create()
+                            .col(TestCaseSetCases::TestCaseSetId)
+                            .col(TestCaseSetCases::TestCaseId),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_test_case_set_cases_test_case_set_id")
+                            .from(TestCaseSetCases::Table, TestCaseSetCases::TestCaseSetId)
+                            .to(TestCaseSets::Table, TestCaseSets::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_test_case_set_cases_test_case_id")
+                            .from(TestCaseSetCases::Table, TestCaseSetCases::TestCaseId)
+                            .to(TestCases::Table, TestCases::Id)
+                            .on_delete(ForeignKeyAction::Restrict),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_test_case_set_cases_test_case_id")
+                    .table(TestCaseSetCases::Table)
+                    .col(TestCaseSetCases::TestCaseId)
+                    .to_owned(),
+            )
+            .await?;
+
+        // ケースのある問題ごとに、そのすべてのケースを含む最初のセットを作る
+        manager
+            .exec_stmt(
+                Query::insert()
+                    .into_table(TestCaseSets::Table)
+                    .columns([
+                        TestCaseSets::ProblemId,
+                        TestCaseSets::Name,
+                        TestCaseSets::CreatedAt,
+                    ])
+                    .select_from(
+                        Query::select()
+                            .distinct()
+                            .column(TestCases::ProblemId)
+                            .expr(Expr::val(INITIAL_SET))
+                            .expr(Expr::current_timestamp())
+                            .from(TestCases::Table)
+                            .to_owned(),
+                    )
+                    .map_err(|e| DbErr::Migration(e.to_string()))?
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .exec_stmt(
+                Query::insert()
+                    .into_table(TestCaseSetCases::Table)
+                    .columns([
+                        TestCaseSetCases::TestCaseSetId,
+                        TestCaseSetCases::TestCaseId,
+                    ])
+                    .select_from(
+                        Query::select()
+                            .column((TestCaseSets::Table, TestCaseSets::Id))
+                            .column((TestCases::Table, TestCases::Id))
+                            .from(TestCases::Table)
+                            .inner_join(
+                                TestCaseSets::Table,
+                                Expr::col((TestCaseSets::Table, TestCaseSets::ProblemId))
+                                    .equals((TestCases::Table, TestCases::ProblemId)),
+                            )
+                            .to_owned(),
+                    )
+                    .map_err(|e| DbErr::Migration(e.to_string()))?
+                    .to_owned(),
+            )
+            .await?;
+
+        // 既存の表に外部キー付きの列を足す構文は SeaQuery にないため SQL で書く
+        // (NULL を既定値にすれば SQLite でも追加できる)
+        db.execute(Statement::from_string(
+            builder,
+            "ALTER TABLE submissions ADD COLUMN test_case_set_id integer NULL \
+             REFERENCES test_case_sets (id) ON DELETE RESTRICT",
+        ))
+        .await?;
+        // これまでの提出はその問題の最初のセットで実行したものとみなす
+        manager
+            .exec_stmt(
+                Query::update()
+                    .table(Submissions::Table)
+                    .value(
+                        Submissions::TestCaseSetId,
+                        SimpleExpr::SubQuery(
+                            None,
+                            Box::new(
+                                Query::select()
+                                    .column(TestCaseSets::Id)
+                                    .from(TestCaseSets::Table)
+                                    .and_where(
+                                        Expr::col((TestCaseSets::Table, TestCaseSets::ProblemId))
+                                            .equals((Submissions::Table, Submissions::ProblemId)),
+                                    )
+                                    .to_owned()
+                                    .into_sub_query_statement(),
+                            ),
+                        ),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_submissions_test_case_set_id")
+                    .table(Submissions::Table)
+                    .col(Submissions::TestCaseSetId)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_index(
+                Index::drop()
+                    .name("idx_submissions_test_case_set_id")
+                    .table(Submissions::Table)
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Submissions::Table)
+                    .drop_column(Submissions::TestCaseSetId)
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .drop_table(Table::drop().table(TestCaseSetCases::Table).to_owned())
+            .await?;
+        manager
+            .drop_table(Table::drop().table(TestCaseSets::Table).to_owned())
+            .await?;
+        manager
+            .drop_index(
+                Index::drop()
+                    .name("idx_test_cases_problem_id_content_hash")
+                    .table(TestCases::Table)
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(TestCases::Table)
+                    .drop_column(TestCases::ContentHash)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+/// 入力の SHA-256 (16 進小文字)。heurs-database の `TestCaseRepository::content_hash` と同じ値
+fn content_hash(input: &str) -> String {
+    format!("{:x}", Sha256::digest(input.as_bytes()))
+}
+
+#[derive(DeriveIden)]
+enum Problems {
+    Table,
+    Id,
+}
+
+#[derive(DeriveIden)]
+enum Submissions {
+    Table,
+    ProblemId,
+    TestCaseSetId,
+}
+
+#[derive(DeriveIden)]
+enum TestCases {
+    Table,
+    Id,
+    ProblemId,
+    Input,
+    ContentHash,
+}
+
+#[derive(DeriveIden)]
+enum TestCaseSets {
+    Table,
+    Id,
+    ProblemId,
+    Name,
+    CreatedAt,
+    ArchivedAt,
+}
+
+#[derive(DeriveIden)]
+enum TestCaseSetCases {
+    Table,
+    TestCaseSetId,
+    TestCaseId,
+}
@@ -0,0 +1,62 @@
+use sea_orm_migration::prelude::*;
+
+/// マニフェストから取り込むテストケースの付加情報 (シード・タグ・既知の最良スコア)。
+///
+/// 入力そのものは変えないので、ケースが不変であることとは両立する。
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // SQLite は 1 文で複数の列を足せないので 1 列ずつ追加する
+        for column in [
+            ColumnDef::new(TestCases::Seed)
+                .big_integer()
+                .null()
+                .to_owned(),
+            // カンマ区切り。タグがなければ空文字列
+            ColumnDef::new(TestCases::Tags)
+                .string()
+                .not_null()
+                .default("")
+                .to_owned(),
+            ColumnDef::new(TestCases::BestKnownScore)
+                .big_integer()
+                .null()
+                .to_owned(),
+        ] {
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(TestCases::Table)
+                        .add_column(column)
+                        .to_owned(),
+                )
+                .await?;
+        }
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        for column in [TestCases::BestKnownScore, TestCases::Tags, TestCases::Seed] {
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(TestCases::Table)
+                        .drop_column(column)
+                        .to_owned(),
+                )
+                .await?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum TestCases {
+    Table,
+    Seed,
+    Tags,
+    BestKnownScore,
+}
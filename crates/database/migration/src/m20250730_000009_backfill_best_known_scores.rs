@@ -0,0 +1,64 @@
+use sea_orm_migration::prelude::*;
+
+/// 既知の最良スコアを、これまでに保存された成功した実行結果の最大スコアで埋める。
+///
+/// 以降は実行結果を保存するたびに更新されるので、ここでは既存のデータだけを扱う。
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let best_result = Query::select()
+            .expr(Expr::col(ExecutionResults::Score).max())
+            .from(ExecutionResults::Table)
+            .and_where(
+                Expr::col((ExecutionResults::Table, ExecutionResults::TestCaseId))
+                    .equals((TestCases::Table, TestCases::Id)),
+            )
+            .and_where(Expr::col(ExecutionResults::Success).eq(true))
+            .to_owned();
+        let best_result = || {
+            SimpleExpr::SubQuery(
+                None,
+                Box::new(best_result.clone().into_sub_query_statement()),
+            )
+        };
+
+        // マニフェストで取り込んだ値のほうが良ければそちらを残す
+        manager
+            .exec_stmt(
+                Query::update()
+                    .table(TestCases::Table)
+                    .value(TestCases::BestKnownScore, best_result())
+                    .cond_where(
+                        Cond::any()
+                            .add(Expr::col(TestCases::BestKnownScore).is_null())
+                            .add(Expr::col(TestCases::BestKnownScore).lt(best_result())),
+                    )
+                    .and_where(best_result().is_not_null())
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, _manager: &SchemaManager) -> Result<(), DbErr> {
+        // 値を埋めただけなので戻すものはない
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum TestCases {
+    Table,
+    Id,
+    BestKnownScore,
+}
+
+#[derive(DeriveIden)]
+enum ExecutionResults {
+    Table,
+    TestCaseId,
+    Success,
+    Score,
+}
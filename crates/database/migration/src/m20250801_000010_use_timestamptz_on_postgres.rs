@@ -0,0 +1,51 @@
+use sea_orm_migration::prelude::*;
+use sea_orm_migration::sea_orm::{ConnectionTrait, DatabaseBackend, Statement};
+
+/// PostgreSQL の日時列をタイムゾーン付き (`timestamptz`) にする。
+///
+/// エンティティは日時を `DateTimeUtc` で扱うが、PostgreSQL ではタイムゾーンなしの
+/// `timestamp` 列をそれとして読めない。保存済みの値は UTC とみなす。
+/// SQLite は日時を文字列で持つので何もしない。
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+/// (表, 列) の組
+const COLUMNS: &[(&str, &str)] = &[
+    ("problems", "created_at"),
+    ("submissions", "timestamp"),
+    ("test_cases", "created_at"),
+    ("execution_results", "created_at"),
+    ("test_case_sets", "created_at"),
+    ("test_case_sets", "archived_at"),
+];
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        alter_columns(manager, "timestamp with time zone").await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        alter_columns(manager, "timestamp without time zone").await
+    }
+}
+
+async fn alter_columns(manager: &SchemaManager<'_>, column_type: &str) -> Result<(), DbErr> {
+    if manager.get_database_backend() != DatabaseBackend::Postgres {
+        return Ok(());
+    }
+
+    for (table, column) in COLUMNS {
+        manager
+            .get_connection()
+            .execute(Statement::from_string(
+                DatabaseBackend::Postgres,
+                format!(
+                    "ALTER TABLE {table} ALTER COLUMN \"{column}\" TYPE {column_type} \
+                     USING \"{column}\" AT TIME ZONE 'UTC'"
+                ),
+            ))
+            .await?;
+    }
+    Ok(())
+}
@@ -0,0 +1,112 @@
+use sea_orm_migration::prelude::*;
+use sea_orm_migration::sea_orm::ConnectionTrait;
+
+/// 実行結果の stdout / stderr を zstd で圧縮して保存できるようにする。
+///
+/// 既存の行は `plain` のまま残る (`heurs db compact` で圧縮できる)。
+/// 戻すときは圧縮済みの出力を展開して Text 列に書き戻す。
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // SQLite は 1 文で複数の列を足せないので 1 列ずつ追加する
+        for column in [
+            // "plain" なら stdout / stderr 列、"zstd" なら *_zstd 列に入っている
+            ColumnDef::new(ExecutionResults::OutputEncoding)
+                .string()
+                .not_null()
+                .default("plain")
+                .to_owned(),
+            ColumnDef::new(ExecutionResults::StdoutZstd)
+                .blob()
+                .null()
+                .to_owned(),
+            ColumnDef::new(ExecutionResults::StderrZstd)
+                .blob()
+                .null()
+                .to_owned(),
+        ] {
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(ExecutionResults::Table)
+                        .add_column(column)
+                        .to_owned(),
+                )
+                .await?;
+        }
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+        let backend = manager.get_database_backend();
+
+        let rows = db
+            .query_all(
+                backend.build(
+                    Query::select()
+                        .columns([
+                            ExecutionResults::Id,
+                            ExecutionResults::StdoutZstd,
+                            ExecutionResults::StderrZstd,
+                        ])
+                        .from(ExecutionResults::Table)
+                        .and_where(Expr::col(ExecutionResults::OutputEncoding).eq("zstd")),
+                ),
+            )
+            .await?;
+        for row in rows {
+            let id: i32 = row.try_get("", "id")?;
+            let stdout = decompress(row.try_get("", "stdout_zstd")?)?;
+            let stderr = decompress(row.try_get("", "stderr_zstd")?)?;
+            db.execute(
+                backend.build(
+                    Query::update()
+                        .table(ExecutionResults::Table)
+                        .value(ExecutionResults::Stdout, stdout)
+                        .value(ExecutionResults::Stderr, stderr)
+                        .and_where(Expr::col(ExecutionResults::Id).eq(id)),
+                ),
+            )
+            .await?;
+        }
+
+        for column in [
+            ExecutionResults::StderrZstd,
+            ExecutionResults::StdoutZstd,
+            ExecutionResults::OutputEncoding,
+        ] {
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(ExecutionResults::Table)
+                        .drop_column(column)
+                        .to_owned(),
+                )
+                .await?;
+        }
+        Ok(())
+    }
+}
+
+fn decompress(data: Option<Vec<u8>>) -> Result<String, DbErr> {
+    let Some(data) = data else {
+        return Ok(String::new());
+    };
+    let bytes = zstd::decode_all(data.as_slice()).map_err(|e| DbErr::Custom(e.to_string()))?;
+    Ok(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+#[derive(DeriveIden)]
+enum ExecutionResults {
+    Table,
+    Id,
+    Stdout,
+    Stderr,
+    OutputEncoding,
+    StdoutZstd,
+    StderrZstd,
+}
@@ -0,0 +1,44 @@
+use sea_orm_migration::prelude::*;
+
+/// 提出にタグを付けられるようにする。
+///
+/// `heurs prune --keep-tagged` はタグの付いた提出を残す。
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Submissions::Table)
+                    // カンマ区切り。タグがなければ空文字列
+                    .add_column(
+                        ColumnDef::new(Submissions::Tags)
+                            .string()
+                            .not_null()
+                            .default(""),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Submissions::Table)
+                    .drop_column(Submissions::Tags)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum Submissions {
+    Table,
+    Tags,
+}
@@ -0,0 +1,68 @@
+use sea_orm_migration::prelude::*;
+
+/// 提出のタイトル・メモ・作者と、どの提出から派生したか (`parent_id`)。
+///
+/// SQLite では列の追加と同時に外部キーを張れないので、`parent_id` は参照制約なしの列にする。
+/// 親を削除したときは `SubmissionRepository::delete` が子の `parent_id` を外す。
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // SQLite は 1 文で複数の列を足せないので 1 列ずつ追加する
+        for column in [
+            ColumnDef::new(Submissions::Title)
+                .string()
+                .null()
+                .to_owned(),
+            ColumnDef::new(Submissions::Notes).text().null().to_owned(),
+            ColumnDef::new(Submissions::Author)
+                .string()
+                .null()
+                .to_owned(),
+            ColumnDef::new(Submissions::ParentId)
+                .integer()
+                .null()
+                .to_owned(),
+        ] {
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(Submissions::Table)
+                        .add_column(column)
+                        .to_owned(),
+                )
+                .await?;
+        }
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        for column in [
+            Submissions::ParentId,
+            Submissions::Author,
+            Submissions::Notes,
+            Submissions::Title,
+        ] {
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(Submissions::Table)
+                        .drop_column(column)
+                        .to_owned(),
+                )
+                .await?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum Submissions {
+    Table,
+    Title,
+    Notes,
+    Author,
+    ParentId,
+}
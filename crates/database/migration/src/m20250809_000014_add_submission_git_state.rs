@@ -0,0 +1,79 @@
+use sea_orm_migration::prelude::*;
+
+/// 提出したファイルのパスと、`heurs run` を実行したときの git リポジトリの状態。
+///
+/// `git_diff` は HEAD からの未コミットの変更 (追跡していないファイルを含む) で、
+/// `heurs submission checkout` はコミットを取り出したうえでこれを当てて元のソースを復元する。
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // SQLite は 1 文で複数の列を足せないので 1 列ずつ追加する
+        for column in [
+            // git リポジトリ内ならリポジトリのルートからの相対パス、そうでなければファイル名
+            ColumnDef::new(Submissions::SourcePath)
+                .string()
+                .null()
+                .to_owned(),
+            ColumnDef::new(Submissions::GitCommit)
+                .string()
+                .null()
+                .to_owned(),
+            ColumnDef::new(Submissions::GitBranch)
+                .string()
+                .null()
+                .to_owned(),
+            ColumnDef::new(Submissions::GitDirty)
+                .boolean()
+                .not_null()
+                .default(false)
+                .to_owned(),
+            ColumnDef::new(Submissions::GitDiff)
+                .text()
+                .null()
+                .to_owned(),
+        ] {
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(Submissions::Table)
+                        .add_column(column)
+                        .to_owned(),
+                )
+                .await?;
+        }
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        for column in [
+            Submissions::GitDiff,
+            Submissions::GitDirty,
+            Submissions::GitBranch,
+            Submissions::GitCommit,
+            Submissions::SourcePath,
+        ] {
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(Submissions::Table)
+                        .drop_column(column)
+                        .to_owned(),
+                )
+                .await?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum Submissions {
+    Table,
+    SourcePath,
+    GitCommit,
+    GitBranch,
+    GitDirty,
+    GitDiff,
+}
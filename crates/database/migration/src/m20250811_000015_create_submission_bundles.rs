@@ -0,0 +1,57 @@
+use sea_orm_migration::prelude::*;
+
+/// 複数ファイルの提出 (バンドル)。
+///
+/// 提出一覧で読み込まないよう `submissions` とは別のテーブルに置く。
+/// `archive` はファイル一式の tar.gz で、`entry` はその中のエントリポイントのパス。
+/// `submissions.source_code` にはエントリポイントの内容を入れておく。
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(SubmissionBundles::Table)
+                    .col(
+                        ColumnDef::new(SubmissionBundles::SubmissionId)
+                            .integer()
+                            .not_null()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(SubmissionBundles::Entry).string().not_null())
+                    .col(ColumnDef::new(SubmissionBundles::Archive).blob().not_null())
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_submission_bundles_submission_id")
+                            .from(SubmissionBundles::Table, SubmissionBundles::SubmissionId)
+                            .to(Submissions::Table, Submissions::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(SubmissionBundles::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum SubmissionBundles {
+    Table,
+    SubmissionId,
+    Entry,
+    Archive,
+}
+
+#[derive(DeriveIden)]
+enum Submissions {
+    Table,
+    Id,
+}
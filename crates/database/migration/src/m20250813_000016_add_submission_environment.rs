@@ -0,0 +1,102 @@
+use sea_orm_migration::prelude::*;
+
+/// 提出を実行した環境 (マシン・ツールチェイン・実行方法)。
+///
+/// 実行時間に依存する解法はマシンによってスコアが変わるので、
+/// 違う環境で実行した提出同士を比べるときに警告できるよう記録しておく。
+/// 既存の提出は環境が分からないので、すべて NULL を許す。
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // SQLite は 1 文で複数の列を足せないので 1 列ずつ追加する
+        for column in [
+            ColumnDef::new(Submissions::Hostname)
+                .string()
+                .null()
+                .to_owned(),
+            ColumnDef::new(Submissions::CpuModel)
+                .string()
+                .null()
+                .to_owned(),
+            ColumnDef::new(Submissions::CpuCores)
+                .integer()
+                .null()
+                .to_owned(),
+            // OS 名とカーネルのバージョン
+            ColumnDef::new(Submissions::Os).string().null().to_owned(),
+            // `g++ --version` などの 1 行目
+            ColumnDef::new(Submissions::CompilerVersion)
+                .string()
+                .null()
+                .to_owned(),
+            ColumnDef::new(Submissions::CompileCmd)
+                .text()
+                .null()
+                .to_owned(),
+            ColumnDef::new(Submissions::ExecCmd)
+                .text()
+                .null()
+                .to_owned(),
+            // local / container / aws / remote
+            ColumnDef::new(Submissions::Runner)
+                .string()
+                .null()
+                .to_owned(),
+            ColumnDef::new(Submissions::Parallelism)
+                .integer()
+                .null()
+                .to_owned(),
+        ] {
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(Submissions::Table)
+                        .add_column(column)
+                        .to_owned(),
+                )
+                .await?;
+        }
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        for column in [
+            Submissions::Parallelism,
+            Submissions::Runner,
+            Submissions::ExecCmd,
+            Submissions::CompileCmd,
+            Submissions::CompilerVersion,
+            Submissions::Os,
+            Submissions::CpuCores,
+            Submissions::CpuModel,
+            Submissions::Hostname,
+        ] {
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(Submissions::Table)
+                        .drop_column(column)
+                        .to_owned(),
+                )
+                .await?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum Submissions {
+    Table,
+    Hostname,
+    CpuModel,
+    CpuCores,
+    Os,
+    CompilerVersion,
+    CompileCmd,
+    ExecCmd,
+    Runner,
+    Parallelism,
+}
@@ -7,8 +7,9 @@ use sea_orm::entity::prelude::*;
 pub struct Model {
     #[sea_orm(primary_key)]
     pub id: i32,
-    pub submission_id: i64,
-    pub test_case_id: i64,
+    pub submission_id: i32,
+    pub test_case_id: i32,
+    pub repeat_index: i32,
     pub success: bool,
     #[sea_orm(column_type = "Text")]
     pub stdout: String,
@@ -17,9 +18,43 @@ pub struct Model {
     pub score: i64,
     pub execution_time_ms: i32,
     pub created_at: DateTimeUtc,
+    pub output_encoding: String,
+    #[sea_orm(column_type = "Blob", nullable)]
+    pub stdout_zstd: Option<Vec<u8>>,
+    #[sea_orm(column_type = "Blob", nullable)]
+    pub stderr_zstd: Option<Vec<u8>>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
-pub enum Relation {}
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "super::submissions::Entity",
+        from = "Column::SubmissionId",
+        to = "super::submissions::Column::Id",
+        on_update = "NoAction",
+        on_delete = "Cascade"
+    )]
+    Submissions,
+    #[sea_orm(
+        belongs_to = "super::test_cases::Entity",
+        from = "Column::TestCaseId",
+        to = "super::test_cases::Column::Id",
+        on_update = "NoAction",
+        on_delete = "Restrict"
+    )]
+    TestCases,
+}
+
+impl Related<super::submissions::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Submissions.def()
+    }
+}
+
+impl Related<super::test_cases::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::TestCases.def()
+    }
+}
 
 impl ActiveModelBehavior for ActiveModel {}
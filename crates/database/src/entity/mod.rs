@@ -3,5 +3,9 @@
 pub mod prelude;
 
 pub mod execution_results;
+pub mod problems;
+pub mod submission_bundles;
 pub mod submissions;
+pub mod test_case_set_cases;
+pub mod test_case_sets;
 pub mod test_cases;
@@ -1,5 +1,9 @@
 //! `SeaORM` Entity, @generated by sea-orm-codegen 1.1.12
 
 pub use super::execution_results::Entity as ExecutionResults;
+pub use super::problems::Entity as Problems;
+pub use super::submission_bundles::Entity as SubmissionBundles;
 pub use super::submissions::Entity as Submissions;
+pub use super::test_case_set_cases::Entity as TestCaseSetCases;
+pub use super::test_case_sets::Entity as TestCaseSets;
 pub use super::test_cases::Entity as TestCases;
@@ -7,12 +7,82 @@ use sea_orm::entity::prelude::*;
 pub struct Model {
     #[sea_orm(primary_key)]
     pub id: i32,
+    pub problem_id: i32,
+    pub test_case_set_id: Option<i32>,
     #[sea_orm(column_type = "Text")]
     pub source_code: String,
     pub timestamp: DateTimeUtc,
+    pub tags: String,
+    pub title: Option<String>,
+    #[sea_orm(column_type = "Text", nullable)]
+    pub notes: Option<String>,
+    pub author: Option<String>,
+    pub parent_id: Option<i32>,
+    pub source_path: Option<String>,
+    pub git_commit: Option<String>,
+    pub git_branch: Option<String>,
+    pub git_dirty: bool,
+    #[sea_orm(column_type = "Text", nullable)]
+    pub git_diff: Option<String>,
+    pub hostname: Option<String>,
+    pub cpu_model: Option<String>,
+    pub cpu_cores: Option<i32>,
+    pub os: Option<String>,
+    pub compiler_version: Option<String>,
+    #[sea_orm(column_type = "Text", nullable)]
+    pub compile_cmd: Option<String>,
+    #[sea_orm(column_type = "Text", nullable)]
+    pub exec_cmd: Option<String>,
+    pub runner: Option<String>,
+    pub parallelism: Option<i32>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
-pub enum Relation {}
+pub enum Relation {
+    #[sea_orm(has_many = "super::execution_results::Entity")]
+    ExecutionResults,
+    #[sea_orm(
+        belongs_to = "super::problems::Entity",
+        from = "Column::ProblemId",
+        to = "super::problems::Column::Id",
+        on_update = "NoAction",
+        on_delete = "Restrict"
+    )]
+    Problems,
+    #[sea_orm(has_one = "super::submission_bundles::Entity")]
+    SubmissionBundles,
+    #[sea_orm(
+        belongs_to = "super::test_case_sets::Entity",
+        from = "Column::TestCaseSetId",
+        to = "super::test_case_sets::Column::Id",
+        on_update = "NoAction",
+        on_delete = "Restrict"
+    )]
+    TestCaseSets,
+}
+
+impl Related<super::execution_results::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::ExecutionResults.def()
+    }
+}
+
+impl Related<super::problems::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Problems.def()
+    }
+}
+
+impl Related<super::submission_bundles::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::SubmissionBundles.def()
+    }
+}
+
+impl Related<super::test_case_sets::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::TestCaseSets.def()
+    }
+}
 
 impl ActiveModelBehavior for ActiveModel {}
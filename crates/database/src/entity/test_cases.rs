@@ -7,13 +7,58 @@ use sea_orm::entity::prelude::*;
 pub struct Model {
     #[sea_orm(primary_key)]
     pub id: i32,
+    pub problem_id: i32,
     #[sea_orm(column_type = "Text")]
     pub input: String,
     pub filename: String,
+    pub content_hash: String,
     pub created_at: DateTimeUtc,
+    pub seed: Option<i64>,
+    pub tags: String,
+    pub best_known_score: Option<i64>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
-pub enum Relation {}
+pub enum Relation {
+    #[sea_orm(has_many = "super::execution_results::Entity")]
+    ExecutionResults,
+    #[sea_orm(
+        belongs_to = "super::problems::Entity",
+        from = "Column::ProblemId",
+        to = "super::problems::Column::Id",
+        on_update = "NoAction",
+        on_delete = "Restrict"
+    )]
+    Problems,
+    #[sea_orm(has_many = "super::test_case_set_cases::Entity")]
+    TestCaseSetCases,
+}
+
+impl Related<super::execution_results::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::ExecutionResults.def()
+    }
+}
+
+impl Related<super::problems::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Problems.def()
+    }
+}
+
+impl Related<super::test_case_set_cases::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::TestCaseSetCases.def()
+    }
+}
+
+impl Related<super::test_case_sets::Entity> for Entity {
+    fn to() -> RelationDef {
+        super::test_case_set_cases::Relation::TestCaseSets.def()
+    }
+    fn via() -> Option<RelationDef> {
+        Some(super::test_case_set_cases::Relation::TestCases.def().rev())
+    }
+}
 
 impl ActiveModelBehavior for ActiveModel {}
@@ -4,10 +4,20 @@
 pub use crate::entity::execution_results::{
     Entity as ExecutionResult, Model as ExecutionResultModel,
 };
+pub use crate::entity::problems::{Entity as Problem, Model as ProblemModel};
 pub use crate::entity::submissions::{Entity as Submission, Model as SubmissionModel};
+pub use crate::entity::test_case_sets::{Entity as TestCaseSet, Model as TestCaseSetModel};
 pub use crate::entity::test_cases::{Entity as TestCase, Model as TestCaseModel};
 
 // Repository exports
-pub use crate::repository::execution_result::ExecutionResultRepository;
-pub use crate::repository::submission::SubmissionRepository;
+pub use crate::repository::execution_result::{
+    ExecutionOutput, ExecutionResultRepository, ExecutionResultSummary, NewExecutionResult,
+    SubmissionAggregate,
+};
+pub use crate::repository::problem::{DEFAULT_PROBLEM, ProblemRepository};
+pub use crate::repository::submission::{
+    Environment, GitState, PrunePolicy, SubmissionBundle, SubmissionEdit, SubmissionMetadata,
+    SubmissionRepository, SubmissionSummary,
+};
 pub use crate::repository::test_case::TestCaseRepository;
+pub use crate::repository::test_case_set::TestCaseSetRepository;
@@ -1,47 +1,278 @@
 use crate::entity::execution_results;
+use sea_orm::sea_query::{Alias, Expr, Func, SimpleExpr};
 use sea_orm::*;
 
+/// stdout と stderr の合計がこのバイト数以上なら zstd で圧縮して保存する
+pub const OUTPUT_COMPRESSION_THRESHOLD: usize = 4 * 1024;
+
+const PLAIN: &str = "plain";
+const ZSTD: &str = "zstd";
+
+/// stdout / stderr を除いた実行結果。集計や一覧ではこちらを使う
+#[derive(Debug, Clone, FromQueryResult)]
+pub struct ExecutionResultSummary {
+    pub id: i32,
+    pub submission_id: i32,
+    pub test_case_id: i32,
+    pub repeat_index: i32,
+    pub success: bool,
+    pub score: i64,
+    pub execution_time_ms: i32,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+}
+
+/// 提出ごとの実行結果の集計 (`GROUP BY submission_id`)
+#[derive(Debug, Clone, FromQueryResult)]
+pub struct SubmissionAggregate {
+    pub submission_id: i32,
+    /// 実行回数 (繰り返し実行した分も数える)
+    pub number_of_results: i64,
+    /// 実行したケースの数 (同じケースは 1 件として数える)
+    pub number_of_test_cases: i64,
+    pub total_score: i64,
+    pub average_score: f64,
+    pub average_execution_time_ms: f64,
+    /// 失敗した (success でない) 実行の数
+    pub number_of_failures: i64,
+}
+
+/// これから保存する 1 回分の実行結果 (`SubmissionRepository::create_with_results` に渡す)
+#[derive(Debug, Clone)]
+pub struct NewExecutionResult {
+    pub test_case_id: i32,
+    pub repeat_index: u32,
+    pub success: bool,
+    pub stdout: String,
+    pub stderr: String,
+    pub score: i64,
+    pub execution_time_ms: u32,
+}
+
+/// 展開済みの stdout / stderr
+#[derive(Debug, Clone)]
+pub struct ExecutionOutput {
+    pub stdout: String,
+    pub stderr: String,
+}
+
 pub struct ExecutionResultRepository;
 
 impl ExecutionResultRepository {
-    pub async fn create(
-        db: &DatabaseConnection,
-        submission_id: i64,
-        test_case_id: i64,
+    #[allow(clippy::too_many_arguments)]
+    pub async fn create<C: ConnectionTrait>(
+        db: &C,
+        submission_id: i32,
+        test_case_id: i32,
+        repeat_index: u32,
         success: bool,
         stdout: String,
         stderr: String,
         score: i64,
         execution_time_ms: u32,
     ) -> Result<execution_results::Model, DbErr> {
-        let result = execution_results::ActiveModel {
+        let mut result = execution_results::ActiveModel {
             submission_id: Set(submission_id),
             test_case_id: Set(test_case_id),
+            repeat_index: Set(repeat_index as i32),
             success: Set(success),
-            stdout: Set(stdout),
-            stderr: Set(stderr),
             score: Set(score),
             execution_time_ms: Set(execution_time_ms as i32),
             created_at: Set(chrono::Utc::now()),
             ..Default::default()
         };
+        set_output(&mut result, stdout, stderr)?;
 
         result.insert(db).await
     }
 
-    pub async fn find_by_submission_id(
+    /// 保存形式によらず stdout / stderr を取り出す
+    pub fn output(model: &execution_results::Model) -> Result<ExecutionOutput, DbErr> {
+        if model.output_encoding == ZSTD {
+            Ok(ExecutionOutput {
+                stdout: decompress(model.stdout_zstd.as_deref())?,
+                stderr: decompress(model.stderr_zstd.as_deref())?,
+            })
+        } else {
+            Ok(ExecutionOutput {
+                stdout: model.stdout.clone(),
+                stderr: model.stderr.clone(),
+            })
+        }
+    }
+
+    /// 1 回分の実行の stdout / stderr を読み込む
+    pub async fn find_output(
         db: &DatabaseConnection,
-        submission_id: i64,
-    ) -> Result<Vec<execution_results::Model>, DbErr> {
+        submission_id: i32,
+        test_case_id: i32,
+        repeat_index: i32,
+    ) -> Result<Option<ExecutionOutput>, DbErr> {
         execution_results::Entity::find()
             .filter(execution_results::Column::SubmissionId.eq(submission_id))
+            .filter(execution_results::Column::TestCaseId.eq(test_case_id))
+            .filter(execution_results::Column::RepeatIndex.eq(repeat_index))
+            .one(db)
+            .await?
+            .map(|model| Self::output(&model))
+            .transpose()
+    }
+
+    /// 提出の実行結果を stdout / stderr を読まずに取得する
+    pub async fn find_summaries_by_submission_id(
+        db: &DatabaseConnection,
+        submission_id: i32,
+    ) -> Result<Vec<ExecutionResultSummary>, DbErr> {
+        Self::find_summaries_by_submission_ids(db, vec![submission_id]).await
+    }
+
+    /// 複数の提出の実行結果を stdout / stderr を読まずに取得する
+    pub async fn find_summaries_by_submission_ids(
+        db: &DatabaseConnection,
+        submission_ids: Vec<i32>,
+    ) -> Result<Vec<ExecutionResultSummary>, DbErr> {
+        execution_results::Entity::find()
+            .select_only()
+            .columns([
+                execution_results::Column::Id,
+                execution_results::Column::SubmissionId,
+                execution_results::Column::TestCaseId,
+                execution_results::Column::RepeatIndex,
+                execution_results::Column::Success,
+                execution_results::Column::Score,
+                execution_results::Column::ExecutionTimeMs,
+                execution_results::Column::CreatedAt,
+            ])
+            .filter(execution_results::Column::SubmissionId.is_in(submission_ids))
+            .order_by_asc(execution_results::Column::Id)
+            .into_model::<ExecutionResultSummary>()
+            .all(db)
+            .await
+    }
+
+    /// 提出ごとに件数・スコアの合計と平均・平均実行時間・失敗数を DB 側で集計する
+    ///
+    /// `test_case_ids` を渡すとそのケースの結果だけを集計する。結果がない提出は含まれない。
+    pub async fn aggregate_by_submission(
+        db: &DatabaseConnection,
+        submission_ids: Vec<i32>,
+        test_case_ids: Option<Vec<i32>>,
+    ) -> Result<Vec<SubmissionAggregate>, DbErr> {
+        // PostgreSQL では bigint の SUM / AVG が numeric になるので型をそろえる
+        let double = || Alias::new("DOUBLE PRECISION");
+        let mut query = execution_results::Entity::find()
+            .select_only()
+            .column(execution_results::Column::SubmissionId)
+            .column_as(
+                Expr::col(execution_results::Column::Id).count(),
+                "number_of_results",
+            )
+            .column_as(
+                Expr::col(execution_results::Column::TestCaseId).count_distinct(),
+                "number_of_test_cases",
+            )
+            .column_as(
+                SimpleExpr::from(Func::cast_as(
+                    Func::sum(Expr::col(execution_results::Column::Score)),
+                    Alias::new("BIGINT"),
+                )),
+                "total_score",
+            )
+            .column_as(
+                SimpleExpr::from(Func::cast_as(
+                    Func::avg(Expr::col(execution_results::Column::Score)),
+                    double(),
+                )),
+                "average_score",
+            )
+            .column_as(
+                SimpleExpr::from(Func::cast_as(
+                    Func::avg(Expr::col(execution_results::Column::ExecutionTimeMs)),
+                    double(),
+                )),
+                "average_execution_time_ms",
+            )
+            .column_as(
+                SimpleExpr::from(Func::sum(
+                    Expr::case(Expr::col(execution_results::Column::Success).eq(true), 0)
+                        .finally(1),
+                )),
+                "number_of_failures",
+            )
+            .filter(execution_results::Column::SubmissionId.is_in(submission_ids));
+        if let Some(test_case_ids) = test_case_ids {
+            query = query.filter(execution_results::Column::TestCaseId.is_in(test_case_ids));
+        }
+
+        query
+            .group_by(execution_results::Column::SubmissionId)
+            .order_by_asc(execution_results::Column::SubmissionId)
+            .into_model::<SubmissionAggregate>()
             .all(db)
             .await
     }
 
+    /// 提出の実行結果から stdout / stderr を消し、スコアと実行時間だけを残す
+    pub async fn clear_outputs(
+        db: &DatabaseConnection,
+        submission_ids: Vec<i32>,
+    ) -> Result<u64, DbErr> {
+        let result = execution_results::Entity::update_many()
+            .col_expr(execution_results::Column::Stdout, Expr::value(""))
+            .col_expr(execution_results::Column::Stderr, Expr::value(""))
+            .col_expr(
+                execution_results::Column::OutputEncoding,
+                Expr::value(PLAIN),
+            )
+            .col_expr(
+                execution_results::Column::StdoutZstd,
+                Expr::value(Option::<Vec<u8>>::None),
+            )
+            .col_expr(
+                execution_results::Column::StderrZstd,
+                Expr::value(Option::<Vec<u8>>::None),
+            )
+            .filter(execution_results::Column::SubmissionId.is_in(submission_ids))
+            .exec(db)
+            .await?;
+        Ok(result.rows_affected)
+    }
+
+    /// 圧縮せずに保存された大きな出力を圧縮し直し、圧縮した件数を返す
+    pub async fn compress_outputs(db: &DatabaseConnection) -> Result<u64, DbErr> {
+        const BATCH: u64 = 200;
+        let mut compressed = 0;
+        let mut last_id = 0;
+        loop {
+            let batch = execution_results::Entity::find()
+                .filter(execution_results::Column::OutputEncoding.eq(PLAIN))
+                .filter(execution_results::Column::Id.gt(last_id))
+                .order_by_asc(execution_results::Column::Id)
+                .limit(BATCH)
+                .all(db)
+                .await?;
+            let Some(last) = batch.last() else {
+                break;
+            };
+            last_id = last.id;
+
+            for model in batch {
+                if model.stdout.len() + model.stderr.len() < OUTPUT_COMPRESSION_THRESHOLD {
+                    continue;
+                }
+                let stdout = model.stdout.clone();
+                let stderr = model.stderr.clone();
+                let mut result: execution_results::ActiveModel = model.into();
+                set_output(&mut result, stdout, stderr)?;
+                result.update(db).await?;
+                compressed += 1;
+            }
+        }
+        Ok(compressed)
+    }
+
     pub async fn find_by_test_case_id(
         db: &DatabaseConnection,
-        test_case_id: i64,
+        test_case_id: i32,
     ) -> Result<Vec<execution_results::Model>, DbErr> {
         execution_results::Entity::find()
             .filter(execution_results::Column::TestCaseId.eq(test_case_id))
@@ -51,17 +282,203 @@ impl ExecutionResultRepository {
 
     pub async fn find_by_submission_and_test_case(
         db: &DatabaseConnection,
-        submission_id: i64,
-        test_case_id: i64,
-    ) -> Result<Option<execution_results::Model>, DbErr> {
+        submission_id: i32,
+        test_case_id: i32,
+    ) -> Result<Vec<execution_results::Model>, DbErr> {
         execution_results::Entity::find()
             .filter(execution_results::Column::SubmissionId.eq(submission_id))
             .filter(execution_results::Column::TestCaseId.eq(test_case_id))
-            .one(db)
+            .order_by_asc(execution_results::Column::RepeatIndex)
+            .all(db)
             .await
     }
+}
+
+/// 大きな出力は圧縮して `*_zstd` 列に、小さな出力はそのまま Text 列に入れる
+fn set_output(
+    result: &mut execution_results::ActiveModel,
+    stdout: String,
+    stderr: String,
+) -> Result<(), DbErr> {
+    if stdout.len() + stderr.len() < OUTPUT_COMPRESSION_THRESHOLD {
+        result.output_encoding = Set(PLAIN.to_string());
+        result.stdout = Set(stdout);
+        result.stderr = Set(stderr);
+        result.stdout_zstd = Set(None);
+        result.stderr_zstd = Set(None);
+    } else {
+        result.output_encoding = Set(ZSTD.to_string());
+        result.stdout = Set(String::new());
+        result.stderr = Set(String::new());
+        result.stdout_zstd = Set(Some(compress(&stdout)?));
+        result.stderr_zstd = Set(Some(compress(&stderr)?));
+    }
+    Ok(())
+}
+
+fn compress(text: &str) -> Result<Vec<u8>, DbErr> {
+    zstd::encode_all(text.as_bytes(), 0).map_err(|e| DbErr::Custom(e.to_string()))
+}
+
+fn decompress(data: Option<&[u8]>) -> Result<String, DbErr> {
+    let Some(data) = data else {
+        return Ok(String::new());
+    };
+    let bytes = zstd::decode_all(data).map_err(|e| DbErr::Custom(e.to_string()))?;
+    String::from_utf8(bytes).map_err(|e| DbErr::Custom(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        DatabaseManager, ProblemRepository, SubmissionRepository, TestCaseRepository,
+        TestCaseSetRepository,
+    };
+
+    /// 結果を 1 件保存し、`find_output` で読み戻した出力が元と一致することを確かめる
+    async fn round_trip(stdout: String, stderr: String) -> execution_results::Model {
+        let (db, _) = DatabaseManager::connect_and_migrate("sqlite::memory:")
+            .await
+            .unwrap();
+        let problem = ProblemRepository::create(&db, "test".to_string())
+            .await
+            .unwrap();
+        let set = TestCaseSetRepository::create(&db, problem.id, "v1".to_string())
+            .await
+            .unwrap();
+        let test_case =
+            TestCaseRepository::create(&db, problem.id, "1 2".to_string(), "0000.txt".to_string())
+                .await
+                .unwrap();
+        let submission = SubmissionRepository::create(
+            &db,
+            problem.id,
+            set.id,
+            String::new(),
+            Default::default(),
+        )
+        .await
+        .unwrap();
+
+        let saved = ExecutionResultRepository::create(
+            &db,
+            submission.id,
+            test_case.id,
+            0,
+            true,
+            stdout.clone(),
+            stderr.clone(),
+            0,
+            1,
+        )
+        .await
+        .unwrap();
+
+        let output = ExecutionResultRepository::find_output(&db, submission.id, test_case.id, 0)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(output.stdout, stdout);
+        assert_eq!(output.stderr, stderr);
+        saved
+    }
+
+    #[tokio::test]
+    async fn small_outputs_are_stored_as_plain_text() {
+        let saved = round_trip("42\n".to_string(), "@@HEURS_SCORE=42\n".to_string()).await;
+        assert_eq!(saved.output_encoding, PLAIN);
+        assert_eq!(saved.stdout, "42\n");
+        assert!(saved.stdout_zstd.is_none());
+    }
+
+    #[tokio::test]
+    async fn large_outputs_are_compressed() {
+        let stdout = "1 2 3\n".repeat(OUTPUT_COMPRESSION_THRESHOLD);
+        let saved = round_trip(stdout, "日本語も扱える\n".to_string()).await;
+        assert_eq!(saved.output_encoding, ZSTD);
+        assert!(saved.stdout.is_empty());
+        assert!(saved.stdout_zstd.unwrap().len() < OUTPUT_COMPRESSION_THRESHOLD);
+    }
+    #[tokio::test]
+    async fn aggregate_by_submission_sums_scores_and_counts_failures() {
+        let (db, _) = DatabaseManager::connect_and_migrate("sqlite::memory:")
+            .await
+            .unwrap();
+        let problem = ProblemRepository::create(&db, "test".to_string())
+            .await
+            .unwrap();
+        let set = TestCaseSetRepository::create(&db, problem.id, "v1".to_string())
+            .await
+            .unwrap();
+        let first =
+            TestCaseRepository::create(&db, problem.id, "1".to_string(), "0000.txt".to_string())
+                .await
+                .unwrap();
+        let second =
+            TestCaseRepository::create(&db, problem.id, "2".to_string(), "0001.txt".to_string())
+                .await
+                .unwrap();
+        let mut submissions = Vec::new();
+        for _ in 0..2 {
+            let submission = SubmissionRepository::create(
+                &db,
+                problem.id,
+                set.id,
+                String::new(),
+                Default::default(),
+            )
+            .await
+            .unwrap();
+            submissions.push(submission.id);
+        }
+
+        // 1 件目のケースを 2 回、2 件目を 1 回 (失敗) 実行する。2 件目の提出は結果なし
+        // 合計は i32 に収まらないので、BIGINT への CAST が効いていることも確かめる
+        for (test_case_id, repeat_index, success, score, time) in [
+            (first.id, 0, true, 3_000_000_000, 10),
+            (first.id, 1, true, 1_000_000_000, 30),
+            (second.id, 0, false, 0, 50),
+        ] {
+            ExecutionResultRepository::create(
+                &db,
+                submissions[0],
+                test_case_id,
+                repeat_index,
+                success,
+                String::new(),
+                String::new(),
+                score,
+                time,
+            )
+            .await
+            .unwrap();
+        }
+
+        let aggregates =
+            ExecutionResultRepository::aggregate_by_submission(&db, submissions.clone(), None)
+                .await
+                .unwrap();
+        assert_eq!(aggregates.len(), 1);
+        let aggregate = &aggregates[0];
+        assert_eq!(aggregate.submission_id, submissions[0]);
+        assert_eq!(aggregate.number_of_results, 3);
+        assert_eq!(aggregate.number_of_test_cases, 2);
+        assert_eq!(aggregate.total_score, 4_000_000_000);
+        assert!((aggregate.average_score - 4_000_000_000.0 / 3.0).abs() < 1.0);
+        assert!((aggregate.average_execution_time_ms - 30.0).abs() < 1e-9);
+        assert_eq!(aggregate.number_of_failures, 1);
 
-    pub async fn find_all(db: &DatabaseConnection) -> Result<Vec<execution_results::Model>, DbErr> {
-        execution_results::Entity::find().all(db).await
+        // ケースを絞ると、そのケースの結果だけが集計される
+        let aggregates = ExecutionResultRepository::aggregate_by_submission(
+            &db,
+            submissions,
+            Some(vec![first.id]),
+        )
+        .await
+        .unwrap();
+        assert_eq!(aggregates[0].number_of_results, 2);
+        assert_eq!(aggregates[0].number_of_test_cases, 1);
+        assert_eq!(aggregates[0].number_of_failures, 0);
     }
 }
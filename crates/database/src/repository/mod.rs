@@ -1,7 +1,11 @@
 pub mod execution_result;
+pub mod problem;
 pub mod submission;
 pub mod test_case;
+pub mod test_case_set;
 
 pub use execution_result::ExecutionResultRepository;
+pub use problem::ProblemRepository;
 pub use submission::SubmissionRepository;
 pub use test_case::TestCaseRepository;
+pub use test_case_set::TestCaseSetRepository;
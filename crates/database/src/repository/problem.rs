@@ -0,0 +1,54 @@
+use crate::entity::problems;
+use sea_orm::*;
+
+/// `--problem` も heurs.toml の `problem` も指定されなかったときの問題名。
+/// マイグレーションで作られ、問題を導入する前のデータはすべてここに属する。
+pub const DEFAULT_PROBLEM: &str = "default";
+
+pub struct ProblemRepository;
+
+impl ProblemRepository {
+    pub async fn create<C: ConnectionTrait>(
+        db: &C,
+        name: String,
+    ) -> Result<problems::Model, DbErr> {
+        let problem = problems::ActiveModel {
+            name: Set(name),
+            created_at: Set(chrono::Utc::now()),
+            ..Default::default()
+        };
+
+        problem.insert(db).await
+    }
+
+    crate::impl_basic_fetch!(problems);
+
+    pub async fn find_by_name<C: ConnectionTrait>(
+        db: &C,
+        name: &str,
+    ) -> Result<Option<problems::Model>, DbErr> {
+        problems::Entity::find()
+            .filter(problems::Column::Name.eq(name))
+            .one(db)
+            .await
+    }
+
+    /// 名前で問題を取得し、なければ作成します
+    pub async fn find_or_create<C: ConnectionTrait>(
+        db: &C,
+        name: &str,
+    ) -> Result<problems::Model, DbErr> {
+        match Self::find_by_name(db, name).await? {
+            Some(problem) => Ok(problem),
+            None => Self::create(db, name.to_string()).await,
+        }
+    }
+
+    /// 問題名として使えるか (URL のパスにそのまま入れるため英数字と `-` `_` のみ)
+    pub fn is_valid_name(name: &str) -> bool {
+        !name.is_empty()
+            && name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_'))
+    }
+}
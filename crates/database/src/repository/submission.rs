@@ -1,21 +1,812 @@
-use crate::entity::submissions;
+use crate::entity::{execution_results, submission_bundles, submissions};
+use crate::repository::execution_result::{ExecutionResultRepository, NewExecutionResult};
+use crate::repository::test_case::TestCaseRepository;
+use sea_orm::sea_query::Expr;
 use sea_orm::*;
+use std::collections::{HashMap, HashSet};
+
+/// `heurs prune` で消す提出の選び方。どの条件にも当てはまらない提出だけが消える
+#[derive(Debug, Clone, Default)]
+pub struct PrunePolicy {
+    /// テストケースのセットごとに、平均スコアの上位この件数を残す
+    pub keep_best: Option<u32>,
+    /// これより古い提出だけを消す
+    pub older_than: Option<chrono::Duration>,
+    /// タグの付いた提出を残す
+    pub keep_tagged: bool,
+}
+
+impl PrunePolicy {
+    /// `14d` や `12h` のような期間を読む (単位は w / d / h / m)
+    pub fn parse_age(age: &str) -> Result<chrono::Duration, String> {
+        let age = age.trim();
+        let invalid = || {
+            format!(
+                "invalid age '{}' (e.g. 14d, 12h; units are w, d, h, m)",
+                age
+            )
+        };
+        let (split, unit) = age.char_indices().last().ok_or_else(invalid)?;
+        let value: i64 = age[..split].parse().map_err(|_| invalid())?;
+        match unit {
+            'w' => chrono::Duration::try_weeks(value),
+            'd' => chrono::Duration::try_days(value),
+            'h' => chrono::Duration::try_hours(value),
+            'm' => chrono::Duration::try_minutes(value),
+            _ => None,
+        }
+        .ok_or_else(invalid)
+    }
+}
+
+/// 提出に付ける説明。すべて省略できる
+#[derive(Debug, Clone, Default)]
+pub struct SubmissionMetadata {
+    pub title: Option<String>,
+    pub notes: Option<String>,
+    pub tags: Vec<String>,
+    pub author: Option<String>,
+    /// どの提出を元にしたか
+    pub parent_id: Option<i32>,
+    /// git リポジトリ内ならルートからの相対パス、そうでなければファイル名
+    pub source_path: Option<String>,
+    /// 提出したファイルが git リポジトリ内にあったときの状態
+    pub git: Option<GitState>,
+    /// 複数ファイルの提出なら、そのファイル一式
+    pub bundle: Option<SubmissionBundle>,
+    /// 実行した環境
+    pub environment: Option<Environment>,
+}
+
+/// 複数ファイルの提出のファイル一式
+#[derive(Debug, Clone)]
+pub struct SubmissionBundle {
+    /// アーカイブ内のエントリポイント (コンパイルコマンドの `{{src}}`) のパス
+    pub entry: String,
+    /// ファイル一式の tar.gz
+    pub archive: Vec<u8>,
+}
+
+/// `heurs run` を実行したときの git リポジトリの状態
+#[derive(Debug, Clone, Default)]
+pub struct GitState {
+    /// HEAD のコミットハッシュ
+    pub commit: String,
+    /// detached HEAD なら `None`
+    pub branch: Option<String>,
+    pub dirty: bool,
+    /// HEAD からの未コミットの変更 (`git apply` で当てられる形式)
+    pub diff: String,
+}
+
+/// 提出を実行した環境。実行時間に依存する解法はマシンによってスコアが変わるので、
+/// 違う環境で実行した提出同士を比べるときに警告するために使う
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Environment {
+    /// 手元で実行しなかった (aws / remote) ならマシンの情報は `None`
+    pub hostname: Option<String>,
+    pub cpu_model: Option<String>,
+    pub cpu_cores: Option<i32>,
+    /// OS 名とカーネルのバージョン
+    pub os: Option<String>,
+    /// `g++ --version` などの 1 行目 (container ではイメージの中のもの)
+    pub compiler_version: Option<String>,
+    /// `{{src}}` を置き換えた実行のコマンド。実行側のコマンドを使う aws では `None`
+    pub compile_cmd: Option<String>,
+    pub exec_cmd: Option<String>,
+    /// local / container / aws / remote
+    pub runner: String,
+    /// 並列に実行したケースの数
+    pub parallelism: i32,
+}
+
+/// 環境の列を持つ行 (`submissions::Model` / [`SubmissionSummary`]) から [`Environment`] を作る
+macro_rules! environment_of {
+    ($submission:expr) => {
+        Some(Environment {
+            hostname: $submission.hostname.clone(),
+            cpu_model: $submission.cpu_model.clone(),
+            cpu_cores: $submission.cpu_cores,
+            os: $submission.os.clone(),
+            compiler_version: $submission.compiler_version.clone(),
+            compile_cmd: $submission.compile_cmd.clone(),
+            exec_cmd: $submission.exec_cmd.clone(),
+            runner: $submission.runner.clone()?,
+            parallelism: $submission.parallelism.unwrap_or(1),
+        })
+    };
+}
+
+impl Environment {
+    /// 提出に記録した環境。記録を始める前の提出なら `None`
+    pub fn of(submission: &submissions::Model) -> Option<Self> {
+        environment_of!(submission)
+    }
+
+    /// `other` と違う項目の名前。ホスト名は同じ構成のマシンでも違うので比べず、
+    /// 片方で分からない項目 (aws / remote で実行したときの CPU など) も比べない
+    pub fn differences(&self, other: &Self) -> Vec<&'static str> {
+        fn differ<T: PartialEq>(a: &Option<T>, b: &Option<T>) -> bool {
+            matches!((a, b), (Some(a), Some(b)) if a != b)
+        }
+        [
+            ("runner", self.runner != other.runner),
+            ("cpu", differ(&self.cpu_model, &other.cpu_model)),
+            ("cores", differ(&self.cpu_cores, &other.cpu_cores)),
+            ("os", differ(&self.os, &other.os)),
+            (
+                "compiler",
+                differ(&self.compiler_version, &other.compiler_version),
+            ),
+            ("compile_cmd", differ(&self.compile_cmd, &other.compile_cmd)),
+            ("exec_cmd", differ(&self.exec_cmd, &other.exec_cmd)),
+            ("parallelism", self.parallelism != other.parallelism),
+        ]
+        .into_iter()
+        .filter(|&(_, differs)| differs)
+        .map(|(name, _)| name)
+        .collect()
+    }
+
+    /// `local, AMD EPYC 7763 x4, Linux 6.1.0, g++ 13.2.0, parallel 4` のような 1 行の要約
+    pub fn summary(&self) -> String {
+        let cpu = match (&self.cpu_model, self.cpu_cores) {
+            (Some(model), Some(cores)) => Some(format!("{} x{}", model, cores)),
+            (Some(model), None) => Some(model.clone()),
+            (None, Some(cores)) => Some(format!("{} cores", cores)),
+            (None, None) => None,
+        };
+        let mut parts = vec![self.runner.clone()];
+        parts.extend(cpu);
+        parts.extend(self.os.clone());
+        parts.extend(self.compiler_version.clone());
+        parts.push(format!("parallel {}", self.parallelism));
+        parts.join(", ")
+    }
+}
+
+/// 一覧に出す提出の列。ソースコードや git diff などの大きな列は読まない
+#[derive(Debug, Clone, FromQueryResult)]
+pub struct SubmissionSummary {
+    pub id: i32,
+    pub problem_id: i32,
+    pub test_case_set_id: Option<i32>,
+    pub timestamp: chrono::DateTime<chrono::Utc>,
+    pub tags: String,
+    pub title: Option<String>,
+    pub author: Option<String>,
+    pub parent_id: Option<i32>,
+    pub git_commit: Option<String>,
+    pub git_dirty: bool,
+    pub hostname: Option<String>,
+    pub cpu_model: Option<String>,
+    pub cpu_cores: Option<i32>,
+    pub os: Option<String>,
+    pub compiler_version: Option<String>,
+    pub compile_cmd: Option<String>,
+    pub exec_cmd: Option<String>,
+    pub runner: Option<String>,
+    pub parallelism: Option<i32>,
+}
+
+impl SubmissionSummary {
+    /// 提出に記録した環境。記録を始める前の提出なら `None`
+    pub fn environment(&self) -> Option<Environment> {
+        environment_of!(self)
+    }
+}
+
+/// 提出の説明の変更。`None` の項目はそのまま残す
+#[derive(Debug, Clone, Default)]
+pub struct SubmissionEdit {
+    /// 空文字列なら消す (`notes`, `author` も同じ)
+    pub title: Option<String>,
+    pub notes: Option<String>,
+    pub author: Option<String>,
+    /// タグをこの一覧で置き換える
+    pub tags: Option<Vec<String>>,
+    /// `Some(None)` なら親との関係を消す
+    pub parent_id: Option<Option<i32>>,
+}
 
 pub struct SubmissionRepository;
 
 impl SubmissionRepository {
-    pub async fn create(
-        db: &DatabaseConnection,
+    /// 提出を保存します。`db` にトランザクションを渡せば、実行結果と合わせてまとめて保存できます
+    pub async fn create<C: ConnectionTrait + TransactionTrait>(
+        db: &C,
+        problem_id: i32,
+        test_case_set_id: i32,
         source_code: String,
+        metadata: SubmissionMetadata,
     ) -> Result<submissions::Model, DbErr> {
-        let submission = submissions::ActiveModel {
+        let mut submission = submissions::ActiveModel {
+            problem_id: Set(problem_id),
+            test_case_set_id: Set(Some(test_case_set_id)),
             source_code: Set(source_code),
             timestamp: Set(chrono::Utc::now()),
+            tags: Set(Self::join_tags(&metadata.tags)),
+            title: Set(non_empty(metadata.title)),
+            notes: Set(non_empty(metadata.notes)),
+            author: Set(non_empty(metadata.author)),
+            parent_id: Set(metadata.parent_id),
+            source_path: Set(metadata.source_path),
+            git_commit: Set(metadata.git.as_ref().map(|g| g.commit.clone())),
+            git_branch: Set(metadata.git.as_ref().and_then(|g| g.branch.clone())),
+            git_dirty: Set(metadata.git.as_ref().is_some_and(|g| g.dirty)),
+            // 差分は末尾の改行まで意味を持つので trim しない
+            git_diff: Set(metadata.git.map(|g| g.diff).filter(|d| !d.is_empty())),
             ..Default::default()
         };
+        if let Some(env) = metadata.environment {
+            submission.hostname = Set(env.hostname);
+            submission.cpu_model = Set(env.cpu_model);
+            submission.cpu_cores = Set(env.cpu_cores);
+            submission.os = Set(env.os);
+            submission.compiler_version = Set(env.compiler_version);
+            submission.compile_cmd = Set(env.compile_cmd);
+            submission.exec_cmd = Set(env.exec_cmd);
+            submission.runner = Set(Some(env.runner));
+            submission.parallelism = Set(Some(env.parallelism));
+        }
+
+        let txn = db.begin().await?;
+        let submission = submission.insert(&txn).await?;
+        if let Some(bundle) = metadata.bundle {
+            submission_bundles::ActiveModel {
+                submission_id: Set(submission.id),
+                entry: Set(bundle.entry),
+                archive: Set(bundle.archive),
+            }
+            .insert(&txn)
+            .await?;
+        }
+        txn.commit().await?;
+        Ok(submission)
+    }
+
+    /// 実行が終わった提出を、実行結果と合わせて 1 つのトランザクションで保存します。
+    ///
+    /// 成功した実行のスコアで各ケースの既知の最良スコアも更新し、途中で失敗したときは
+    /// どれも残しません。2 つめの値は最良スコアを更新した実行の数です
+    pub async fn create_with_results<C: TransactionTrait>(
+        db: &C,
+        problem_id: i32,
+        test_case_set_id: i32,
+        source_code: String,
+        metadata: SubmissionMetadata,
+        results: impl IntoIterator<Item = NewExecutionResult>,
+    ) -> Result<(submissions::Model, usize), DbErr> {
+        let txn = db.begin().await?;
+        let submission =
+            Self::create(&txn, problem_id, test_case_set_id, source_code, metadata).await?;
+        let mut new_bests = 0;
+        for result in results {
+            let (test_case_id, success, score) =
+                (result.test_case_id, result.success, result.score);
+            ExecutionResultRepository::create(
+                &txn,
+                submission.id,
+                test_case_id,
+                result.repeat_index,
+                success,
+                result.stdout,
+                result.stderr,
+                score,
+                result.execution_time_ms,
+            )
+            .await?;
+            if success && TestCaseRepository::record_score(&txn, test_case_id, score).await? {
+                new_bests += 1;
+            }
+        }
+        txn.commit().await?;
+        Ok((submission, new_bests))
+    }
+
+    /// 複数ファイルの提出ならそのファイル一式を取得します
+    pub async fn find_bundle(
+        db: &DatabaseConnection,
+        submission_id: i32,
+    ) -> Result<Option<SubmissionBundle>, DbErr> {
+        Ok(submission_bundles::Entity::find_by_id(submission_id)
+            .one(db)
+            .await?
+            .map(|b| SubmissionBundle {
+                entry: b.entry,
+                archive: b.archive,
+            }))
+    }
 
-        submission.insert(db).await
+    /// 提出の説明を書き換えます
+    pub async fn update_metadata(
+        db: &DatabaseConnection,
+        submission: submissions::Model,
+        edit: SubmissionEdit,
+    ) -> Result<submissions::Model, DbErr> {
+        let mut submission: submissions::ActiveModel = submission.into();
+        if let Some(title) = edit.title {
+            submission.title = Set(non_empty(Some(title)));
+        }
+        if let Some(notes) = edit.notes {
+            submission.notes = Set(non_empty(Some(notes)));
+        }
+        if let Some(author) = edit.author {
+            submission.author = Set(non_empty(Some(author)));
+        }
+        if let Some(tags) = edit.tags {
+            submission.tags = Set(Self::join_tags(&tags));
+        }
+        if let Some(parent_id) = edit.parent_id {
+            submission.parent_id = Set(parent_id);
+        }
+        submission.update(db).await
+    }
+
+    /// `parent_id` を `submission_id` の親にできるか。
+    /// 親は同じ問題の提出でなければならず、親をたどって自分に戻ってきてもいけない
+    pub async fn is_valid_parent(
+        db: &DatabaseConnection,
+        problem_id: i32,
+        submission_id: Option<i32>,
+        parent_id: i32,
+    ) -> Result<bool, DbErr> {
+        let mut visited = HashSet::new();
+        let mut current = Some(parent_id);
+        while let Some(id) = current {
+            if Some(id) == submission_id || !visited.insert(id) {
+                return Ok(false);
+            }
+            match Self::find_by_id(db, id).await? {
+                Some(parent) if parent.problem_id == problem_id => current = parent.parent_id,
+                _ => return Ok(false),
+            }
+        }
+        Ok(true)
+    }
+
+    /// カンマ区切りで保存しているタグを分解する
+    pub fn split_tags(tags: &str) -> Vec<&str> {
+        tags.split(',').filter(|t| !t.is_empty()).collect()
+    }
+
+    /// タグを保存用のカンマ区切りにする。`a,b` のような指定は分けて、重複は除く
+    pub fn join_tags(tags: &[String]) -> String {
+        let mut joined: Vec<&str> = Vec::new();
+        for tag in tags.iter().flat_map(|t| t.split(',')).map(str::trim) {
+            if !tag.is_empty() && !joined.contains(&tag) {
+                joined.push(tag);
+            }
+        }
+        joined.join(",")
     }
 
     crate::impl_basic_fetch!(submissions);
+
+    /// 問題に属する提出を id 昇順で取得します
+    pub async fn find_by_problem(
+        db: &DatabaseConnection,
+        problem_id: i32,
+    ) -> Result<Vec<submissions::Model>, DbErr> {
+        submissions::Entity::find()
+            .filter(submissions::Column::ProblemId.eq(problem_id))
+            .order_by_asc(submissions::Column::Id)
+            .all(db)
+            .await
+    }
+
+    /// 問題に属する提出の一覧用の列を id 昇順で取得します。`limit` があれば先頭からその件数まで
+    pub async fn find_summaries_by_problem(
+        db: &DatabaseConnection,
+        problem_id: i32,
+        limit: Option<u64>,
+    ) -> Result<Vec<SubmissionSummary>, DbErr> {
+        submissions::Entity::find()
+            .select_only()
+            .columns([
+                submissions::Column::Id,
+                submissions::Column::ProblemId,
+                submissions::Column::TestCaseSetId,
+                submissions::Column::Timestamp,
+                submissions::Column::Tags,
+                submissions::Column::Title,
+                submissions::Column::Author,
+                submissions::Column::ParentId,
+                submissions::Column::GitCommit,
+                submissions::Column::GitDirty,
+                submissions::Column::Hostname,
+                submissions::Column::CpuModel,
+                submissions::Column::CpuCores,
+                submissions::Column::Os,
+                submissions::Column::CompilerVersion,
+                submissions::Column::CompileCmd,
+                submissions::Column::ExecCmd,
+                submissions::Column::Runner,
+                submissions::Column::Parallelism,
+            ])
+            .filter(submissions::Column::ProblemId.eq(problem_id))
+            .order_by_asc(submissions::Column::Id)
+            .limit(limit)
+            .into_model::<SubmissionSummary>()
+            .all(db)
+            .await
+    }
+
+    /// テストケースのセットで実行した提出を id 昇順で取得します
+    pub async fn find_by_set(
+        db: &DatabaseConnection,
+        test_case_set_id: i32,
+    ) -> Result<Vec<submissions::Model>, DbErr> {
+        submissions::Entity::find()
+            .filter(submissions::Column::TestCaseSetId.eq(test_case_set_id))
+            .order_by_asc(submissions::Column::Id)
+            .all(db)
+            .await
+    }
+
+    /// 問題の提出のうち、`policy` で残すことにならなかったものを id 昇順で返します
+    pub async fn find_prunable(
+        db: &DatabaseConnection,
+        problem_id: i32,
+        policy: &PrunePolicy,
+    ) -> Result<Vec<submissions::Model>, DbErr> {
+        let submissions = Self::find_by_problem(db, problem_id).await?;
+
+        let mut keep: HashSet<i32> = HashSet::new();
+        if let Some(keep_best) = policy.keep_best {
+            let averages: HashMap<i32, f64> = ExecutionResultRepository::aggregate_by_submission(
+                db,
+                submissions.iter().map(|s| s.id).collect(),
+                None,
+            )
+            .await?
+            .into_iter()
+            .map(|a| (a.submission_id, a.average_score))
+            .collect();
+
+            // セットが違う提出のスコアは比べられないので、セットごとに上位を残す
+            let mut by_set: HashMap<Option<i32>, Vec<&submissions::Model>> = HashMap::new();
+            for submission in &submissions {
+                by_set
+                    .entry(submission.test_case_set_id)
+                    .or_default()
+                    .push(submission);
+            }
+            for ranked in by_set.values_mut() {
+                let score = |s: &submissions::Model| averages.get(&s.id).copied();
+                ranked.sort_by(|a, b| {
+                    score(b)
+                        .partial_cmp(&score(a))
+                        .unwrap_or(std::cmp::Ordering::Equal)
+                });
+                keep.extend(ranked.iter().take(keep_best as usize).map(|s| s.id));
+            }
+        }
+
+        let cutoff = policy.older_than.map(|age| chrono::Utc::now() - age);
+        Ok(submissions
+            .into_iter()
+            .filter(|s| !keep.contains(&s.id))
+            .filter(|s| !policy.keep_tagged || s.tags.is_empty())
+            .filter(|s| cutoff.is_none_or(|cutoff| s.timestamp < cutoff))
+            .collect())
+    }
+
+    /// 提出をその実行結果とともに削除し、削除した提出の件数を返します
+    pub async fn delete(db: &DatabaseConnection, ids: Vec<i32>) -> Result<u64, DbErr> {
+        let txn = db.begin().await?;
+        // 派生した提出は残し、親との関係だけを外す
+        submissions::Entity::update_many()
+            .col_expr(
+                submissions::Column::ParentId,
+                Expr::value(Option::<i32>::None),
+            )
+            .filter(submissions::Column::ParentId.is_in(ids.clone()))
+            .exec(&txn)
+            .await?;
+        execution_results::Entity::delete_many()
+            .filter(execution_results::Column::SubmissionId.is_in(ids.clone()))
+            .exec(&txn)
+            .await?;
+        submission_bundles::Entity::delete_many()
+            .filter(submission_bundles::Column::SubmissionId.is_in(ids.clone()))
+            .exec(&txn)
+            .await?;
+        let result = submissions::Entity::delete_many()
+            .filter(submissions::Column::Id.is_in(ids))
+            .exec(&txn)
+            .await?;
+        txn.commit().await?;
+        Ok(result.rows_affected)
+    }
+
+    /// テストケースのセットで実行した提出の件数
+    pub async fn count_by_set<C: ConnectionTrait>(
+        db: &C,
+        test_case_set_id: i32,
+    ) -> Result<u64, DbErr> {
+        submissions::Entity::find()
+            .filter(submissions::Column::TestCaseSetId.eq(test_case_set_id))
+            .count(db)
+            .await
+    }
+}
+
+/// 空白だけの文字列は指定なしとして扱う
+fn non_empty(value: Option<String>) -> Option<String> {
+    value
+        .map(|v| v.trim().to_string())
+        .filter(|v| !v.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{DatabaseManager, ProblemRepository, TestCaseRepository, TestCaseSetRepository};
+
+    #[test]
+    fn parse_age_reads_each_unit() {
+        assert_eq!(
+            PrunePolicy::parse_age("2w").unwrap(),
+            chrono::Duration::weeks(2)
+        );
+        assert_eq!(
+            PrunePolicy::parse_age(" 14d ").unwrap(),
+            chrono::Duration::days(14)
+        );
+        assert_eq!(
+            PrunePolicy::parse_age("12h").unwrap(),
+            chrono::Duration::hours(12)
+        );
+        assert_eq!(
+            PrunePolicy::parse_age("30m").unwrap(),
+            chrono::Duration::minutes(30)
+        );
+    }
+
+    #[test]
+    fn parse_age_rejects_invalid_ages() {
+        for age in ["", "d", "14", "14s", "1.5d", "d14", "99999999999999w"] {
+            assert!(PrunePolicy::parse_age(age).is_err(), "{}", age);
+        }
+    }
+
+    #[test]
+    fn join_tags_splits_trims_and_dedups() {
+        let tags = ["a,b", " a ", "", "c,,b"].map(String::from);
+        assert_eq!(SubmissionRepository::join_tags(&tags), "a,b,c");
+        assert_eq!(SubmissionRepository::join_tags(&[]), "");
+    }
+
+    #[test]
+    fn split_tags_skips_empty_tags() {
+        assert_eq!(SubmissionRepository::split_tags("a,b,,c"), ["a", "b", "c"]);
+        assert!(SubmissionRepository::split_tags("").is_empty());
+        let tags = ["x", "y"].map(String::from);
+        assert_eq!(
+            SubmissionRepository::split_tags(&SubmissionRepository::join_tags(&tags)),
+            ["x", "y"]
+        );
+    }
+
+    #[tokio::test]
+    async fn is_valid_parent_rejects_cycles_and_other_problems() {
+        let (db, _) = DatabaseManager::connect_and_migrate("sqlite::memory:")
+            .await
+            .unwrap();
+        let db = &db;
+        let problem_id = ProblemRepository::create(db, "test".to_string())
+            .await
+            .unwrap()
+            .id;
+        let set_id = TestCaseSetRepository::create(db, problem_id, "v1".to_string())
+            .await
+            .unwrap()
+            .id;
+        let root =
+            SubmissionRepository::create(db, problem_id, set_id, String::new(), Default::default())
+                .await
+                .unwrap()
+                .id;
+        let metadata = SubmissionMetadata {
+            parent_id: Some(root),
+            ..Default::default()
+        };
+        let child = SubmissionRepository::create(db, problem_id, set_id, String::new(), metadata)
+            .await
+            .unwrap()
+            .id;
+
+        assert!(
+            SubmissionRepository::is_valid_parent(db, problem_id, None, child)
+                .await
+                .unwrap()
+        );
+        // root の親を child にすると root -> child -> root と戻ってくる
+        assert!(
+            !SubmissionRepository::is_valid_parent(db, problem_id, Some(root), child)
+                .await
+                .unwrap()
+        );
+        assert!(
+            !SubmissionRepository::is_valid_parent(db, problem_id, Some(root), root)
+                .await
+                .unwrap()
+        );
+        assert!(
+            !SubmissionRepository::is_valid_parent(db, problem_id, None, child + 100)
+                .await
+                .unwrap()
+        );
+        assert!(
+            !SubmissionRepository::is_valid_parent(db, problem_id + 1, None, root)
+                .await
+                .unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn find_prunable_keeps_the_best_of_each_set() {
+        let (db, _) = DatabaseManager::connect_and_migrate("sqlite::memory:")
+            .await
+            .unwrap();
+        let db = &db;
+        let problem = ProblemRepository::create(db, "test".to_string())
+            .await
+            .unwrap();
+        let set = TestCaseSetRepository::create(db, problem.id, "v1".to_string())
+            .await
+            .unwrap();
+        let other_set = TestCaseSetRepository::create(db, problem.id, "v2".to_string())
+            .await
+            .unwrap();
+        let test_case =
+            TestCaseRepository::create(db, problem.id, "1 2".to_string(), "0000.txt".to_string())
+                .await
+                .unwrap();
+
+        let mut ids = Vec::new();
+        for (set_id, score) in [(set.id, 10), (set.id, 30), (set.id, 20), (other_set.id, 1)] {
+            let id = SubmissionRepository::create(
+                db,
+                problem.id,
+                set_id,
+                String::new(),
+                Default::default(),
+            )
+            .await
+            .unwrap()
+            .id;
+            ExecutionResultRepository::create(
+                db,
+                id,
+                test_case.id,
+                0,
+                true,
+                String::new(),
+                String::new(),
+                score,
+                1,
+            )
+            .await
+            .unwrap();
+            ids.push(id);
+        }
+        let metadata = SubmissionMetadata {
+            tags: vec!["keep".to_string()],
+            ..Default::default()
+        };
+        let tagged = SubmissionRepository::create(db, problem.id, set.id, String::new(), metadata)
+            .await
+            .unwrap()
+            .id;
+
+        let prunable = |policy: PrunePolicy| async move {
+            SubmissionRepository::find_prunable(db, problem.id, &policy)
+                .await
+                .unwrap()
+                .into_iter()
+                .map(|s| s.id)
+                .collect::<Vec<_>>()
+        };
+
+        // 各セットの上位 2 件を残す。スコアのない提出は最下位
+        let policy = PrunePolicy {
+            keep_best: Some(2),
+            ..Default::default()
+        };
+        assert_eq!(prunable(policy).await, [ids[0], tagged]);
+
+        let policy = PrunePolicy {
+            keep_best: Some(1),
+            keep_tagged: true,
+            ..Default::default()
+        };
+        assert_eq!(prunable(policy).await, [ids[0], ids[2]]);
+
+        // 作ったばかりの提出は 1 日より古くない
+        let policy = PrunePolicy {
+            older_than: Some(chrono::Duration::days(1)),
+            ..Default::default()
+        };
+        assert!(prunable(policy).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn create_with_results_saves_everything_or_nothing() {
+        let (db, _) = DatabaseManager::connect_and_migrate("sqlite::memory:")
+            .await
+            .unwrap();
+        let db = &db;
+        let problem = ProblemRepository::create(db, "test".to_string())
+            .await
+            .unwrap();
+        let set = TestCaseSetRepository::create(db, problem.id, "v1".to_string())
+            .await
+            .unwrap();
+        let test_case =
+            TestCaseRepository::create(db, problem.id, "1 2".to_string(), "0000.txt".to_string())
+                .await
+                .unwrap();
+        let result = |test_case_id, success, score| NewExecutionResult {
+            test_case_id,
+            repeat_index: 0,
+            success,
+            stdout: String::new(),
+            stderr: String::new(),
+            score,
+            execution_time_ms: 1,
+        };
+
+        // 失敗した実行のスコアは最良スコアに数えない
+        let (submission, new_bests) = SubmissionRepository::create_with_results(
+            db,
+            problem.id,
+            set.id,
+            String::new(),
+            Default::default(),
+            [
+                result(test_case.id, true, 10),
+                result(test_case.id, false, 99),
+            ],
+        )
+        .await
+        .unwrap();
+        assert_eq!(new_bests, 1);
+        assert_eq!(
+            ExecutionResultRepository::find_summaries_by_submission_id(db, submission.id)
+                .await
+                .unwrap()
+                .len(),
+            2
+        );
+        let test_case = TestCaseRepository::find_by_ids(db, vec![test_case.id])
+            .await
+            .unwrap()
+            .remove(0);
+        assert_eq!(test_case.best_known_score, Some(10));
+
+        // 存在しないケースの結果で失敗したら、提出も最良スコアの更新も残らない
+        assert!(
+            SubmissionRepository::create_with_results(
+                db,
+                problem.id,
+                set.id,
+                String::new(),
+                Default::default(),
+                [
+                    result(test_case.id, true, 20),
+                    result(test_case.id + 100, true, 0)
+                ],
+            )
+            .await
+            .is_err()
+        );
+        assert_eq!(submissions::Entity::find().count(db).await.unwrap(), 1);
+        let test_case = TestCaseRepository::find_by_ids(db, vec![test_case.id])
+            .await
+            .unwrap()
+            .remove(0);
+        assert_eq!(test_case.best_known_score, Some(10));
+    }
 }
@@ -1,15 +1,20 @@
-use crate::entity::test_cases;
+use crate::entity::{test_case_set_cases, test_cases};
+use sea_orm::sea_query::Expr;
 use sea_orm::*;
+use sha2::{Digest, Sha256};
 
 pub struct TestCaseRepository;
 
 impl TestCaseRepository {
-    pub async fn create(
-        db: &DatabaseConnection,
+    pub async fn create<C: ConnectionTrait>(
+        db: &C,
+        problem_id: i32,
         input: String,
         filename: String,
     ) -> Result<test_cases::Model, DbErr> {
         let test_case = test_cases::ActiveModel {
+            problem_id: Set(problem_id),
+            content_hash: Set(Self::content_hash(&input)),
             input: Set(input),
             filename: Set(filename),
             created_at: Set(chrono::Utc::now()),
@@ -19,20 +24,158 @@ impl TestCaseRepository {
         test_case.insert(db).await
     }
 
+    /// 同じ内容のケースが既にあればそれを、なければ新しく登録して返します。
+    /// 2 つめの値は新しく登録したかどうか
+    pub async fn find_or_create(
+        db: &DatabaseConnection,
+        problem_id: i32,
+        input: String,
+        filename: String,
+    ) -> Result<(test_cases::Model, bool), DbErr> {
+        let hash = Self::content_hash(&input);
+        match Self::find_by_content_hash(db, problem_id, &hash).await? {
+            Some(test_case) => Ok((test_case, false)),
+            None => Ok((Self::create(db, problem_id, input, filename).await?, true)),
+        }
+    }
+
     crate::impl_basic_fetch!(test_cases);
 
-    /// 先頭 `limit` 件のテストケースをファイル名昇順で取得します
+    /// ケースを識別する入力の SHA-256 (16 進小文字)
+    pub fn content_hash(input: &str) -> String {
+        format!("{:x}", Sha256::digest(input.as_bytes()))
+    }
+
+    /// 実行結果のスコアが既知の最良スコアを上回っていれば更新します。更新したら true
+    pub async fn record_score<C: ConnectionTrait>(
+        db: &C,
+        test_case_id: i32,
+        score: i64,
+    ) -> Result<bool, DbErr> {
+        let result = test_cases::Entity::update_many()
+            .col_expr(test_cases::Column::BestKnownScore, Expr::value(score))
+            .filter(test_cases::Column::Id.eq(test_case_id))
+            .filter(
+                Condition::any()
+                    .add(test_cases::Column::BestKnownScore.is_null())
+                    .add(test_cases::Column::BestKnownScore.lt(score)),
+            )
+            .exec(db)
+            .await?;
+        Ok(result.rows_affected > 0)
+    }
+
+    /// カンマ区切りで保存しているタグを分解する
+    pub fn split_tags(tags: &str) -> Vec<&str> {
+        tags.split(',').filter(|t| !t.is_empty()).collect()
+    }
+
+    /// マニフェストの付加情報をケースに反映します。
+    /// シードは未設定のときだけ入れ、タグは和集合、既知の最良スコアは大きいほうを残します
+    pub async fn merge_metadata<C: ConnectionTrait>(
+        db: &C,
+        test_case: test_cases::Model,
+        seed: Option<i64>,
+        tags: &[String],
+        best_known_score: Option<i64>,
+    ) -> Result<test_cases::Model, DbErr> {
+        let mut merged: Vec<String> = Self::split_tags(&test_case.tags)
+            .into_iter()
+            .map(str::to_string)
+            .collect();
+        for tag in tags {
+            if !tag.is_empty() && !tag.contains(',') && !merged.contains(tag) {
+                merged.push(tag.clone());
+            }
+        }
+        let merged = merged.join(",");
+        let seed = test_case.seed.or(seed);
+        let best_known_score = match (test_case.best_known_score, best_known_score) {
+            (Some(a), Some(b)) => Some(a.max(b)),
+            (a, b) => a.or(b),
+        };
+        if merged == test_case.tags
+            && seed == test_case.seed
+            && best_known_score == test_case.best_known_score
+        {
+            return Ok(test_case);
+        }
+
+        let mut test_case: test_cases::ActiveModel = test_case.into();
+        test_case.seed = Set(seed);
+        test_case.tags = Set(merged);
+        test_case.best_known_score = Set(best_known_score);
+        test_case.update(db).await
+    }
+
+    pub async fn find_by_content_hash<C: ConnectionTrait>(
+        db: &C,
+        problem_id: i32,
+        content_hash: &str,
+    ) -> Result<Option<test_cases::Model>, DbErr> {
+        test_cases::Entity::find()
+            .filter(test_cases::Column::ProblemId.eq(problem_id))
+            .filter(test_cases::Column::ContentHash.eq(content_hash))
+            .order_by_asc(test_cases::Column::Id)
+            .one(db)
+            .await
+    }
+
+    /// 問題に属するテストケースをファイル名昇順で取得します (どのセットに属するかを問わない)
+    pub async fn find_by_problem(
+        db: &DatabaseConnection,
+        problem_id: i32,
+    ) -> Result<Vec<test_cases::Model>, DbErr> {
+        test_cases::Entity::find()
+            .filter(test_cases::Column::ProblemId.eq(problem_id))
+            .order_by_asc(test_cases::Column::Filename)
+            .all(db)
+            .await
+    }
+
+    /// セットに含まれるテストケースをファイル名昇順で取得します
+    pub async fn find_by_set<C: ConnectionTrait>(
+        db: &C,
+        test_case_set_id: i32,
+    ) -> Result<Vec<test_cases::Model>, DbErr> {
+        test_cases::Entity::find()
+            .inner_join(test_case_set_cases::Entity)
+            .filter(test_case_set_cases::Column::TestCaseSetId.eq(test_case_set_id))
+            .order_by_asc(test_cases::Column::Filename)
+            .all(db)
+            .await
+    }
+
+    /// セットに含まれるテストケースの先頭 `limit` 件をファイル名昇順で取得します
     pub async fn find_limit(
         db: &DatabaseConnection,
+        test_case_set_id: i32,
         limit: u64,
     ) -> Result<Vec<test_cases::Model>, DbErr> {
         test_cases::Entity::find()
+            .inner_join(test_case_set_cases::Entity)
+            .filter(test_case_set_cases::Column::TestCaseSetId.eq(test_case_set_id))
             .order_by_asc(test_cases::Column::Filename)
             .limit(limit)
             .all(db)
             .await
     }
 
+    /// 問題のケースのうち、タグ `tag` が付いたものの ID を取得します
+    pub async fn find_ids_by_tag(
+        db: &DatabaseConnection,
+        problem_id: i32,
+        tag: &str,
+    ) -> Result<Vec<i32>, DbErr> {
+        // タグはカンマ区切りの文字列なので、LIKE では部分一致してしまう。分解して比べる
+        Ok(Self::find_by_problem(db, problem_id)
+            .await?
+            .into_iter()
+            .filter(|c| Self::split_tags(&c.tags).contains(&tag))
+            .map(|c| c.id)
+            .collect())
+    }
+
     pub async fn find_by_ids(
         db: &DatabaseConnection,
         ids: Vec<i32>,
@@ -42,8 +185,62 @@ impl TestCaseRepository {
             .all(db)
             .await
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{DatabaseManager, ProblemRepository};
+
+    #[tokio::test]
+    async fn record_score_only_raises_the_best_known_score() {
+        let (db, _) = DatabaseManager::connect_and_migrate("sqlite::memory:")
+            .await
+            .unwrap();
+        let problem = ProblemRepository::create(&db, "test".to_string())
+            .await
+            .unwrap();
+        let test_case =
+            TestCaseRepository::create(&db, problem.id, "1 2".to_string(), "0000.txt".to_string())
+                .await
+                .unwrap();
+        assert_eq!(test_case.best_known_score, None);
+
+        let best = |db| async move {
+            TestCaseRepository::find_by_id(db, test_case.id)
+                .await
+                .unwrap()
+                .unwrap()
+                .best_known_score
+        };
+
+        // 未設定なら必ず入る
+        assert!(
+            TestCaseRepository::record_score(&db, test_case.id, 100)
+                .await
+                .unwrap()
+        );
+        assert_eq!(best(&db).await, Some(100));
+
+        // 低いスコアや同じスコアでは変わらない
+        assert!(
+            !TestCaseRepository::record_score(&db, test_case.id, 50)
+                .await
+                .unwrap()
+        );
+        assert!(
+            !TestCaseRepository::record_score(&db, test_case.id, 100)
+                .await
+                .unwrap()
+        );
+        assert_eq!(best(&db).await, Some(100));
 
-    pub async fn clear(db: &DatabaseConnection) -> Result<DeleteResult, DbErr> {
-        test_cases::Entity::delete_many().exec(db).await
+        // 高いスコアなら上がる
+        assert!(
+            TestCaseRepository::record_score(&db, test_case.id, 150)
+                .await
+                .unwrap()
+        );
+        assert_eq!(best(&db).await, Some(150));
     }
 }
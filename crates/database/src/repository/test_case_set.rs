@@ -0,0 +1,258 @@
+use crate::entity::{test_case_set_cases, test_case_sets};
+use sea_orm::*;
+
+pub struct TestCaseSetRepository;
+
+impl TestCaseSetRepository {
+    pub async fn create<C: ConnectionTrait>(
+        db: &C,
+        problem_id: i32,
+        name: String,
+    ) -> Result<test_case_sets::Model, DbErr> {
+        let set = test_case_sets::ActiveModel {
+            problem_id: Set(problem_id),
+            name: Set(name),
+            created_at: Set(chrono::Utc::now()),
+            archived_at: Set(None),
+            ..Default::default()
+        };
+
+        set.insert(db).await
+    }
+
+    crate::impl_basic_fetch!(test_case_sets);
+
+    /// 問題に属するセットを作成順に取得します (アーカイブ済みを含む)
+    pub async fn find_by_problem(
+        db: &DatabaseConnection,
+        problem_id: i32,
+    ) -> Result<Vec<test_case_sets::Model>, DbErr> {
+        test_case_sets::Entity::find()
+            .filter(test_case_sets::Column::ProblemId.eq(problem_id))
+            .order_by_asc(test_case_sets::Column::Id)
+            .all(db)
+            .await
+    }
+
+    pub async fn find_by_name<C: ConnectionTrait>(
+        db: &C,
+        problem_id: i32,
+        name: &str,
+    ) -> Result<Option<test_case_sets::Model>, DbErr> {
+        test_case_sets::Entity::find()
+            .filter(test_case_sets::Column::ProblemId.eq(problem_id))
+            .filter(test_case_sets::Column::Name.eq(name))
+            .one(db)
+            .await
+    }
+
+    /// 現在のセット (アーカイブされていない中で最後に作ったもの) を取得します
+    pub async fn find_active<C: ConnectionTrait>(
+        db: &C,
+        problem_id: i32,
+    ) -> Result<Option<test_case_sets::Model>, DbErr> {
+        test_case_sets::Entity::find()
+            .filter(test_case_sets::Column::ProblemId.eq(problem_id))
+            .filter(test_case_sets::Column::ArchivedAt.is_null())
+            .order_by_desc(test_case_sets::Column::Id)
+            .one(db)
+            .await
+    }
+
+    /// 名前が指定されていればそのセットを、なければ現在のセットを取得します
+    pub async fn resolve<C: ConnectionTrait>(
+        db: &C,
+        problem_id: i32,
+        name: Option<&str>,
+    ) -> Result<Option<test_case_sets::Model>, DbErr> {
+        match name {
+            Some(name) => Self::find_by_name(db, problem_id, name).await,
+            None => Self::find_active(db, problem_id).await,
+        }
+    }
+
+    /// 新しいセットの既定の名前 ("v1", "v2", ... のうち使われていないもの)
+    pub async fn next_name<C: ConnectionTrait>(db: &C, problem_id: i32) -> Result<String, DbErr> {
+        let mut n = test_case_sets::Entity::find()
+            .filter(test_case_sets::Column::ProblemId.eq(problem_id))
+            .count(db)
+            .await?
+            + 1;
+        while Self::find_by_name(db, problem_id, &format!("v{}", n))
+            .await?
+            .is_some()
+        {
+            n += 1;
+        }
+        Ok(format!("v{}", n))
+    }
+
+    /// セットにケースを加えます。既に含まれていれば何もせず false を返します
+    pub async fn add_case<C: ConnectionTrait>(
+        db: &C,
+        test_case_set_id: i32,
+        test_case_id: i32,
+    ) -> Result<bool, DbErr> {
+        let exists = test_case_set_cases::Entity::find_by_id((test_case_set_id, test_case_id))
+            .one(db)
+            .await?
+            .is_some();
+        if exists {
+            return Ok(false);
+        }
+
+        test_case_set_cases::ActiveModel {
+            test_case_set_id: Set(test_case_set_id),
+            test_case_id: Set(test_case_id),
+        }
+        .insert(db)
+        .await?;
+        Ok(true)
+    }
+
+    /// セットに含まれるケースの数
+    pub async fn count_cases<C: ConnectionTrait>(
+        db: &C,
+        test_case_set_id: i32,
+    ) -> Result<u64, DbErr> {
+        test_case_set_cases::Entity::find()
+            .filter(test_case_set_cases::Column::TestCaseSetId.eq(test_case_set_id))
+            .count(db)
+            .await
+    }
+
+    /// セットをアーカイブします。ケースや実行結果は消さず、現在のセットとして選ばれなくなるだけです
+    pub async fn archive(
+        db: &DatabaseConnection,
+        set: test_case_sets::Model,
+    ) -> Result<test_case_sets::Model, DbErr> {
+        let mut set: test_case_sets::ActiveModel = set.into();
+        set.archived_at = Set(Some(chrono::Utc::now()));
+        set.update(db).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{DatabaseManager, ProblemRepository, TestCaseRepository};
+
+    #[tokio::test]
+    async fn new_sets_become_the_active_set() {
+        let (db, _) = DatabaseManager::connect_and_migrate("sqlite::memory:")
+            .await
+            .unwrap();
+        let problem = ProblemRepository::create(&db, "test".to_string())
+            .await
+            .unwrap();
+
+        let name = TestCaseSetRepository::next_name(&db, problem.id)
+            .await
+            .unwrap();
+        assert_eq!(name, "v1");
+        let v1 = TestCaseSetRepository::create(&db, problem.id, name)
+            .await
+            .unwrap();
+        let name = TestCaseSetRepository::next_name(&db, problem.id)
+            .await
+            .unwrap();
+        assert_eq!(name, "v2");
+        let v2 = TestCaseSetRepository::create(&db, problem.id, name)
+            .await
+            .unwrap();
+
+        // 名前がなければ最後に作ったセット、あればそのセット
+        let active = TestCaseSetRepository::resolve(&db, problem.id, None)
+            .await
+            .unwrap();
+        assert_eq!(active.unwrap().id, v2.id);
+        let named = TestCaseSetRepository::resolve(&db, problem.id, Some("v1"))
+            .await
+            .unwrap();
+        assert_eq!(named.unwrap().id, v1.id);
+
+        // アーカイブしたセットは現在のセットに選ばれない
+        TestCaseSetRepository::archive(&db, v2).await.unwrap();
+        let active = TestCaseSetRepository::find_active(&db, problem.id)
+            .await
+            .unwrap();
+        assert_eq!(active.unwrap().id, v1.id);
+    }
+
+    #[tokio::test]
+    async fn sets_resolve_to_their_own_cases() {
+        let (db, _) = DatabaseManager::connect_and_migrate("sqlite::memory:")
+            .await
+            .unwrap();
+        let problem = ProblemRepository::create(&db, "test".to_string())
+            .await
+            .unwrap();
+        let set = TestCaseSetRepository::create(&db, problem.id, "v1".to_string())
+            .await
+            .unwrap();
+        let mut cases = Vec::new();
+        for filename in ["0001.txt", "0000.txt", "0002.txt"] {
+            let case = TestCaseRepository::create(
+                &db,
+                problem.id,
+                filename.to_string(),
+                filename.to_string(),
+            )
+            .await
+            .unwrap();
+            cases.push(case);
+        }
+
+        // 3 件目はセットに入れない。同じケースを 2 回加えても 1 件のまま
+        assert!(
+            TestCaseSetRepository::add_case(&db, set.id, cases[0].id)
+                .await
+                .unwrap()
+        );
+        assert!(
+            TestCaseSetRepository::add_case(&db, set.id, cases[1].id)
+                .await
+                .unwrap()
+        );
+        assert!(
+            !TestCaseSetRepository::add_case(&db, set.id, cases[0].id)
+                .await
+                .unwrap()
+        );
+
+        assert_eq!(
+            TestCaseSetRepository::count_cases(&db, set.id)
+                .await
+                .unwrap(),
+            2
+        );
+        let members = TestCaseRepository::find_by_set(&db, set.id).await.unwrap();
+        let filenames: Vec<&str> = members.iter().map(|c| c.filename.as_str()).collect();
+        assert_eq!(filenames, ["0000.txt", "0001.txt"]);
+    }
+
+    #[tokio::test]
+    async fn sets_of_another_problem_are_not_resolved() {
+        let (db, _) = DatabaseManager::connect_and_migrate("sqlite::memory:")
+            .await
+            .unwrap();
+        let problem = ProblemRepository::create(&db, "test".to_string())
+            .await
+            .unwrap();
+        let other = ProblemRepository::create(&db, "other".to_string())
+            .await
+            .unwrap();
+        TestCaseSetRepository::create(&db, problem.id, "v1".to_string())
+            .await
+            .unwrap();
+
+        let named = TestCaseSetRepository::resolve(&db, other.id, Some("v1"))
+            .await
+            .unwrap();
+        assert!(named.is_none());
+        let active = TestCaseSetRepository::resolve(&db, other.id, None)
+            .await
+            .unwrap();
+        assert!(active.is_none());
+    }
+}
@@ -1,3 +1,4 @@
 pub mod item_list_panel;
 pub mod nav_bar;
+pub mod source_files;
 pub mod visualizer_host;
@@ -4,11 +4,19 @@ use yew_router::prelude::*;
 
 #[function_component(NavBar)]
 pub fn nav_bar() -> Html {
+    // 問題ごとのページにいる間は、その問題のページへのリンクを出す
+    let route = use_route::<Route>();
+    let problem = route.as_ref().and_then(|r| r.problem()).map(str::to_string);
+
     html! {
         <nav class="nav-bar">
-            <Link<Route> classes="nav-link" to={Route::Submit}>{"Submit"}</Link<Route>>
-            <Link<Route> classes="nav-link" to={Route::Submissions}>{"Submissions"}</Link<Route>>
-            <Link<Route> classes="nav-link" to={Route::TestCases}>{"TestCases"}</Link<Route>>
+            <Link<Route> classes="nav-link" to={Route::Problems}>{"Problems"}</Link<Route>>
+            if let Some(problem) = problem {
+                <span class="nav-problem">{problem.clone()}</span>
+                <Link<Route> classes="nav-link" to={Route::Submit { problem: problem.clone() }}>{"Submit"}</Link<Route>>
+                <Link<Route> classes="nav-link" to={Route::Submissions { problem: problem.clone() }}>{"Submissions"}</Link<Route>>
+                <Link<Route> classes="nav-link" to={Route::TestCases { problem }}>{"TestCases"}</Link<Route>>
+            }
             <Link<Route> classes="nav-link" to={Route::Visualize}>{"Visualize"}</Link<Route>>
         </nav>
     }
@@ -0,0 +1,117 @@
+use crate::types::{SubmissionDetail, api_error_message};
+use gloo_net::http::Request;
+use serde::Deserialize;
+use wasm_bindgen_futures::spawn_local;
+use yew::prelude::*;
+
+#[derive(Deserialize)]
+struct SubmissionFileResponse {
+    content: String,
+}
+
+#[derive(Properties, PartialEq)]
+pub struct Props {
+    pub problem: String,
+    pub submission: SubmissionDetail,
+}
+
+/// 提出のソースコード。複数ファイルの提出ならファイルツリーから選んだファイルを表示する
+#[function_component(SourceFiles)]
+pub fn source_files(props: &Props) -> Html {
+    let s = &props.submission;
+    // 選択中のファイルのパスと内容 (エントリポイントの内容は source_code にある)
+    let selected = use_state(|| None::<(String, String)>);
+    let error = use_state(|| None::<String>);
+
+    // 別の提出に切り替わったらエントリポイントの表示に戻す
+    {
+        let selected = selected.clone();
+        let error = error.clone();
+        use_effect_with(s.id, move |_| {
+            selected.set(None);
+            error.set(None);
+            || ()
+        });
+    }
+
+    if s.files.is_empty() {
+        return html! {
+            <div style="max-height: 400px; overflow-y: auto; background-color: #f8f9fa;">
+                <pre><code>{ &s.source_code }</code></pre>
+            </div>
+        };
+    }
+
+    let entry = s.entry_point.clone().unwrap_or_default();
+    let (current_path, content) = match &*selected {
+        Some((path, content)) => (path.clone(), content.clone()),
+        None => (entry.clone(), s.source_code.clone()),
+    };
+
+    let on_select = {
+        let selected = selected.clone();
+        let error = error.clone();
+        let problem = props.problem.clone();
+        let id = s.id;
+        let entry = entry.clone();
+        let source_code = s.source_code.clone();
+        Callback::from(move |path: String| {
+            if path == entry {
+                selected.set(Some((path, source_code.clone())));
+                return;
+            }
+            let selected = selected.clone();
+            let error = error.clone();
+            let url = format!(
+                "/api/problems/{}/submissions/{}/files/{}",
+                problem, id, path
+            );
+            spawn_local(async move {
+                match Request::get(&url).send().await {
+                    Ok(response) if response.ok() => {
+                        match response.json::<SubmissionFileResponse>().await {
+                            Ok(resp) => {
+                                error.set(None);
+                                selected.set(Some((path, resp.content)));
+                            }
+                            Err(e) => error.set(Some(format!("Parse error: {}", e))),
+                        }
+                    }
+                    Ok(response) => error.set(Some(api_error_message(&response).await)),
+                    Err(e) => error.set(Some(format!("Request error: {}", e))),
+                }
+            });
+        })
+    };
+
+    html! {
+        <div style="display:flex;">
+            <ul style="list-style:none; padding:0 1em 0 0; margin:0; min-width:30%;">
+                { for s.files.iter().map(|f| {
+                    let path = f.path.clone();
+                    let on_select = on_select.clone();
+                    let onclick = Callback::from(move |_| on_select.emit(path.clone()));
+                    let style = if f.path == current_path {
+                        "cursor:pointer; background-color:#d0e0ff;"
+                    } else {
+                        "cursor:pointer;"
+                    };
+                    html! {
+                        <li {onclick} {style} title={format!("{} bytes", f.size)}>
+                            { &f.path }
+                            if f.path == entry {
+                                <span class="text-muted">{ " (entry)" }</span>
+                            }
+                        </li>
+                    }
+                }) }
+            </ul>
+            <div style="flex:1; max-height: 400px; overflow-y: auto; background-color: #f8f9fa;">
+                if let Some(err) = &*error {
+                    <div class="alert alert-danger">{ err }</div>
+                }
+                <pre><code>{ content }</code></pre>
+            </div>
+        </div>
+    }
+}
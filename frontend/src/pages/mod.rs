@@ -1,4 +1,5 @@
 pub mod not_found;
+pub mod problems;
 pub mod submission_detail;
 pub mod submissions;
 pub mod submit;
@@ -0,0 +1,64 @@
+use crate::router::Route;
+use crate::types::{ProblemMeta, ProblemsResponse, api_error_message};
+use gloo_net::http::Request;
+use wasm_bindgen_futures::spawn_local;
+use yew::prelude::*;
+use yew_router::prelude::*;
+
+#[function_component(ProblemsPage)]
+pub fn problems_page() -> Html {
+    let problems = use_state(Vec::<ProblemMeta>::new);
+    let error = use_state(|| None::<String>);
+
+    // 初回ロードで一覧取得
+    {
+        let problems = problems.clone();
+        let error = error.clone();
+        use_effect_with((), move |_| {
+            spawn_local(async move {
+                match Request::get("/api/problems").send().await {
+                    Ok(response) if response.ok() => {
+                        match response.json::<ProblemsResponse>().await {
+                            Ok(resp) => problems.set(resp.problems),
+                            Err(e) => error.set(Some(format!("Parse error: {}", e))),
+                        }
+                    }
+                    Ok(response) => error.set(Some(api_error_message(&response).await)),
+                    Err(e) => error.set(Some(format!("Request error: {}", e))),
+                }
+            });
+        });
+    }
+
+    if let Some(err_msg) = &*error {
+        return html! { <div class="alert alert-danger">{ err_msg }</div> };
+    }
+
+    html! {
+        <>
+            <h1>{"Problems"}</h1>
+            <table class="table">
+                <thead>
+                    <tr>
+                        <th>{"ID"}</th>
+                        <th>{"Name"}</th>
+                        <th>{"Created At"}</th>
+                    </tr>
+                </thead>
+                <tbody>
+                { for problems.iter().map(|p| html! {
+                    <tr>
+                        <td>{p.id}</td>
+                        <td>
+                            <Link<Route> to={Route::Submissions { problem: p.name.clone() }}>
+                                {p.name.clone()}
+                            </Link<Route>>
+                        </td>
+                        <td>{crate::types::format_datetime_minute(&p.created_at)}</td>
+                    </tr>
+                })}
+                </tbody>
+            </table>
+        </>
+    }
+}
@@ -1,7 +1,10 @@
 // This is a placeholder for the submission detail page.
 // We will implement this in the next step.
 
+use crate::components::source_files::SourceFiles;
+use crate::types::ExecutionResultMeta;
 use crate::types::SubmissionDetail as SubmissionDetailData; // Rename to avoid conflict
+use crate::types::api_error_message;
 use gloo_net::http::Request;
 use serde::Deserialize;
 use yew::prelude::*;
@@ -14,6 +17,7 @@ struct SubmissionResponse {
 
 #[derive(Properties, PartialEq)]
 pub struct Props {
+    pub problem: String,
     pub id: i32,
 }
 
@@ -25,10 +29,11 @@ pub fn submission_detail(props: &Props) -> Html {
     {
         let submission = submission.clone();
         let error = error.clone();
+        let problem = props.problem.clone();
         let id = props.id;
 
-        use_effect_with((id,), move |_| {
-            let url = format!("/api/submissions/{}", id);
+        use_effect_with((problem, id), move |(problem, id)| {
+            let url = format!("/api/problems/{}/submissions/{}", problem, id);
             wasm_bindgen_futures::spawn_local(async move {
                 match Request::get(&url).send().await {
                     Ok(response) => {
@@ -42,10 +47,7 @@ pub fn submission_detail(props: &Props) -> Html {
                                 }
                             }
                         } else {
-                            error.set(Some(format!(
-                                "API request failed: {}",
-                                response.status_text()
-                            )));
+                            error.set(Some(api_error_message(&response).await));
                         }
                     }
                     Err(e) => error.set(Some(format!("Failed to send request: {}", e))),
@@ -64,16 +66,18 @@ pub fn submission_detail(props: &Props) -> Html {
             <div>
                 <div class="card mb-4">
                     <div class="card-header">
-                        { format!("Submission #{}", s.id) }
+                        { format!("Submission #{} {}", s.id, s.title.as_deref().unwrap_or("")) }
                     </div>
                     <div class="card-body">
                         <h5 class="card-title">{ "Summary" }</h5>
                         <p class="card-text">{ format!("Avg Score: {:.2}", s.average_score) }</p>
                         <p class="card-text">{ format!("Avg Time: {:.2} ms", s.average_execution_time_ms) }</p>
                         <p class="card-text">{ format!("Test Cases: {}", s.number_of_test_cases) }</p>
+                        <p class="card-text">{ format!("Test Case Set: {}", s.test_case_set.as_deref().unwrap_or("-")) }</p>
+                        { crate::types::render_submission_metadata(s) }
                         <hr />
                         <h5 class="card-title">{ "Source Code" }</h5>
-                        <pre><code>{ &s.source_code }</code></pre>
+                        <SourceFiles problem={props.problem.clone()} submission={s.clone()} />
                     </div>
                     <div class="card-footer text-muted">
                         { format!("Submitted at: {}", crate::types::format_datetime_minute(&s.created_at)) }
@@ -86,6 +90,8 @@ pub fn submission_detail(props: &Props) -> Html {
                         <tr>
                             <th>{ "Test Case ID" }</th>
                             <th>{ "Score" }</th>
+                            <th>{ "Best Known" }</th>
+                            <th>{ "% Best" }</th>
                             <th>{ "Execution Time (ms)" }</th>
                         </tr>
                     </thead>
@@ -94,13 +100,56 @@ pub fn submission_detail(props: &Props) -> Html {
                             <tr>
                                 <td>{ res.test_case_id }</td>
                                 <td>{ res.score }</td>
+                                <td>{ res.best_known.map(|b| b.to_string()).unwrap_or_default() }</td>
+                                <td>{ crate::types::format_relative(res.relative_score) }</td>
                                 <td>{ res.execution_time_ms }</td>
                             </tr>
                         }) }
                     </tbody>
                 </table>
+
+                // 既知の最良スコアとの差が大きい (伸びしろのある) ケース
+                <h2>{ "Worst Gaps" }</h2>
+                <table class="table table-striped">
+                    <thead>
+                        <tr>
+                            <th>{ "Test Case ID" }</th>
+                            <th>{ "Score" }</th>
+                            <th>{ "Best Known" }</th>
+                            <th>{ "% Best" }</th>
+                        </tr>
+                    </thead>
+                    <tbody>
+                        { for worst_gaps(&s.execution_results).into_iter().map(|res| html! {
+                            <tr>
+                                <td>{ res.test_case_id }</td>
+                                <td>{ res.score }</td>
+                                <td>{ res.best_known.map(|b| b.to_string()).unwrap_or_default() }</td>
+                                <td>{ crate::types::format_relative(res.relative_score) }</td>
+                            </tr>
+                        }) }
+                    </tbody>
+                </table>
             </div>
         },
         None => html! { <p>{ "Loading submission details..." }</p> },
     }
 }
+
+/// 表示するワーストギャップの件数
+const WORST_GAPS: usize = 10;
+
+/// 既知の最良スコアに対する割合が低い順に並べた実行結果
+fn worst_gaps(results: &[ExecutionResultMeta]) -> Vec<&ExecutionResultMeta> {
+    let mut gaps: Vec<&ExecutionResultMeta> = results
+        .iter()
+        .filter(|r| r.relative_score.is_some())
+        .collect();
+    gaps.sort_by(|a, b| {
+        a.relative_score
+            .partial_cmp(&b.relative_score)
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
+    gaps.truncate(WORST_GAPS);
+    gaps
+}
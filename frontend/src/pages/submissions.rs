@@ -1,6 +1,7 @@
 // use crate::components::code_block::CodeBlock; // No longer needed
 use crate::components::item_list_panel::ItemListPanel;
-use crate::types::{SubmissionDetail, SubmissionMeta};
+use crate::components::source_files::SourceFiles;
+use crate::types::{SubmissionDetail, SubmissionMeta, api_error_message};
 use gloo_net::http::Request;
 use serde::Deserialize;
 use wasm_bindgen_futures::spawn_local;
@@ -17,21 +18,28 @@ struct SubmissionDetailResponse {
     submission: SubmissionDetail,
 }
 
+#[derive(Properties, PartialEq)]
+pub struct Props {
+    pub problem: String,
+}
+
 #[function_component(Submissions)]
-pub fn submissions() -> Html {
+pub fn submissions(props: &Props) -> Html {
     // 状態: 提出リストと選択された提出詳細
     let submission_metas = use_state(Vec::<SubmissionMeta>::new);
     let selected_submission = use_state(|| None::<SubmissionDetail>);
     let error = use_state(|| None::<String>);
 
-    // 初回ロードでリストを取得
+    // 初回ロードと問題の切り替え時にリストを取得
     {
         let submission_metas = submission_metas.clone();
         let selected_submission_state = selected_submission.clone();
         let error = error.clone();
-        use_effect_with((), move |_| {
+        use_effect_with(props.problem.clone(), move |problem| {
+            let problem = problem.clone();
             spawn_local(async move {
-                match Request::get("/api/submissions").send().await {
+                let url = format!("/api/problems/{}/submissions", problem);
+                match Request::get(&url).send().await {
                     Ok(response) if response.ok() => {
                         match response.json::<SubmissionsListResponse>().await {
                             Ok(mut resp) => {
@@ -46,9 +54,12 @@ pub fn submissions() -> Html {
                                 // 先頭を自動選択
                                 if let Some(first_id) = first_id_opt {
                                     // Fetch detail of first submission
-                                    match Request::get(&format!("/api/submissions/{}", first_id))
-                                        .send()
-                                        .await
+                                    match Request::get(&format!(
+                                        "/api/problems/{}/submissions/{}",
+                                        problem, first_id
+                                    ))
+                                    .send()
+                                    .await
                                     {
                                         Ok(detail_resp) if detail_resp.ok() => {
                                             if let Ok(detail_json) =
@@ -58,14 +69,17 @@ pub fn submissions() -> Html {
                                                     .set(Some(detail_json.submission));
                                             }
                                         }
-                                        _ => {}
+                                        Ok(detail_resp) => {
+                                            error.set(Some(api_error_message(&detail_resp).await))
+                                        }
+                                        Err(_) => {}
                                     }
                                 }
                             }
                             Err(e) => error.set(Some(format!("Parse error: {}", e))),
                         }
                     }
-                    Ok(response) => error.set(Some(format!("API error: {}", response.status()))),
+                    Ok(response) => error.set(Some(api_error_message(&response).await)),
                     Err(e) => error.set(Some(format!("Request error: {}", e))),
                 }
             });
@@ -76,11 +90,12 @@ pub fn submissions() -> Html {
     let on_select = {
         let selected_submission = selected_submission.clone();
         let error = error.clone();
+        let problem = props.problem.clone();
         Callback::from(move |id: i32| {
             let selected_submission = selected_submission.clone();
             let error = error.clone();
+            let url = format!("/api/problems/{}/submissions/{}", problem, id);
             spawn_local(async move {
-                let url = format!("/api/submissions/{}", id);
                 match Request::get(&url).send().await {
                     Ok(response) if response.ok() => {
                         match response.json::<SubmissionDetailResponse>().await {
@@ -88,7 +103,7 @@ pub fn submissions() -> Html {
                             Err(e) => error.set(Some(format!("Parse error: {}", e))),
                         }
                     }
-                    Ok(response) => error.set(Some(format!("API error: {}", response.status()))),
+                    Ok(response) => error.set(Some(api_error_message(&response).await)),
                     Err(e) => error.set(Some(format!("Request error: {}", e))),
                 }
             });
@@ -101,14 +116,32 @@ pub fn submissions() -> Html {
         return html! { <div class="alert alert-danger">{ err_msg }</div> };
     }
 
-    let render_item_row = Callback::from(|meta: SubmissionMeta| {
-        html! {
-            <>
-                <td style="padding:4px;">{meta.id}</td>
-                <td style="padding:4px;">{format!("{:.2}", meta.average_score)}</td>
-                <td style="padding:4px;">{meta.number_of_test_cases}</td>
-                <td style="padding:4px;">{crate::types::format_datetime_minute(&meta.created_at)}</td>
-            </>
+    // 同じ環境で実行した提出に同じ記号を付ける
+    let environments = crate::types::distinct_environments(&submission_metas);
+
+    let render_item_row = Callback::from({
+        let environments = environments.clone();
+        move |meta: SubmissionMeta| {
+            html! {
+                <>
+                    <td style="padding:4px;">{meta.id}</td>
+                    <td style="padding:4px;">{meta.title.clone().unwrap_or_default()}</td>
+                    <td style="padding:4px;"><code>{
+                        // 未コミットの変更があれば * を付ける
+                        meta.git_commit.as_deref().map(|c| format!(
+                            "{}{}",
+                            crate::types::short_commit(c),
+                            if meta.git_dirty { "*" } else { "" },
+                        )).unwrap_or_default()
+                    }</code></td>
+                    <td style="padding:4px;">{meta.test_case_set.clone().unwrap_or_default()}</td>
+                    <td style="padding:4px;">{format!("{:.2}", meta.average_score)}</td>
+                    <td style="padding:4px;">{meta.number_of_test_cases}</td>
+                    <td style="padding:4px;">{meta.number_of_failures}</td>
+                    <td style="padding:4px;">{crate::types::environment_label(&environments, meta.environment.as_ref())}</td>
+                    <td style="padding:4px;">{crate::types::format_datetime_minute(&meta.created_at)}</td>
+                </>
+            }
         }
     });
 
@@ -121,8 +154,13 @@ pub fn submissions() -> Html {
             on_select={on_select}
             headers={vec![
                 "ID".to_string(),
+                "Title".to_string(),
+                "Commit".to_string(),
+                "Set".to_string(),
                 "Avg Score".to_string(),
                 "Test Cases".to_string(),
+                "Failed".to_string(),
+                "Env".to_string(),
                 "Created At".to_string(),
             ]}
             render_item_row={render_item_row}
@@ -135,12 +173,11 @@ pub fn submissions() -> Html {
             <div style="width:55%; padding-left: 1em;">
                 <h2>{ "Details" }</h2>
                 <div class="card">
-                    <div class="card-header">{format!("#{} @ {}", s.id, crate::types::format_datetime_minute(&s.created_at))}</div>
+                    <div class="card-header">{format!("#{} {} @ {}", s.id, s.title.as_deref().unwrap_or(""), crate::types::format_datetime_minute(&s.created_at))}</div>
                     <div class="card-body">
+                        { crate::types::render_submission_metadata(s) }
                         <h5 class="card-title">{ "Source Code" }</h5>
-                        <div style="max-height: 400px; overflow-y: auto; background-color: #f8f9fa;">
-                            <pre><code>{ &s.source_code }</code></pre>
-                        </div>
+                        <SourceFiles problem={props.problem.clone()} submission={s.clone()} />
                         <hr/>
                         <h5 class="card-title">{ "Execution Results" }</h5>
                         <div style="max-height: 300px; overflow-y: auto;">
@@ -149,6 +186,8 @@ pub fn submissions() -> Html {
                                     <tr>
                                         <th>{ "Test Case ID" }</th>
                                         <th>{ "Score" }</th>
+                                        <th>{ "Best Known" }</th>
+                                        <th>{ "% Best" }</th>
                                         <th>{ "Execution Time (ms)" }</th>
                                     </tr>
                                 </thead>
@@ -157,6 +196,8 @@ pub fn submissions() -> Html {
                                     <tr>
                                         <td>{res.test_case_id}</td>
                                         <td>{res.score}</td>
+                                        <td>{res.best_known.map(|b| b.to_string()).unwrap_or_default()}</td>
+                                        <td>{crate::types::format_relative(res.relative_score)}</td>
                                         <td>{res.execution_time_ms}</td>
                                     </tr>
                                 })}
@@ -177,6 +218,7 @@ pub fn submissions() -> Html {
     html! {
         <div>
             <h1>{ "Submissions" }</h1>
+            { crate::types::render_environment_warning(&environments) }
             <div style="display:flex; justify-content:space-between; align-items:flex-start;">
                 { list_panel }
                 { detail_panel }
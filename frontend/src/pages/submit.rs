@@ -1,12 +1,23 @@
+use crate::types::api_error_message;
 use gloo_net::http::Request;
 use wasm_bindgen_futures::spawn_local;
 use yew::prelude::*;
 
+#[derive(Properties, PartialEq)]
+pub struct Props {
+    pub problem: String,
+}
+
 #[function_component(SubmitPage)]
-pub fn submit_page() -> Html {
+pub fn submit_page(props: &Props) -> Html {
     let source_code = use_state(|| String::new());
     let cases = use_state(|| 10u32);
     let parallel = use_state(|| 1u32);
+    let title = use_state(|| String::new());
+    let tags = use_state(|| String::new());
+    let author = use_state(|| String::new());
+    let parent = use_state(|| String::new());
+    let notes = use_state(|| String::new());
     let result = use_state(|| None::<String>);
     let loading = use_state(|| false);
 
@@ -36,10 +47,28 @@ pub fn submit_page() -> Html {
         })
     };
 
+    let on_title = on_text_input(&title);
+    let on_tags = on_text_input(&tags);
+    let on_author = on_text_input(&author);
+    let on_parent = on_text_input(&parent);
+    let on_notes = {
+        let notes = notes.clone();
+        Callback::from(move |e: InputEvent| {
+            let input: web_sys::HtmlTextAreaElement = e.target_unchecked_into();
+            notes.set(input.value());
+        })
+    };
+
     let on_submit = {
+        let url = format!("/api/problems/{}/run", props.problem);
         let source_code = source_code.clone();
         let cases = cases.clone();
         let parallel = parallel.clone();
+        let title = title.clone();
+        let tags = tags.clone();
+        let author = author.clone();
+        let parent = parent.clone();
+        let notes = notes.clone();
         let result = result.clone();
         let loading = loading.clone();
         Callback::from(move |e: SubmitEvent| {
@@ -48,29 +77,48 @@ pub fn submit_page() -> Html {
             let source_code = (*source_code).clone();
             let cases = *cases;
             let parallel = *parallel;
+            // 空欄は送らない (タグはカンマ区切りで入力する)
+            let title = non_empty(&title);
+            let author = non_empty(&author);
+            let notes = non_empty(&notes);
+            let tags: Vec<String> = tags
+                .split(',')
+                .map(str::trim)
+                .filter(|t| !t.is_empty())
+                .map(str::to_string)
+                .collect();
+            let parent_id = parent.trim().parse::<i32>().ok();
             let result = result.clone();
             let loading = loading.clone();
+            let url = url.clone();
             spawn_local(async move {
                 let body = serde_json::json!({
                     "source_code": source_code,
                     "cases": cases,
                     "parallel": parallel,
-                    "timeout": 10
+                    "timeout": 10,
+                    "title": title,
+                    "tags": tags,
+                    "author": author,
+                    "notes": notes,
+                    "parent_id": parent_id
                 });
-                let resp = Request::post("/api/run")
+                let resp = Request::post(&url)
                     .header("Content-Type", "application/json")
                     .body(body.to_string())
                     .expect("body作成失敗")
                     .send()
                     .await;
                 match resp {
-                    Ok(r) => {
+                    Ok(r) if r.ok() => {
                         let text = r
                             .text()
                             .await
                             .unwrap_or_else(|_| "レスポンス取得失敗".to_string());
                         result.set(Some(text));
                     }
+                    // 実行前に断られた (問題やセットがない、親の指定が正しくないなど)
+                    Ok(r) => result.set(Some(format!("エラー: {}", api_error_message(&r).await))),
                     Err(e) => {
                         result.set(Some(format!("リクエスト失敗: {}", e)));
                     }
@@ -82,7 +130,7 @@ pub fn submit_page() -> Html {
 
     html! {
         <>
-            <h1>{"Submit"}</h1>
+            <h1>{format!("Submit ({})", props.problem)}</h1>
             <form onsubmit={on_submit}>
                 <div style="margin-bottom:1em;">
                     <label>{"ソースコード"}</label><br/>
@@ -96,6 +144,26 @@ pub fn submit_page() -> Html {
                     <label>{"並列数"}</label><br/>
                     <input type="number" min=1 value={parallel.to_string()} oninput={on_parallel} required=true />
                 </div>
+                <div style="margin-bottom:1em;">
+                    <label>{"タイトル"}</label><br/>
+                    <input type="text" value={(*title).clone()} oninput={on_title} />
+                </div>
+                <div style="margin-bottom:1em;">
+                    <label>{"タグ (カンマ区切り)"}</label><br/>
+                    <input type="text" value={(*tags).clone()} oninput={on_tags} />
+                </div>
+                <div style="margin-bottom:1em;">
+                    <label>{"作者"}</label><br/>
+                    <input type="text" value={(*author).clone()} oninput={on_author} />
+                </div>
+                <div style="margin-bottom:1em;">
+                    <label>{"元にした提出の ID"}</label><br/>
+                    <input type="number" min=1 value={(*parent).clone()} oninput={on_parent} />
+                </div>
+                <div style="margin-bottom:1em;">
+                    <label>{"メモ"}</label><br/>
+                    <textarea rows=3 cols=60 value={(*notes).clone()} oninput={on_notes} />
+                </div>
                 <button type="submit" disabled={*loading}>{ if *loading { "送信中..." } else { "実行" } }</button>
             </form>
             <div style="margin-top:2em;">
@@ -105,3 +173,18 @@ pub fn submit_page() -> Html {
         </>
     }
 }
+
+// テキスト入力の内容を state に反映するコールバック
+fn on_text_input(state: &UseStateHandle<String>) -> Callback<InputEvent> {
+    let state = state.clone();
+    Callback::from(move |e: InputEvent| {
+        let input: web_sys::HtmlInputElement = e.target_unchecked_into();
+        state.set(input.value());
+    })
+}
+
+// 空白だけの入力は未指定として扱う
+fn non_empty(value: &str) -> Option<String> {
+    let value = value.trim();
+    (!value.is_empty()).then(|| value.to_string())
+}
@@ -1,44 +1,62 @@
 use crate::components::item_list_panel::ItemListPanel;
-use crate::types::{TestCase, TestCaseMeta, TestCaseResponse, TestCasesResponse};
+use crate::types::{
+    TestCase, TestCaseMeta, TestCaseResponse, TestCasesResponse, api_error_message,
+};
 use gloo_net::http::Request;
 use wasm_bindgen_futures::spawn_local;
 use yew::prelude::*;
 
+#[derive(Properties, PartialEq)]
+pub struct Props {
+    pub problem: String,
+}
+
 #[function_component(TestCasesPage)]
-pub fn test_cases_page() -> Html {
+pub fn test_cases_page(props: &Props) -> Html {
     let metas = use_state(|| Vec::<TestCaseMeta>::new());
     let selected = use_state(|| Option::<TestCase>::None);
+    let error = use_state(|| None::<String>);
 
-    // 初回ロードで一覧取得
+    // 初回ロードと問題の切り替え時に一覧取得
     {
         let metas = metas.clone();
         let selected_state = selected.clone();
-        use_effect_with((), move |_| {
+        let error = error.clone();
+        use_effect_with(props.problem.clone(), move |problem| {
             let metas = metas.clone();
+            let problem = problem.clone();
             spawn_local(async move {
-                if let Ok(resp) = Request::get("/api/test_cases").send().await {
-                    if let Ok(json) = resp.json::<TestCasesResponse>().await {
-                        if !json.test_cases.is_empty() {
-                            let first_id = json.test_cases[0].id;
-                            // 先に state を設定（リスト描画用）
-                            metas.set(json.test_cases.clone());
+                let url = format!("/api/problems/{}/test_cases", problem);
+                match Request::get(&url).send().await {
+                    Ok(resp) if resp.ok() => {
+                        if let Ok(json) = resp.json::<TestCasesResponse>().await {
+                            error.set(None);
+                            if !json.test_cases.is_empty() {
+                                let first_id = json.test_cases[0].id;
+                                // 先に state を設定（リスト描画用）
+                                metas.set(json.test_cases.clone());
 
-                            // 詳細を取得して自動選択
-                            if let Ok(detail_resp) =
-                                Request::get(&format!("/api/test_cases/{}", first_id))
-                                    .send()
-                                    .await
-                            {
-                                if let Ok(detail_json) =
-                                    detail_resp.json::<TestCaseResponse>().await
+                                // 詳細を取得して自動選択
+                                if let Ok(detail_resp) = Request::get(&format!(
+                                    "/api/problems/{}/test_cases/{}",
+                                    problem, first_id
+                                ))
+                                .send()
+                                .await
                                 {
-                                    selected_state.set(Some(detail_json.test_case));
+                                    if let Ok(detail_json) =
+                                        detail_resp.json::<TestCaseResponse>().await
+                                    {
+                                        selected_state.set(Some(detail_json.test_case));
+                                    }
                                 }
+                            } else {
+                                metas.set(json.test_cases);
                             }
-                        } else {
-                            metas.set(json.test_cases);
                         }
                     }
+                    Ok(resp) => error.set(Some(api_error_message(&resp).await)),
+                    Err(e) => error.set(Some(format!("Request error: {}", e))),
                 }
             });
         });
@@ -47,14 +65,21 @@ pub fn test_cases_page() -> Html {
     // 行クリックハンドラ
     let on_select = {
         let selected = selected.clone();
+        let error = error.clone();
+        let problem = props.problem.clone();
         Callback::from(move |id: i32| {
             let selected = selected.clone();
+            let error = error.clone();
+            let url = format!("/api/problems/{}/test_cases/{}", problem, id);
             spawn_local(async move {
-                let url = format!("/api/test_cases/{}", id);
-                if let Ok(resp) = Request::get(&url).send().await {
-                    if let Ok(json) = resp.json::<TestCaseResponse>().await {
-                        selected.set(Some(json.test_case));
+                match Request::get(&url).send().await {
+                    Ok(resp) if resp.ok() => {
+                        if let Ok(json) = resp.json::<TestCaseResponse>().await {
+                            selected.set(Some(json.test_case));
+                        }
                     }
+                    Ok(resp) => error.set(Some(api_error_message(&resp).await)),
+                    Err(e) => error.set(Some(format!("Request error: {}", e))),
                 }
             });
         })
@@ -97,6 +122,9 @@ pub fn test_cases_page() -> Html {
     html! {
         <>
             <h1>{"TestCases"}</h1>
+            if let Some(err) = &*error {
+                <div class="alert alert-danger">{ err }</div>
+            }
             <div style="display:flex; justify-content:space-between; align-items:flex-start;">
                 {list_panel}
                 {detail_panel}
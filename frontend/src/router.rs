@@ -1,20 +1,23 @@
 use crate::pages::{
-    not_found::NotFound, submission_detail::SubmissionDetail, submissions::Submissions,
-    submit::SubmitPage, test_cases::TestCasesPage, visualize::VisualizePage,
+    not_found::NotFound, problems::ProblemsPage, submission_detail::SubmissionDetail,
+    submissions::Submissions, submit::SubmitPage, test_cases::TestCasesPage,
+    visualize::VisualizePage,
 };
 use yew::prelude::*;
 use yew_router::prelude::*;
 
 #[derive(Clone, Routable, PartialEq)]
 pub enum Route {
-    #[at("/submit")]
-    Submit,
-    #[at("/submissions")]
-    Submissions,
-    #[at("/submissions/:id")]
-    SubmissionDetail { id: i32 },
-    #[at("/test_cases")]
-    TestCases,
+    #[at("/")]
+    Problems,
+    #[at("/problems/:problem/submit")]
+    Submit { problem: String },
+    #[at("/problems/:problem/submissions")]
+    Submissions { problem: String },
+    #[at("/problems/:problem/submissions/:id")]
+    SubmissionDetail { problem: String, id: i32 },
+    #[at("/problems/:problem/test_cases")]
+    TestCases { problem: String },
     #[at("/visualize")]
     Visualize,
     #[not_found]
@@ -22,12 +25,28 @@ pub enum Route {
     NotFound,
 }
 
+impl Route {
+    /// 問題ごとのページなら、その問題名
+    pub fn problem(&self) -> Option<&str> {
+        match self {
+            Route::Submit { problem }
+            | Route::Submissions { problem }
+            | Route::SubmissionDetail { problem, .. }
+            | Route::TestCases { problem } => Some(problem),
+            _ => None,
+        }
+    }
+}
+
 pub fn switch(routes: Route) -> Html {
     match routes {
-        Route::Submit => html! { <SubmitPage /> },
-        Route::Submissions => html! { <Submissions /> },
-        Route::SubmissionDetail { id } => html! { <SubmissionDetail id={id} /> },
-        Route::TestCases => html! { <TestCasesPage /> },
+        Route::Problems => html! { <ProblemsPage /> },
+        Route::Submit { problem } => html! { <SubmitPage problem={problem} /> },
+        Route::Submissions { problem } => html! { <Submissions problem={problem} /> },
+        Route::SubmissionDetail { problem, id } => {
+            html! { <SubmissionDetail problem={problem} id={id} /> }
+        }
+        Route::TestCases { problem } => html! { <TestCasesPage problem={problem} /> },
         Route::Visualize => html! { <VisualizePage /> },
         Route::NotFound => html! { <NotFound /> },
     }
@@ -1,6 +1,38 @@
 use crate::components::item_list_panel::ListItem;
 use serde::Deserialize;
 
+// API のエラー時の本文: {"code": ..., "message": ..., "details": ...}
+#[derive(Clone, Deserialize, PartialEq)]
+pub struct ApiErrorBody {
+    pub code: String,
+    pub message: String,
+}
+
+/// 失敗したレスポンスから表示するメッセージを作る。本文がエラーの JSON でなければステータスを出す
+pub async fn api_error_message(response: &gloo_net::http::Response) -> String {
+    match response.json::<ApiErrorBody>().await {
+        Ok(body) => body.message,
+        Err(_) => format!(
+            "API error: {} {}",
+            response.status(),
+            response.status_text()
+        ),
+    }
+}
+
+// For the problem list: /api/problems
+#[derive(Clone, Deserialize, PartialEq)]
+pub struct ProblemMeta {
+    pub id: i32,
+    pub name: String,
+    pub created_at: String,
+}
+
+#[derive(Clone, Deserialize, PartialEq)]
+pub struct ProblemsResponse {
+    pub problems: Vec<ProblemMeta>,
+}
+
 #[derive(Clone, Deserialize, PartialEq)]
 pub struct TestCaseMeta {
     pub id: i32,
@@ -32,14 +64,32 @@ pub struct TestCaseResponse {
     pub test_case: TestCase,
 }
 
-// For the submissions list: /api/submissions
+// For the submissions list: /api/problems/{problem}/submissions
 #[derive(Clone, PartialEq, Deserialize, Debug)]
 pub struct SubmissionMeta {
     pub id: i32,
     pub number_of_test_cases: i32,
     pub average_score: f64,
     pub average_execution_time_ms: f64,
+    #[serde(default)]
+    pub number_of_failures: i32,
     pub created_at: String, // Assuming DateTime<Utc> serializes to a string
+    #[serde(default)]
+    pub test_case_set: Option<String>,
+    #[serde(default)]
+    pub title: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub author: Option<String>,
+    #[serde(default)]
+    pub parent_id: Option<i32>,
+    #[serde(default)]
+    pub git_commit: Option<String>,
+    #[serde(default)]
+    pub git_dirty: bool,
+    #[serde(default)]
+    pub environment: Option<Environment>,
 }
 
 impl ListItem for SubmissionMeta {
@@ -48,12 +98,21 @@ impl ListItem for SubmissionMeta {
     }
 }
 
-// For the submission detail: /api/submissions/{id}
+// For the submission detail: /api/problems/{problem}/submissions/{id}
 #[derive(Clone, PartialEq, Deserialize, Debug)]
 pub struct ExecutionResultMeta {
     pub test_case_id: i32,
     pub score: i64,
     pub execution_time_ms: i32,
+    #[serde(default)]
+    pub best_known: Option<i64>,
+    #[serde(default)]
+    pub relative_score: Option<f64>,
+}
+
+// 既知の最良スコアに対する割合の表示 (なければ空)
+pub fn format_relative(relative: Option<f64>) -> String {
+    relative.map(|r| format!("{:.2}%", r)).unwrap_or_default()
 }
 
 #[derive(Clone, PartialEq, Deserialize, Debug)]
@@ -64,9 +123,197 @@ pub struct SubmissionDetail {
     pub average_score: f64,
     pub average_execution_time_ms: f64,
     pub created_at: String,
+    #[serde(default)]
+    pub test_case_set: Option<String>,
+    #[serde(default)]
+    pub title: Option<String>,
+    #[serde(default)]
+    pub notes: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub author: Option<String>,
+    #[serde(default)]
+    pub parent_id: Option<i32>,
+    #[serde(default)]
+    pub source_path: Option<String>,
+    #[serde(default)]
+    pub git_commit: Option<String>,
+    #[serde(default)]
+    pub git_branch: Option<String>,
+    #[serde(default)]
+    pub git_dirty: bool,
+    #[serde(default)]
+    pub git_diff: Option<String>,
+    #[serde(default)]
+    pub environment: Option<Environment>,
+    #[serde(default)]
+    pub entry_point: Option<String>,
+    #[serde(default)]
+    pub files: Vec<SubmissionFileMeta>,
     pub execution_results: Vec<ExecutionResultMeta>,
 }
 
+// 複数ファイルの提出に含まれるファイル
+#[derive(Clone, PartialEq, Deserialize, Debug)]
+pub struct SubmissionFileMeta {
+    pub path: String,
+    pub size: u64,
+}
+
+// 提出を実行した環境
+#[derive(Clone, PartialEq, Deserialize, Debug)]
+pub struct Environment {
+    #[serde(default)]
+    pub hostname: Option<String>,
+    #[serde(default)]
+    pub cpu_model: Option<String>,
+    #[serde(default)]
+    pub cpu_cores: Option<i32>,
+    #[serde(default)]
+    pub os: Option<String>,
+    #[serde(default)]
+    pub compiler_version: Option<String>,
+    #[serde(default)]
+    pub compile_cmd: Option<String>,
+    #[serde(default)]
+    pub exec_cmd: Option<String>,
+    pub runner: String,
+    pub parallelism: i32,
+    pub summary: String,
+}
+
+impl Environment {
+    // 違う項目の名前。ホスト名と、片方で分からない項目は比べない (CLI の leader-board と同じ)
+    pub fn differences(&self, other: &Environment) -> Vec<&'static str> {
+        fn differ<T: PartialEq>(a: &Option<T>, b: &Option<T>) -> bool {
+            matches!((a, b), (Some(a), Some(b)) if a != b)
+        }
+        [
+            ("runner", self.runner != other.runner),
+            ("cpu", differ(&self.cpu_model, &other.cpu_model)),
+            ("cores", differ(&self.cpu_cores, &other.cpu_cores)),
+            ("os", differ(&self.os, &other.os)),
+            (
+                "compiler",
+                differ(&self.compiler_version, &other.compiler_version),
+            ),
+            ("compile_cmd", differ(&self.compile_cmd, &other.compile_cmd)),
+            ("exec_cmd", differ(&self.exec_cmd, &other.exec_cmd)),
+            ("parallelism", self.parallelism != other.parallelism),
+        ]
+        .into_iter()
+        .filter(|&(_, differs)| differs)
+        .map(|(name, _)| name)
+        .collect()
+    }
+}
+
+// 提出一覧に現れる環境 (同じものは 1 つにまとめる)
+pub fn distinct_environments(submissions: &[SubmissionMeta]) -> Vec<Environment> {
+    let mut environments: Vec<Environment> = Vec::new();
+    for env in submissions.iter().filter_map(|s| s.environment.as_ref()) {
+        if !environments.iter().any(|e| e.differences(env).is_empty()) {
+            environments.push(env.clone());
+        }
+    }
+    environments
+}
+
+// 環境の記号 (A, B, ...)。記録がなければ "-"
+pub fn environment_label(environments: &[Environment], env: Option<&Environment>) -> String {
+    let Some(index) = env.and_then(|env| {
+        environments
+            .iter()
+            .position(|e| e.differences(env).is_empty())
+    }) else {
+        return "-".to_string();
+    };
+    match u8::try_from(index) {
+        Ok(i) if i < 26 => char::from(b'A' + i).to_string(),
+        _ => (index + 1).to_string(),
+    }
+}
+
+// 違う環境で実行した提出が混ざっていれば警告を出す
+pub fn render_environment_warning(environments: &[Environment]) -> yew::Html {
+    if environments.len() < 2 {
+        return yew::Html::default();
+    }
+    let mut fields: Vec<&str> = Vec::new();
+    for env in &environments[1..] {
+        for field in environments[0].differences(env) {
+            if !fields.contains(&field) {
+                fields.push(field);
+            }
+        }
+    }
+    yew::html! {
+        <div class="alert alert-warning">
+            { format!(
+                "These submissions ran in {} different environments (differing: {}); timing-dependent scores may not be comparable.",
+                environments.len(),
+                fields.join(", "),
+            ) }
+            <ul style="margin-bottom:0;">
+                { for environments.iter().map(|env| yew::html! {
+                    <li>{ format!("{}: {}", environment_label(environments, Some(env)), env.summary) }</li>
+                }) }
+            </ul>
+        </div>
+    }
+}
+
+// 提出の作者・タグ・親・メモのうち、付いているものを表示する
+pub fn render_submission_metadata(s: &SubmissionDetail) -> yew::Html {
+    yew::html! {
+        <>
+            if let Some(author) = &s.author {
+                <p class="card-text">{ format!("Author: {}", author) }</p>
+            }
+            if !s.tags.is_empty() {
+                <p class="card-text">{ format!("Tags: {}", s.tags.join(", ")) }</p>
+            }
+            if let Some(parent_id) = s.parent_id {
+                <p class="card-text">{ format!("Parent: #{}", parent_id) }</p>
+            }
+            if let Some(commit) = &s.git_commit {
+                <p class="card-text">{ format!(
+                    "Git: {}{}{}",
+                    short_commit(commit),
+                    s.git_branch.as_ref().map(|b| format!(" ({})", b)).unwrap_or_default(),
+                    if s.git_dirty { " dirty" } else { "" },
+                ) }</p>
+            }
+            if let Some(path) = &s.source_path {
+                <p class="card-text">{ format!("Source: {}", path) }</p>
+            }
+            if let Some(env) = &s.environment {
+                <p class="card-text">{ format!("Environment: {}", env.summary) }</p>
+                if let Some(hostname) = &env.hostname {
+                    <p class="card-text">{ format!("Host: {}", hostname) }</p>
+                }
+            }
+            if let Some(notes) = &s.notes {
+                <p class="card-text" style="white-space: pre-wrap;">{ notes }</p>
+            }
+            if let Some(diff) = &s.git_diff {
+                <details>
+                    <summary>{ "Uncommitted changes" }</summary>
+                    <div style="max-height: 300px; overflow-y: auto; background-color: #f8f9fa;">
+                        <pre><code>{ diff }</code></pre>
+                    </div>
+                </details>
+            }
+        </>
+    }
+}
+
+// コミットハッシュの先頭 7 文字
+pub fn short_commit(commit: &str) -> &str {
+    commit.get(..7).unwrap_or(commit)
+}
+
 // 日時文字列 (RFC3339想定) を「YYYY-MM-DD HH:MM」の形に整形するヘルパ
 // 例: "2024-07-24T10:39:12Z" -> "2024-07-24 10:39"
 pub fn format_datetime_minute(datetime: &str) -> String {
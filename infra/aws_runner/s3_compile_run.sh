@@ -2,25 +2,63 @@
 # s3_compile_run.sh: C++ ソースを S3 から取得 → コンパイル → 入力ファイル群に対して実行し、結果を S3 へアップロードする
 # 必須環境変数:
 #   CODE_BUCKET   : C++ ソースが格納されている S3 バケット
-#   CODE_KEY      : ソースファイルのキー (例: src/main.cpp)
-#   SEED_START    : 実行対象シードの開始番号 (整数, 0 など)
+#   CODE_KEY      : ソースファイルのキー (例: src/main.cpp)。CODE_ENTRY があればバンドル (tar.gz) のキー
+# 実行対象の指定 (どちらか):
+#   CASE_LIST_KEY : 配列ジョブ用。ケース ID を 1 行に 1 件並べたファイルの IO_BUCKET 上のキー。
+#                   子ジョブ i は (i / REPEAT + 1) 行目のケースを HEURS_RNG_SEED = i % REPEAT で
+#                   1 回だけ実行する (配列ジョブでない場合、インデックスは 0 とみなす)。
+#                   入力は <INPUT_PREFIX><ケース ID><INPUT_SUFFIX>、結果のキーは <ケース ID>_<HEURS_RNG_SEED>
+#   REPEAT        : CASE_LIST_KEY と合わせて使う、1 ケースあたりの実行回数 (デフォルト 1)
+#   SEED_START    : 実行対象シードの開始番号 (整数, 0 など)。入力は <INPUT_PREFIX><4 桁のシード><INPUT_SUFFIX>
 #   SEED_END      : 実行対象シードの終了番号 (整数, 開始以上)
 # 任意環境変数:
+#   CODE_ENTRY    : 複数ファイルの提出のとき、バンドル内のエントリポイントのパス (例: src/main.cpp)。
+#                   バンドルを /tmp/work に展開し、そこでエントリポイントをコンパイルする
 #   IO_BUCKET     : 入力/出力ファイルを置く S3 バケット (デフォルト CODE_BUCKET)
 #   INPUT_PREFIX  : 入力ファイルのプレフィックス (デフォルト inputs/)
 #   INPUT_SUFFIX  : 入力ファイルのサフィックス (デフォルト .txt)
 #   OUTPUT_PREFIX : 出力ファイルのプレフィックス (デフォルト outputs/output_)
 #   OUTPUT_SUFFIX : 出力ファイルのサフィックス (デフォルト .txt)
+#   ERROR_PREFIX  : 標準エラー出力ファイルのプレフィックス (デフォルト errors/error_)
+#   ERROR_SUFFIX  : 標準エラー出力ファイルのサフィックス (デフォルト .txt)
+#   STATUS_PREFIX : 終了コードファイルのプレフィックス (デフォルト status/status_)
+#   STATUS_SUFFIX : 終了コードファイルのサフィックス (デフォルト .txt)
+#   RUN_ID        : 実行 ID (ログ出力用。AWSRunner は runs/<RUN_ID>/ 以下を出力先に指定する)
+#   COMPILE_ERROR_KEY : コンパイルに失敗したとき、コンパイラの標準エラー出力を置く IO_BUCKET 上のキー
 
 set -euo pipefail
 
 # ---- 変数チェック ----
 : "${CODE_BUCKET:?Need to set CODE_BUCKET}"  # S3 バケット
 : "${CODE_KEY:?Need to set CODE_KEY}"        # ソースのキー
-: "${SEED_START:?Need to set SEED_START}"    # シード開始
-: "${SEED_END:?Need to set SEED_END}"        # シード終了
 
 IO_BUCKET="${IO_BUCKET:-$CODE_BUCKET}"
+
+# 実行する入力の名前と、結果のキーに使う ID を組で並べる
+INPUT_NAMES=()
+RESULT_IDS=()
+if [ -n "${CASE_LIST_KEY:-}" ]; then
+  # 配列ジョブでは子ジョブごとに 1 ケースの 1 回分を担当する
+  ARRAY_INDEX="${AWS_BATCH_JOB_ARRAY_INDEX:-0}"
+  REPEAT="${REPEAT:-1}"
+  echo "📥 Downloading case list: s3://${IO_BUCKET}/${CASE_LIST_KEY}"
+  aws s3 cp "s3://${IO_BUCKET}/${CASE_LIST_KEY}" /tmp/cases.txt
+  CASE_ID="$(sed -n "$((ARRAY_INDEX / REPEAT + 1))p" /tmp/cases.txt)"
+  : "${CASE_ID:?No case for array index ${ARRAY_INDEX}}"
+  export HEURS_RNG_SEED=$((ARRAY_INDEX % REPEAT))
+  INPUT_NAMES+=("$CASE_ID")
+  RESULT_IDS+=("${CASE_ID}_${HEURS_RNG_SEED}")
+else
+  : "${SEED_START:?Need to set SEED_START or CASE_LIST_KEY}"  # シード開始
+  : "${SEED_END:?Need to set SEED_END or CASE_LIST_KEY}"      # シード終了
+  export HEURS_RNG_SEED="${HEURS_RNG_SEED:-0}"
+  for ((seed=SEED_START; seed<=SEED_END; seed++)); do
+    printf -v seed_padded "%04d" "$seed"
+    INPUT_NAMES+=("$seed_padded")
+    RESULT_IDS+=("$seed")
+  done
+fi
+
 INPUT_PREFIX="${INPUT_PREFIX:-inputs/}"
 INPUT_SUFFIX="${INPUT_SUFFIX:-.txt}"
 OUTPUT_PREFIX="${OUTPUT_PREFIX:-outputs/output_}"
@@ -30,33 +68,70 @@ OUTPUT_SUFFIX="${OUTPUT_SUFFIX:-.txt}"
 ERROR_PREFIX="${ERROR_PREFIX:-errors/error_}"
 ERROR_SUFFIX="${ERROR_SUFFIX:-.txt}"
 
-echo "📥 Downloading source: s3://${CODE_BUCKET}/${CODE_KEY}"
-aws s3 cp "s3://${CODE_BUCKET}/${CODE_KEY}" /tmp/main.cpp
+# 終了コードファイルの S3 キー設定
+# プログラムが異常終了してもジョブは失敗させず、終了コードをケースごとの判定に使う
+STATUS_PREFIX="${STATUS_PREFIX:-status/status_}"
+STATUS_SUFFIX="${STATUS_SUFFIX:-.txt}"
+
+# コンパイルエラー時の終了コード (AWSRunner はこのコードでは Batch の再試行をしない)
+COMPILE_ERROR_EXIT_CODE=3
+
+echo "🆔 Run ID: ${RUN_ID:-(none)}"
+
+if [ -n "${CODE_ENTRY:-}" ]; then
+  echo "📥 Downloading bundle: s3://${CODE_BUCKET}/${CODE_KEY}"
+  aws s3 cp "s3://${CODE_BUCKET}/${CODE_KEY}" /tmp/bundle.tar.gz
+  mkdir -p /tmp/work
+  tar -xzf /tmp/bundle.tar.gz -C /tmp/work
+  SOURCE="/tmp/work/${CODE_ENTRY}"
+  cd /tmp/work
+else
+  echo "📥 Downloading source: s3://${CODE_BUCKET}/${CODE_KEY}"
+  aws s3 cp "s3://${CODE_BUCKET}/${CODE_KEY}" /tmp/main.cpp
+  SOURCE=/tmp/main.cpp
+fi
 
 echo "🔧 Compiling C++ source..."
-g++ -std=c++20 -O2 /tmp/main.cpp -o /tmp/main
+if ! g++ -std=c++20 -O2 "$SOURCE" -o /tmp/main 2> /tmp/compile_error.txt; then
+  echo "❌ Compile failed"
+  cat /tmp/compile_error.txt >&2
+  if [ -n "${COMPILE_ERROR_KEY:-}" ]; then
+    echo "↗️  Upload compiler output: s3://${IO_BUCKET}/${COMPILE_ERROR_KEY}"
+    aws s3 cp /tmp/compile_error.txt "s3://${IO_BUCKET}/${COMPILE_ERROR_KEY}"
+  fi
+  exit "$COMPILE_ERROR_EXIT_CODE"
+fi
+cat /tmp/compile_error.txt >&2
 
-echo "✅ Compile finished. Executing seeds ${SEED_START}-${SEED_END}"
+echo "✅ Compile finished. Executing ${#INPUT_NAMES[@]} case(s)"
 
-for ((seed=SEED_START; seed<=SEED_END; seed++)); do
-  printf -v seed_padded "%04d" "$seed"
-  INPUT_KEY="${INPUT_PREFIX}${seed_padded}${INPUT_SUFFIX}"
-  OUTPUT_KEY="${OUTPUT_PREFIX}${seed}${OUTPUT_SUFFIX}"
-  ERROR_KEY="${ERROR_PREFIX}${seed}${ERROR_SUFFIX}"
+for i in "${!INPUT_NAMES[@]}"; do
+  RESULT_ID="${RESULT_IDS[$i]}"
+  INPUT_KEY="${INPUT_PREFIX}${INPUT_NAMES[$i]}${INPUT_SUFFIX}"
+  OUTPUT_KEY="${OUTPUT_PREFIX}${RESULT_ID}${OUTPUT_SUFFIX}"
+  ERROR_KEY="${ERROR_PREFIX}${RESULT_ID}${ERROR_SUFFIX}"
+  STATUS_KEY="${STATUS_PREFIX}${RESULT_ID}${STATUS_SUFFIX}"
 
-  echo "\n▶️  Seed $seed"
+  echo "\n▶️  Case ${RESULT_ID}"
   echo "   ↙️  Download input: s3://${IO_BUCKET}/${INPUT_KEY}"
   aws s3 cp "s3://${IO_BUCKET}/${INPUT_KEY}" /tmp/input.txt
 
   echo "   ⚙️  Running program..."
+  set +e
   /tmp/main < /tmp/input.txt > /tmp/output.txt 2> /tmp/error.txt
+  echo "$?" > /tmp/status.txt
+  set -e
 
   echo "   ↗️  Upload stdout: s3://${IO_BUCKET}/${OUTPUT_KEY}"
   aws s3 cp /tmp/output.txt "s3://${IO_BUCKET}/${OUTPUT_KEY}"
 
   echo "   ↗️  Upload stderr: s3://${IO_BUCKET}/${ERROR_KEY}"
   aws s3 cp /tmp/error.txt "s3://${IO_BUCKET}/${ERROR_KEY}"
-  echo "   ✅ Done seed $seed"
+
+  # 終了コードは最後に置く (これがあれば stdout / stderr はアップロード済み)
+  echo "   ↗️  Upload status: s3://${IO_BUCKET}/${STATUS_KEY} (exit $(cat /tmp/status.txt))"
+  aws s3 cp /tmp/status.txt "s3://${IO_BUCKET}/${STATUS_KEY}"
+  echo "   ✅ Done case ${RESULT_ID}"
 done
 
-echo "🏁 All seeds processed successfully." 
\ No newline at end of file
+echo "🏁 All cases processed successfully." 
\ No newline at end of file
@@ -0,0 +1,30 @@
+#!/usr/bin/env bash
+# s3_lifecycle.sh: AWSRunner の実行ごとの名前空間 (runs/) を期限切れにするライフサイクルルールを設定する
+# AWSRunner は実行後に runs/<run_id>/ を削除するが、途中で失敗した実行の残骸はここで掃除される
+set -euo pipefail
+
+# .env があれば読み込む（ディレクトリ直下想定）
+if [ -f "$(dirname "$0")/.env" ]; then
+  set -a
+  source "$(dirname "$0")/.env"
+  set +a
+fi
+
+BUCKET="${HEURS_S3_BUCKET:-heurs-fs}"
+EXPIRE_DAYS="${HEURS_S3_RUN_EXPIRE_DAYS:-1}"
+
+read -r -d '' LIFECYCLE_JSON <<JSON || true
+{
+  "Rules": [
+    {
+      "ID": "expire-heurs-runs",
+      "Filter": { "Prefix": "runs/" },
+      "Status": "Enabled",
+      "Expiration": { "Days": ${EXPIRE_DAYS} }
+    }
+  ]
+}
+JSON
+
+aws s3api put-bucket-lifecycle-configuration --bucket "${BUCKET}" \
+  --lifecycle-configuration "${LIFECYCLE_JSON}"